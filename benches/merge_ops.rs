@@ -0,0 +1,123 @@
+//! Benchmarks for the merge based set operations, for different size ratios of the inputs.
+//!
+//! Besides the timings, this checks that the in place operations do not allocate when the result fits into
+//! the existing allocation, so an allocation regression fails the benchmark run.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use stats_alloc::{Region, StatsAlloc, INSTRUMENTED_SYSTEM};
+use std::alloc::System;
+use vec_collections::{SortedIterator, VecSet};
+
+#[global_allocator]
+static GLOBAL: &StatsAlloc<System> = &INSTRUMENTED_SYSTEM;
+
+type TestSet = VecSet<[u64; 4]>;
+
+/// A set of `large` elements, and a set of `small` elements spread over the same range, half of which are
+/// contained in the large set.
+fn sets(large: u64, small: u64) -> (TestSet, TestSet) {
+    let step = large * 2 / small;
+    let a = (0..large).map(|x| x * 2).collect();
+    let b = (0..small).map(|x| x * step + x % 2).collect();
+    (a, b)
+}
+
+fn check_in_place_allocations() {
+    let (a, b) = sets(1000, 100);
+    let mut x = a.clone();
+    let region = Region::new(GLOBAL);
+    // intersection and difference never grow the set
+    x.intersection_with(&b);
+    x.difference_with(&b);
+    x &= &a;
+    x -= &b;
+    assert_eq!(
+        region.change().allocations,
+        0,
+        "in place operations that shrink the set must not allocate"
+    );
+}
+
+const RATIOS: [u64; 4] = [1, 10, 100, 1000];
+
+fn union(c: &mut Criterion) {
+    check_in_place_allocations();
+    let mut group = c.benchmark_group("union");
+    for ratio in RATIOS.iter() {
+        let (a, b) = sets(10000, 10000 / ratio);
+        group.bench_with_input(
+            BenchmarkId::new("allocate", ratio),
+            &(&a, &b),
+            |bencher, (a, b)| bencher.iter(|| black_box(*a) | black_box(*b)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("in place", ratio),
+            &(&a, &b),
+            |bencher, (a, b)| {
+                bencher.iter_batched(
+                    || (*a).clone(),
+                    |mut a| {
+                        a.union_with(black_box(*b));
+                        a
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+        // a linear merge that compares every element, to compare with the minimum comparison merge
+        group.bench_with_input(
+            BenchmarkId::new("linear", ratio),
+            &(&a, &b),
+            |bencher, (a, b)| {
+                bencher.iter(|| {
+                    black_box(*a)
+                        .iter()
+                        .union(black_box(*b).iter())
+                        .cloned()
+                        .collect::<Vec<_>>()
+                })
+            },
+        );
+    }
+}
+
+fn intersection(c: &mut Criterion) {
+    let mut group = c.benchmark_group("intersection");
+    for ratio in RATIOS.iter() {
+        let (a, b) = sets(10000, 10000 / ratio);
+        group.bench_with_input(
+            BenchmarkId::new("allocate", ratio),
+            &(&a, &b),
+            |bencher, (a, b)| bencher.iter(|| black_box(*a) & black_box(*b)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("in place", ratio),
+            &(&a, &b),
+            |bencher, (a, b)| {
+                bencher.iter_batched(
+                    || (*a).clone(),
+                    |mut a| {
+                        a.intersection_with(black_box(*b));
+                        a
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("linear", ratio),
+            &(&a, &b),
+            |bencher, (a, b)| {
+                bencher.iter(|| {
+                    black_box(*a)
+                        .iter()
+                        .intersection(black_box(*b).iter())
+                        .cloned()
+                        .collect::<Vec<_>>()
+                })
+            },
+        );
+    }
+}
+
+criterion_group!(benches, union, intersection);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+//! Benchmarks for radix tree scans and combine operations.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use vec_collections::radix_tree::{AbstractRadixTree, AbstractRadixTreeMut, RadixTree};
+
+type TestTree = RadixTree<u8, u32>;
+
+/// A tree with keys that have long common prefixes, like paths or formatted ids
+fn tree(n: u32, offset: u32) -> TestTree {
+    (0..n)
+        .map(|i| (format!("item/{:08}", i * 2 + offset), i))
+        .collect()
+}
+
+fn scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("radix tree scan");
+    let t = tree(100000, 0);
+    group.bench_function("iter", |b| b.iter(|| black_box(&t).iter().count()));
+    group.bench_function("values", |b| b.iter(|| black_box(&t).values().count()));
+    group.bench_function("scan_prefix", |b| {
+        b.iter(|| black_box(&t).scan_prefix(b"item/0001").count())
+    });
+    group.bench_function("fold_prefix", |b| {
+        b.iter(|| black_box(&t).fold_prefix(b"item/0001", 0u64, |acc, v| acc + *v as u64))
+    });
+    group.bench_function("get", |b| b.iter(|| black_box(&t).get(b"item/00012346")));
+}
+
+fn combine(c: &mut Criterion) {
+    let mut group = c.benchmark_group("radix tree combine");
+    let a = tree(10000, 0);
+    let b = tree(10000, 1);
+    group.bench_function("union", |bencher| {
+        bencher.iter(|| black_box(&a).union(black_box(&b)))
+    });
+    group.bench_function("union_with", |bencher| {
+        bencher.iter_batched(
+            || a.clone(),
+            |mut a| {
+                a.union_with(black_box(&b));
+                a
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("intersection", |bencher| {
+        bencher.iter(|| black_box(&a).intersection(black_box(&b)))
+    });
+    group.bench_function("intersection_with", |bencher| {
+        bencher.iter_batched(
+            || a.clone(),
+            |mut a| {
+                a.intersection_with(black_box(&b));
+                a
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, scan, combine);
+criterion_main!(benches);
@@ -143,6 +143,40 @@ pub fn lookup_bench(c: &mut Criterion, title: &str, range: impl Iterator<Item =
         );
     }
 }
+/// Lookups in tiny sets in an unpredictable order, against a plain binary search
+///
+/// `VecSet::contains` scans sets with less than 16 elements without branches, and uses a binary search above that.
+/// This is to check where the cutover should be.
+pub fn lookup_unpredictable(c: &mut Criterion) {
+    use rand::Rng;
+    let mut group = c.benchmark_group("Lookup unpredictable");
+    let mut rand = rand::rngs::StdRng::from_seed([0u8; 32]);
+    for i in [1u32, 2, 4, 8, 16, 32] {
+        let coll: VecSet<[u32; 4]> = (0..i).map(|x| x * 2).collect();
+        // half of the lookups are misses
+        let lookup = (0..1000)
+            .map(|_| rand.gen_range(0, 2 * i))
+            .collect::<Vec<_>>();
+        group.bench_with_input(
+            BenchmarkId::new("VecSet<[u32; 4]> contains", i),
+            &(&coll, &lookup),
+            |b, coll| b.iter(|| vs_contains(black_box(coll.0), coll.1)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("[u32] binary_search", i),
+            &(&coll, &lookup),
+            |b, coll| {
+                b.iter(|| {
+                    let slice = black_box(coll.0.as_slice());
+                    coll.1
+                        .iter()
+                        .filter(|x| slice.binary_search(x).is_ok())
+                        .count()
+                })
+            },
+        );
+    }
+}
 pub fn lookup_medium(c: &mut Criterion) {
     lookup_bench(c, "medium", (10..=100).step_by(10))
 }
@@ -154,6 +188,7 @@ criterion_group!(
     benches,
     creation_small,
     lookup_small,
+    lookup_unpredictable,
     creation_medium,
     lookup_medium
 );
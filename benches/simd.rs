@@ -0,0 +1,37 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{Rng, SeedableRng};
+use vec_collections::{simd, VecSet};
+
+type TestSet = VecSet<[u64; 4]>;
+
+/// random ids, so that about half of the elements of the smaller set are also in the larger one
+fn random_set(rand: &mut impl Rng, n: usize, range: u64) -> TestSet {
+    (0..n).map(|_| rand.gen_range(0, range)).collect()
+}
+
+fn intersection(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Intersection u64");
+    let mut rand = rand::rngs::StdRng::from_seed([0u8; 32]);
+    for (n, m) in [
+        (100, 100),
+        (1000, 1000),
+        (10000, 10000),
+        (10, 10000),
+        (100, 100000),
+    ] {
+        let a = random_set(&mut rand, n, 2 * n as u64);
+        let b = random_set(&mut rand, m, 2 * n as u64);
+        let id = format!("{}x{}", n, m);
+        group.bench_with_input(
+            BenchmarkId::new("merge", &id),
+            &(&a, &b),
+            |bench, (a, b)| bench.iter(|| black_box(*a) & black_box(*b)),
+        );
+        group.bench_with_input(BenchmarkId::new("simd", &id), &(&a, &b), |bench, (a, b)| {
+            bench.iter(|| simd::intersection::<u64, [u64; 4]>(black_box(a), black_box(b)))
+        });
+    }
+}
+
+criterion_group!(benches, intersection);
+criterion_main!(benches);
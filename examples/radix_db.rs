@@ -26,11 +26,19 @@ use rkyv::{
         SharedSerializeRegistry,
     },
     validation::validators::DefaultValidator,
-    AlignedVec, Archived, Deserialize, Fallible, Serialize,
-};
-use vec_collections::radix_tree::{
-    AbstractRadixTree, AbstractRadixTreeMut, ArcRadixTree, TKey, TValue,
+    AlignedVec, Archive, Archived, Deserialize, Fallible, Serialize,
 };
+use vec_collections::radix_tree::{self, AbstractRadixTree, AbstractRadixTreeMut, ArcRadixTree};
+
+/// Key components need to be archivable and shareable between threads to be stored in the db
+trait TKey: radix_tree::TArchivedKey + std::fmt::Debug + Send + Sync {}
+
+impl<T: radix_tree::TArchivedKey + std::fmt::Debug + Send + Sync> TKey for T {}
+
+/// Values need to be archivable and shareable between threads to be stored in the db
+trait TValue: radix_tree::TValue + std::fmt::Debug + Archive + Send + Sync {}
+
+impl<T: radix_tree::TValue + std::fmt::Debug + Archive + Send + Sync> TValue for T {}
 
 struct Batch<K: TKey, V: TValue> {
     v0: ArcRadixTree<K, V>,
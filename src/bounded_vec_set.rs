@@ -0,0 +1,226 @@
+use crate::{AbstractVecSet, VecSet};
+use core::{fmt, ops::Deref};
+use smallvec::Array;
+
+/// A [VecSet] that keeps at most a fixed number of the smallest or largest elements.
+///
+/// The bound is maintained by every operation, so this can be used e.g. for a leaderboard or a set of nearest
+/// neighbour candidates, without truncating the set after every operation. All read only operations of [VecSet]
+/// are available via [Deref].
+///
+/// # Example
+/// ```
+/// use vec_collections::{BoundedVecSet, VecSet};
+/// let mut top: BoundedVecSet<[u32; 4]> = BoundedVecSet::largest(3);
+/// top.extend(vec![5, 1, 9, 3]);
+/// assert_eq!(top.as_slice(), &[3, 5, 9]);
+/// top.union_with(&vec![4, 10].into_iter().collect::<VecSet<[u32; 4]>>());
+/// assert_eq!(top.as_slice(), &[5, 9, 10]);
+/// assert!(!top.insert(2));
+/// ```
+pub struct BoundedVecSet<A: Array> {
+    elements: VecSet<A>,
+    /// the maximum number of elements
+    bound: usize,
+    /// true to keep the largest elements, false to keep the smallest elements
+    largest: bool,
+}
+
+impl<A: Array> BoundedVecSet<A> {
+    /// An empty set that keeps at most the `bound` smallest elements
+    pub fn smallest(bound: usize) -> Self {
+        Self {
+            elements: VecSet::empty(),
+            bound,
+            largest: false,
+        }
+    }
+
+    /// An empty set that keeps at most the `bound` largest elements
+    pub fn largest(bound: usize) -> Self {
+        Self {
+            elements: VecSet::empty(),
+            bound,
+            largest: true,
+        }
+    }
+
+    /// The maximum number of elements
+    pub fn bound(&self) -> usize {
+        self.bound
+    }
+
+    /// True if the set contains the maximum number of elements
+    pub fn is_full(&self) -> bool {
+        self.elements.len() >= self.bound
+    }
+
+    /// The elements as a [VecSet]
+    pub fn as_set(&self) -> &VecSet<A> {
+        &self.elements
+    }
+
+    /// Returns the elements as a [VecSet]
+    pub fn into_set(self) -> VecSet<A> {
+        self.elements
+    }
+
+    /// Drop the elements that exceed the bound
+    fn enforce_bound(&mut self) {
+        if self.largest {
+            self.elements.truncate_front(self.bound)
+        } else {
+            self.elements.truncate(self.bound)
+        }
+    }
+
+    /// The part of a sorted slice that can possibly end up in the set
+    fn candidates<'a, T>(&self, elements: &'a [T]) -> &'a [T] {
+        let n = elements.len().min(self.bound);
+        if self.largest {
+            &elements[elements.len() - n..]
+        } else {
+            &elements[..n]
+        }
+    }
+}
+
+impl<T: Ord, A: Array<Item = T>> BoundedVecSet<A> {
+    /// Insert an element, evicting the smallest or largest element if the bound is exceeded.
+    ///
+    /// Returns true if the set was changed, false if the element was already present or does not fit.
+    pub fn insert(&mut self, value: T) -> bool {
+        if self.is_full() {
+            let fits = if self.largest {
+                self.elements.first().is_some_and(|min| &value > min)
+            } else {
+                self.elements.last().is_some_and(|max| &value < max)
+            };
+            if !fits {
+                return false;
+            }
+        }
+        let res = self.elements.insert(value);
+        self.enforce_bound();
+        res
+    }
+
+    /// In place intersection with another set. This can only shrink the set, so it never evicts.
+    pub fn intersection_with(&mut self, that: &impl AbstractVecSet<T>) {
+        self.elements.intersection_with(that)
+    }
+
+    /// In place difference with another set. This can only shrink the set, so it never evicts.
+    pub fn difference_with(&mut self, that: &impl AbstractVecSet<T>) {
+        self.elements.difference_with(that)
+    }
+}
+
+impl<T: Ord + Clone, A: Array<Item = T>> BoundedVecSet<A> {
+    /// In place union with another set, keeping only the elements within the bound.
+    ///
+    /// Only the part of `that` that can end up in the result is merged.
+    pub fn union_with(&mut self, that: &impl AbstractVecSet<T>) {
+        let candidates = self.candidates(that.as_slice());
+        self.elements.union_with(&candidates);
+        self.enforce_bound();
+    }
+}
+
+impl<T: Ord, A: Array<Item = T>> Extend<T> for BoundedVecSet<A> {
+    /// Adds the elements in batches, so the intermediate storage is bounded by the bound of the set.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let batch_size = self.bound.max(16);
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut iter = iter.into_iter();
+        loop {
+            batch.extend(iter.by_ref().take(batch_size));
+            if batch.is_empty() {
+                break;
+            }
+            self.elements.union_with_iter(batch.drain(..));
+            self.enforce_bound();
+        }
+    }
+}
+
+impl<A: Array> Deref for BoundedVecSet<A> {
+    type Target = VecSet<A>;
+
+    fn deref(&self) -> &VecSet<A> {
+        &self.elements
+    }
+}
+
+impl<T: Clone, A: Array<Item = T>> Clone for BoundedVecSet<A> {
+    fn clone(&self) -> Self {
+        Self {
+            elements: self.elements.clone(),
+            bound: self.bound,
+            largest: self.largest,
+        }
+    }
+}
+
+impl<T: fmt::Debug, A: Array<Item = T>> fmt::Debug for BoundedVecSet<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.elements.fmt(f)
+    }
+}
+
+impl<T: Ord, A: Array<Item = T>> From<BoundedVecSet<A>> for VecSet<A> {
+    fn from(value: BoundedVecSet<A>) -> Self {
+        value.elements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+    use std::collections::BTreeSet;
+
+    type Test = BoundedVecSet<[i64; 2]>;
+
+    fn reference(elements: &BTreeSet<i64>, bound: usize, largest: bool) -> Vec<i64> {
+        if largest {
+            let mut res: Vec<i64> = elements.iter().rev().take(bound).cloned().collect();
+            res.reverse();
+            res
+        } else {
+            elements.iter().take(bound).cloned().collect()
+        }
+    }
+
+    fn new(bound: usize, largest: bool) -> Test {
+        if largest {
+            Test::largest(bound)
+        } else {
+            Test::smallest(bound)
+        }
+    }
+
+    quickcheck! {
+        fn insert_extend(values: Vec<i64>, bound: u8, largest: bool) -> bool {
+            let bound = usize::from(bound % 8);
+            let all: BTreeSet<i64> = values.iter().cloned().collect();
+            let expected = reference(&all, bound, largest);
+            let mut inserted = new(bound, largest);
+            for value in values.iter() {
+                inserted.insert(*value);
+            }
+            let mut extended = new(bound, largest);
+            extended.extend(values);
+            inserted.as_slice() == &expected[..] && extended.as_slice() == &expected[..]
+        }
+
+        fn union_with(a: BTreeSet<i64>, b: BTreeSet<i64>, bound: u8, largest: bool) -> bool {
+            let bound = usize::from(bound % 8);
+            let expected = reference(&a.union(&b).cloned().collect(), bound, largest);
+            let mut actual = new(bound, largest);
+            actual.extend(a);
+            actual.union_with(&b.into_iter().collect::<VecSet<[i64; 2]>>());
+            actual.as_slice() == &expected[..] && actual.len() <= bound
+        }
+    }
+}
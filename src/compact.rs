@@ -0,0 +1,719 @@
+//! A compact, length-prefixed binary encoding for the collections in this crate.
+//!
+//! This is independent of serde, so the layout will not change when a serde data format changes.
+//! It is meant for embedding collections into your own file format.
+//!
+//! All integers are written as LEB128 varints. Signed integers are zigzag encoded. The elements of a
+//! [VecSet] and the keys of a [VecMap] are delta encoded, which is very compact for dense sets of integers.
+//!
+//! # Layout
+//!
+//! - `VecSet`: `len`, first element, then for each following element `element - previous - 1`
+//! - `VecMap`: `len`, then for each entry the delta encoded key followed by the encoded value
+//!
+//! # Example
+//! ```
+//! use vec_collections::{compact::CompactEncoding, VecSet};
+//! let a: VecSet<[u64; 4]> = (1000..2000).collect();
+//! let bytes = a.to_compact_bytes();
+//! // 2 bytes for the length, 2 bytes for the first element, 1 byte per following element
+//! assert_eq!(bytes.len(), 1003);
+//! let b: VecSet<[u64; 4]> = VecSet::from_compact_bytes(&bytes).unwrap();
+//! assert_eq!(a, b);
+//! ```
+//!
+//! [VecSet]: ../struct.VecSet.html
+//! [VecMap]: ../struct.VecMap.html
+use crate::{VecMap, VecSet};
+use core::{convert::TryFrom, fmt};
+use smallvec::{Array, SmallVec};
+
+/// Error when decoding the compact binary encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactError {
+    /// the input ended before the value was complete
+    UnexpectedEof,
+    /// a varint or a delta did not fit into the target type
+    Overflow,
+    /// a value was not valid for the target type, e.g. a bool that is neither 0 nor 1
+    InvalidValue,
+    /// there were bytes left after decoding a value
+    TrailingBytes,
+}
+
+impl std::error::Error for CompactError {}
+
+impl fmt::Display for CompactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Write a u64 as a LEB128 varint
+pub fn write_varint(mut value: u64, target: &mut Vec<u8>) {
+    while value >= 0x80 {
+        target.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    target.push(value as u8);
+}
+
+/// Read a LEB128 varint, advancing the source slice
+pub fn read_varint(source: &mut &[u8]) -> Result<u64, CompactError> {
+    let mut result = 0u64;
+    let mut shift = 0;
+    loop {
+        let (byte, rest) = source.split_first().ok_or(CompactError::UnexpectedEof)?;
+        *source = rest;
+        let bits = u64::from(byte & 0x7f);
+        if shift == 63 && bits > 1 || shift > 63 {
+            return Err(CompactError::Overflow);
+        }
+        result |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+/// A type that can be written to and read from the compact binary encoding
+pub trait CompactEncoding: Sized {
+    /// Append the encoded value to `target`
+    fn write_compact(&self, target: &mut Vec<u8>);
+
+    /// Read a value from the front of `source`, advancing it
+    fn read_compact(source: &mut &[u8]) -> Result<Self, CompactError>;
+
+    /// Encode the value into a new byte vec
+    fn to_compact_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        self.write_compact(&mut res);
+        res
+    }
+
+    /// Decode a value from a byte slice, which must contain exactly one value
+    fn from_compact_bytes(bytes: &[u8]) -> Result<Self, CompactError> {
+        let mut source = bytes;
+        let res = Self::read_compact(&mut source)?;
+        if !source.is_empty() {
+            return Err(CompactError::TrailingBytes);
+        }
+        Ok(res)
+    }
+}
+
+/// An ordered type that can be mapped to an u64 in an order preserving way
+///
+/// This is used for delta encoding the elements of sets and the keys of maps.
+pub trait CompactKey: Ord + Copy {
+    /// Order preserving conversion to u64
+    fn to_ordered_u64(self) -> u64;
+    /// Inverse of [to_ordered_u64](CompactKey::to_ordered_u64). Returns None if the value is out of range.
+    fn from_ordered_u64(value: u64) -> Option<Self>;
+}
+
+macro_rules! unsigned_impl {
+    ($($t:ty),*) => {$(
+        impl CompactKey for $t {
+            fn to_ordered_u64(self) -> u64 {
+                self as u64
+            }
+            fn from_ordered_u64(value: u64) -> Option<Self> {
+                if value <= <$t>::max_value() as u64 {
+                    Some(value as $t)
+                } else {
+                    None
+                }
+            }
+        }
+
+        impl CompactEncoding for $t {
+            fn write_compact(&self, target: &mut Vec<u8>) {
+                write_varint(*self as u64, target)
+            }
+            fn read_compact(source: &mut &[u8]) -> Result<Self, CompactError> {
+                Self::from_ordered_u64(read_varint(source)?).ok_or(CompactError::Overflow)
+            }
+        }
+    )*};
+}
+
+macro_rules! signed_impl {
+    ($($t:ty),*) => {$(
+        impl CompactKey for $t {
+            fn to_ordered_u64(self) -> u64 {
+                (self as i64 as u64) ^ (1 << 63)
+            }
+            fn from_ordered_u64(value: u64) -> Option<Self> {
+                let value = (value ^ (1 << 63)) as i64;
+                if value >= <$t>::min_value() as i64 && value <= <$t>::max_value() as i64 {
+                    Some(value as $t)
+                } else {
+                    None
+                }
+            }
+        }
+
+        impl CompactEncoding for $t {
+            fn write_compact(&self, target: &mut Vec<u8>) {
+                // zigzag encoding, so small negative numbers stay small
+                let value = *self as i64;
+                write_varint(((value << 1) ^ (value >> 63)) as u64, target)
+            }
+            fn read_compact(source: &mut &[u8]) -> Result<Self, CompactError> {
+                let value = read_varint(source)?;
+                let value = ((value >> 1) as i64) ^ -((value & 1) as i64);
+                if value >= <$t>::min_value() as i64 && value <= <$t>::max_value() as i64 {
+                    Ok(value as $t)
+                } else {
+                    Err(CompactError::Overflow)
+                }
+            }
+        }
+    )*};
+}
+
+unsigned_impl!(u8, u16, u32, u64, usize);
+signed_impl!(i8, i16, i32, i64, isize);
+
+impl CompactEncoding for bool {
+    fn write_compact(&self, target: &mut Vec<u8>) {
+        target.push(*self as u8)
+    }
+    fn read_compact(source: &mut &[u8]) -> Result<Self, CompactError> {
+        match read_varint(source)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(CompactError::InvalidValue),
+        }
+    }
+}
+
+impl CompactEncoding for () {
+    fn write_compact(&self, _: &mut Vec<u8>) {}
+    fn read_compact(_: &mut &[u8]) -> Result<Self, CompactError> {
+        Ok(())
+    }
+}
+
+/// Delta encode a strictly sorted sequence of keys
+struct DeltaWriter(Option<u64>);
+
+impl DeltaWriter {
+    fn write<K: CompactKey>(&mut self, key: K, target: &mut Vec<u8>) {
+        let value = key.to_ordered_u64();
+        let delta = match self.0 {
+            // strictly sorted, so the difference is at least 1
+            Some(prev) => value - prev - 1,
+            None => value,
+        };
+        write_varint(delta, target);
+        self.0 = Some(value);
+    }
+}
+
+/// Decode a delta encoded sequence of keys. Strict order is guaranteed by construction.
+struct DeltaReader(Option<u64>);
+
+impl DeltaReader {
+    fn read<K: CompactKey>(&mut self, source: &mut &[u8]) -> Result<K, CompactError> {
+        let delta = read_varint(source)?;
+        let value = match self.0 {
+            Some(prev) => prev
+                .checked_add(delta)
+                .and_then(|x| x.checked_add(1))
+                .ok_or(CompactError::Overflow)?,
+            None => delta,
+        };
+        self.0 = Some(value);
+        K::from_ordered_u64(value).ok_or(CompactError::Overflow)
+    }
+}
+
+/// read a length prefix
+pub(crate) fn read_len(source: &mut &[u8]) -> Result<usize, CompactError> {
+    usize::try_from(read_varint(source)?).map_err(|_| CompactError::Overflow)
+}
+
+impl<A: Array> CompactEncoding for VecSet<A>
+where
+    A::Item: CompactKey,
+{
+    fn write_compact(&self, target: &mut Vec<u8>) {
+        write_varint(self.len() as u64, target);
+        let mut writer = DeltaWriter(None);
+        for x in self.iter() {
+            writer.write(*x, target);
+        }
+    }
+
+    fn read_compact(source: &mut &[u8]) -> Result<Self, CompactError> {
+        let len = read_len(source)?;
+        let mut reader = DeltaReader(None);
+        // each element takes at least one byte
+        let mut res: SmallVec<A> = SmallVec::with_capacity(len.min(source.len()));
+        for _ in 0..len {
+            res.push(reader.read(source)?);
+        }
+        Ok(Self::new_unsafe(res))
+    }
+}
+
+impl<K, V, A> CompactEncoding for VecMap<A>
+where
+    A: Array<Item = (K, V)>,
+    K: CompactKey,
+    V: CompactEncoding,
+{
+    fn write_compact(&self, target: &mut Vec<u8>) {
+        write_varint(self.len() as u64, target);
+        let mut writer = DeltaWriter(None);
+        for (k, v) in self.iter() {
+            writer.write(*k, target);
+            v.write_compact(target);
+        }
+    }
+
+    fn read_compact(source: &mut &[u8]) -> Result<Self, CompactError> {
+        let len = read_len(source)?;
+        let mut reader = DeltaReader(None);
+        // each entry takes at least one byte for the key
+        let mut res: SmallVec<A> = SmallVec::with_capacity(len.min(source.len()));
+        for _ in 0..len {
+            let k = reader.read(source)?;
+            let v = V::read_compact(source)?;
+            res.push((k, v));
+        }
+        Ok(Self::new(res))
+    }
+}
+
+#[cfg(feature = "rkyv_validated")]
+pub use archived::ArchivedDeltaVecSetError;
+#[cfg(feature = "rkyv")]
+pub use archived::{ArchivedDeltaVecSet, ArchivedDeltaVecSetIter, DeltaEncoded};
+
+#[cfg(feature = "rkyv")]
+mod archived {
+    use super::{read_varint, write_varint, CompactKey};
+    use crate::VecSet;
+    use core::marker::PhantomData;
+    use rkyv::{
+        out_field,
+        ser::{ScratchSpace, Serializer},
+        vec::{ArchivedVec, VecResolver},
+        with::{ArchiveWith, DeserializeWith, SerializeWith},
+        Archive, Fallible,
+    };
+    use smallvec::{Array, SmallVec};
+
+    /// number of elements per block. The first element of each block is stored in the skip table.
+    ///
+    /// Block boundaries are computed with `%` and `/` instead of `is_multiple_of` and `div_ceil`, which would raise the
+    /// minimum supported rust version, hence the `manual_is_multiple_of` and `manual_div_ceil` allows below.
+    const BLOCK_SIZE: usize = 64;
+
+    /// rkyv wrapper to archive a [VecSet] of integers using delta and varint encoding.
+    ///
+    /// This is opt in per field, using `#[with(DeltaEncoded)]`. Lookup in the archived set is done by
+    /// binary search in a small skip table, followed by a linear scan of at most 64 elements.
+    ///
+    /// [VecSet]: ../struct.VecSet.html
+    pub struct DeltaEncoded;
+
+    /// An archived [VecSet] of integers, stored as delta and varint encoded bytes.
+    ///
+    /// [VecSet]: ../struct.VecSet.html
+    #[repr(C)]
+    pub struct ArchivedDeltaVecSet<T> {
+        /// the encoded elements. The first element of each block is encoded as absolute value.
+        data: ArchivedVec<u8>,
+        /// for each block, the ordered value of the first element and the byte offset in data
+        skip: ArchivedVec<[u64; 2]>,
+        /// number of elements
+        len: u64,
+        _t: PhantomData<T>,
+    }
+
+    pub struct ArchivedDeltaVecSetResolver {
+        data: VecResolver,
+        data_len: usize,
+        skip: VecResolver,
+        skip_len: usize,
+    }
+
+    impl<T: CompactKey> ArchivedDeltaVecSet<T> {
+        /// The number of elements in the set
+        pub fn len(&self) -> usize {
+            self.len as usize
+        }
+
+        /// true if the set is empty
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        /// An iterator that decodes the elements of this set in sorted order
+        pub fn iter(&self) -> ArchivedDeltaVecSetIter<'_, T> {
+            ArchivedDeltaVecSetIter::new(&self.data, 0, self.len())
+        }
+
+        /// true if the set contains the value.
+        ///
+        /// Time complexity is O(log N) for the skip table plus a scan of at most one block.
+        pub fn contains(&self, value: &T) -> bool {
+            let value = value.to_ordered_u64();
+            let block = match self.skip.binary_search_by_key(&value, |x| x[0]) {
+                Ok(_) => return true,
+                Err(0) => return false,
+                Err(i) => i - 1,
+            };
+            let offset = self.skip[block][1] as usize;
+            let index = block * BLOCK_SIZE;
+            let remaining = BLOCK_SIZE.min(self.len() - index);
+            let mut iter =
+                ArchivedDeltaVecSetIter::<T>::new(&self.data[offset..], index, remaining);
+            while let Some(x) = iter.next_ordered() {
+                if x >= value {
+                    return x == value;
+                }
+            }
+            false
+        }
+    }
+
+    /// Iterator over the elements of an [ArchivedDeltaVecSet]
+    pub struct ArchivedDeltaVecSetIter<'a, T> {
+        data: &'a [u8],
+        index: usize,
+        remaining: usize,
+        prev: u64,
+        _t: PhantomData<T>,
+    }
+
+    impl<'a, T: CompactKey> ArchivedDeltaVecSetIter<'a, T> {
+        fn new(data: &'a [u8], index: usize, remaining: usize) -> Self {
+            Self {
+                data,
+                index,
+                remaining,
+                prev: 0,
+                _t: PhantomData,
+            }
+        }
+
+        #[allow(clippy::manual_is_multiple_of)]
+        fn next_ordered(&mut self) -> Option<u64> {
+            if self.remaining == 0 {
+                return None;
+            }
+            let delta = read_varint(&mut self.data).ok()?;
+            let value = if self.index % BLOCK_SIZE == 0 {
+                delta
+            } else {
+                self.prev.checked_add(delta)?.checked_add(1)?
+            };
+            self.prev = value;
+            self.index += 1;
+            self.remaining -= 1;
+            Some(value)
+        }
+    }
+
+    impl<'a, T: CompactKey> Iterator for ArchivedDeltaVecSetIter<'a, T> {
+        type Item = T;
+
+        fn next(&mut self) -> Option<T> {
+            T::from_ordered_u64(self.next_ordered()?)
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (0, Some(self.remaining))
+        }
+    }
+
+    impl<'a, T: CompactKey> sorted_iter::sorted_iterator::SortedByItem
+        for ArchivedDeltaVecSetIter<'a, T>
+    {
+    }
+
+    impl<A: Array> ArchiveWith<VecSet<A>> for DeltaEncoded
+    where
+        A::Item: CompactKey,
+    {
+        type Archived = ArchivedDeltaVecSet<A::Item>;
+        type Resolver = ArchivedDeltaVecSetResolver;
+
+        unsafe fn resolve_with(
+            field: &VecSet<A>,
+            pos: usize,
+            resolver: Self::Resolver,
+            out: *mut Self::Archived,
+        ) {
+            let (fp, fo) = out_field!(out.data);
+            ArchivedVec::resolve_from_len(resolver.data_len, pos + fp, resolver.data, fo);
+            let (fp, fo) = out_field!(out.skip);
+            ArchivedVec::resolve_from_len(resolver.skip_len, pos + fp, resolver.skip, fo);
+            let (fp, fo) = out_field!(out.len);
+            (field.len() as u64).resolve(pos + fp, (), fo);
+        }
+    }
+
+    impl<S, A> SerializeWith<VecSet<A>, S> for DeltaEncoded
+    where
+        A: Array,
+        A::Item: CompactKey,
+        S: ScratchSpace + Serializer + ?Sized,
+    {
+        fn serialize_with(
+            field: &VecSet<A>,
+            serializer: &mut S,
+        ) -> Result<Self::Resolver, S::Error> {
+            let (data, skip) = encode(field);
+            // remember the lengths, so resolving does not have to encode again
+            Ok(ArchivedDeltaVecSetResolver {
+                data: ArchivedVec::serialize_from_slice(&data, serializer)?,
+                data_len: data.len(),
+                skip: ArchivedVec::serialize_from_slice(&skip, serializer)?,
+                skip_len: skip.len(),
+            })
+        }
+    }
+
+    impl<D, A> DeserializeWith<ArchivedDeltaVecSet<A::Item>, VecSet<A>, D> for DeltaEncoded
+    where
+        A: Array,
+        A::Item: CompactKey,
+        D: Fallible + ?Sized,
+    {
+        fn deserialize_with(
+            field: &ArchivedDeltaVecSet<A::Item>,
+            _: &mut D,
+        ) -> Result<VecSet<A>, D::Error> {
+            let items: SmallVec<A> = field.iter().collect();
+            Ok(VecSet::new_unsafe(items))
+        }
+    }
+
+    /// encode the elements of a set into the data bytes and the skip table
+    #[allow(clippy::manual_is_multiple_of, clippy::manual_div_ceil)]
+    fn encode<A: Array>(set: &VecSet<A>) -> (Vec<u8>, Vec<[u64; 2]>)
+    where
+        A::Item: CompactKey,
+    {
+        let mut data = Vec::new();
+        let mut skip = Vec::with_capacity((set.len() + BLOCK_SIZE - 1) / BLOCK_SIZE);
+        let mut prev = 0;
+        for (i, x) in set.iter().enumerate() {
+            let value = x.to_ordered_u64();
+            if i % BLOCK_SIZE == 0 {
+                skip.push([value, data.len() as u64]);
+                write_varint(value, &mut data);
+            } else {
+                write_varint(value - prev - 1, &mut data);
+            }
+            prev = value;
+        }
+        (data, skip)
+    }
+
+    #[cfg(feature = "rkyv_validated")]
+    pub use validation_support::ArchivedDeltaVecSetError;
+
+    #[cfg(feature = "rkyv_validated")]
+    mod validation_support {
+        use super::{ArchivedDeltaVecSet, CompactKey, BLOCK_SIZE};
+        use bytecheck::CheckBytes;
+        use core::{convert::TryFrom, fmt};
+        use rkyv::{validation::ArchiveContext, vec::ArchivedVec};
+
+        /// Validation error for a delta encoded vec set
+        #[derive(Debug)]
+        pub enum ArchivedDeltaVecSetError {
+            /// error with the encoded data or the skip table
+            ValueCheckError,
+            /// the encoded data does not match the length or the skip table
+            EncodingError,
+        }
+
+        impl std::error::Error for ArchivedDeltaVecSetError {}
+
+        impl std::fmt::Display for ArchivedDeltaVecSetError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{:?}", self)
+            }
+        }
+
+        impl<C, T> CheckBytes<C> for ArchivedDeltaVecSet<T>
+        where
+            C: ?Sized + ArchiveContext,
+            C::Error: std::error::Error,
+            T: CompactKey,
+        {
+            type Error = ArchivedDeltaVecSetError;
+            #[allow(clippy::manual_is_multiple_of)]
+            unsafe fn check_bytes<'a>(
+                value: *const Self,
+                context: &mut C,
+            ) -> Result<&'a Self, Self::Error> {
+                let this = &*value;
+                ArchivedVec::<u8>::check_bytes(&this.data, context)
+                    .map_err(|_| ArchivedDeltaVecSetError::ValueCheckError)?;
+                ArchivedVec::<[u64; 2]>::check_bytes(&this.skip, context)
+                    .map_err(|_| ArchivedDeltaVecSetError::ValueCheckError)?;
+                // the length comes from the archive, so it must neither be truncated nor overflow
+                let len = usize::try_from(this.len)
+                    .map_err(|_| ArchivedDeltaVecSetError::EncodingError)?;
+                if this.skip.len() != len / BLOCK_SIZE + (len % BLOCK_SIZE != 0) as usize {
+                    return Err(ArchivedDeltaVecSetError::EncodingError);
+                }
+                // decode everything to make sure that lookups will work
+                let mut iter = this.iter();
+                let mut offset = 0;
+                let mut prev = None;
+                for i in 0..len {
+                    if i % BLOCK_SIZE == 0 {
+                        offset = this.data.len() - iter.data.len();
+                    }
+                    let value = iter
+                        .next_ordered()
+                        .ok_or(ArchivedDeltaVecSetError::EncodingError)?;
+                    if T::from_ordered_u64(value).is_none()
+                        || prev.map(|prev| prev >= value).unwrap_or_default()
+                        || (i % BLOCK_SIZE == 0
+                            && this.skip[i / BLOCK_SIZE] != [value, offset as u64])
+                    {
+                        return Err(ArchivedDeltaVecSetError::EncodingError);
+                    }
+                    prev = Some(value);
+                }
+                if !iter.data.is_empty() {
+                    return Err(ArchivedDeltaVecSetError::EncodingError);
+                }
+                Ok(this)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VecMap1, VecSet2};
+    use quickcheck::quickcheck;
+
+    #[cfg(feature = "rkyv")]
+    #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+    #[cfg_attr(
+        feature = "rkyv_validated",
+        archive_attr(derive(bytecheck::CheckBytes))
+    )]
+    struct DeltaTest {
+        #[with(DeltaEncoded)]
+        set: VecSet2<u64>,
+    }
+
+    #[cfg(feature = "rkyv")]
+    fn delta_archive(set: VecSet2<u64>) -> Vec<u8> {
+        use rkyv::ser::{serializers::AllocSerializer, Serializer};
+        let mut serializer = AllocSerializer::<256>::default();
+        serializer.serialize_value(&DeltaTest { set }).unwrap();
+        serializer.into_serializer().into_inner().to_vec()
+    }
+
+    quickcheck! {
+        fn varint_roundtrip(x: u64) -> bool {
+            let mut bytes = Vec::new();
+            write_varint(x, &mut bytes);
+            let mut source = bytes.as_ref();
+            read_varint(&mut source) == Ok(x) && source.is_empty()
+        }
+
+        fn i32_roundtrip(x: i32) -> bool {
+            i32::from_compact_bytes(&x.to_compact_bytes()) == Ok(x)
+        }
+
+        fn set_roundtrip_u64(x: Vec<u64>) -> bool {
+            let set: VecSet2<u64> = x.into_iter().collect();
+            VecSet2::<u64>::from_compact_bytes(&set.to_compact_bytes()) == Ok(set)
+        }
+
+        fn set_roundtrip_i16(x: Vec<i16>) -> bool {
+            let set: VecSet2<i16> = x.into_iter().collect();
+            VecSet2::<i16>::from_compact_bytes(&set.to_compact_bytes()) == Ok(set)
+        }
+
+        fn map_roundtrip(x: Vec<(i64, u32)>) -> bool {
+            let map: VecMap1<i64, u32> = x.into_iter().collect();
+            VecMap1::<i64, u32>::from_compact_bytes(&map.to_compact_bytes()) == Ok(map)
+        }
+
+        #[cfg(feature = "rkyv")]
+        fn delta_archive_roundtrip(x: Vec<u64>, probe: Vec<u64>) -> bool {
+            use rkyv::{Deserialize, Infallible};
+            let set: VecSet2<u64> = x.into_iter().collect();
+            let bytes = delta_archive(set.clone());
+            let archived = unsafe { rkyv::archived_root::<DeltaTest>(&bytes) };
+            let deserialized: DeltaTest = archived.deserialize(&mut Infallible).unwrap();
+            archived.set.len() == set.len()
+                && archived.set.iter().eq(set.iter().cloned())
+                && probe.iter().chain(set.iter()).all(|x| archived.set.contains(x) == set.contains(x))
+                && deserialized.set == set
+        }
+
+        #[cfg(feature = "rkyv_validated")]
+        fn delta_archive_validated(x: Vec<u64>) -> bool {
+            let set: VecSet2<u64> = x.into_iter().collect();
+            let bytes = delta_archive(set.clone());
+            let archived = rkyv::check_archived_root::<DeltaTest>(&bytes).unwrap();
+            archived.set.iter().eq(set.iter().cloned())
+        }
+
+        fn truncated_input_fails(x: Vec<u32>) -> bool {
+            let set: VecSet2<u32> = x.into_iter().collect();
+            let bytes = set.to_compact_bytes();
+            (0..bytes.len()).all(|n| VecSet2::<u32>::from_compact_bytes(&bytes[..n]).is_err())
+        }
+    }
+
+    #[cfg(feature = "rkyv_validated")]
+    #[test]
+    fn delta_archive_invalid_len() {
+        let mut bytes = delta_archive(VecSet2::empty());
+        // the root is at the end of the archive, and the element count is its last field
+        let n = bytes.len();
+        for &len in &[1, u64::MAX - 1, u64::MAX] {
+            bytes[n - 8..].copy_from_slice(&len.to_le_bytes());
+            assert!(rkyv::check_archived_root::<DeltaTest>(&bytes).is_err());
+        }
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn delta_archive_large() {
+        let set: VecSet2<u64> = (0..10000).map(|x| x * 3).collect();
+        let bytes = delta_archive(set.clone());
+        // one byte per element, plus the skip table
+        assert!(bytes.len() < 13000);
+        let archived = unsafe { rkyv::archived_root::<DeltaTest>(&bytes) };
+        for x in 0..30010 {
+            assert_eq!(archived.set.contains(&x), set.contains(&x));
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        let set: VecSet2<u32> = (250..260).collect();
+        let mut bytes = set.to_compact_bytes();
+        assert_eq!(
+            VecSet2::<u8>::from_compact_bytes(&bytes),
+            Err(CompactError::Overflow)
+        );
+        bytes.push(0);
+        assert_eq!(
+            VecSet2::<u32>::from_compact_bytes(&bytes),
+            Err(CompactError::TrailingBytes)
+        );
+    }
+}
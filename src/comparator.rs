@@ -0,0 +1,324 @@
+use crate::VecSet;
+use core::{cmp::Ordering, fmt, marker::PhantomData, ops::Deref};
+use smallvec::Array;
+
+/// A stateless comparison function for elements of type `T`.
+///
+/// This is used together with [OrderedBy] to get sets and maps with a custom order, e.g. case insensitive
+/// string sets. The comparison must be a total order, otherwise the merge based operations will produce garbage.
+pub trait Comparator<T: ?Sized> {
+    fn compare(a: &T, b: &T) -> Ordering;
+}
+
+/// The natural order, as given by the [Ord] instance of the type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct NaturalOrder;
+
+impl<T: Ord + ?Sized> Comparator<T> for NaturalOrder {
+    fn compare(a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// Case insensitive order for strings.
+///
+/// Strings are compared by their lowercase chars, so `"Foo"` and `"FOO"` are considered equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CaseInsensitive;
+
+impl<T: AsRef<str> + ?Sized> Comparator<T> for CaseInsensitive {
+    fn compare(a: &T, b: &T) -> Ordering {
+        let a = a.as_ref().chars().flat_map(char::to_lowercase);
+        let b = b.as_ref().chars().flat_map(char::to_lowercase);
+        a.cmp(b)
+    }
+}
+
+/// Total order for floating point numbers, as defined by [f64::total_cmp].
+///
+/// With this, floats can be used as elements of sets or keys of maps, e.g. `VecSet<[OrderedBy<f64, TotalOrder>; 4]>`.
+/// Note that `-0.0` and `0.0` are distinct. NaNs are ordered by their sign: negative NaNs before everything else,
+/// and positive NaNs after everything else.
+///
+/// # Example
+/// ```
+/// use vec_collections::{OrderedBy, TotalOrder, VecSet};
+/// let a: VecSet<[OrderedBy<f64, TotalOrder>; 4]> =
+///     vec![f64::NAN, 1.0, -f64::NAN, f64::NEG_INFINITY, -0.0, 0.0].into_iter().map(OrderedBy::new).collect();
+/// let values: Vec<f64> = a.iter().map(|x| **x).collect();
+/// assert_eq!(values.len(), 6);
+/// assert!(values[0].is_nan() && values[0].is_sign_negative());
+/// assert_eq!(values[1..5], [f64::NEG_INFINITY, -0.0, 0.0, 1.0]);
+/// assert!(values[5].is_nan() && values[5].is_sign_positive());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TotalOrder;
+
+impl Comparator<f64> for TotalOrder {
+    fn compare(a: &f64, b: &f64) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+impl Comparator<f32> for TotalOrder {
+    fn compare(a: &f32, b: &f32) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+/// Order for sets by their number of elements first, and then lexicographically.
+///
+/// See [VecSet::cmp_by_len_then_lex](crate::VecSet::cmp_by_len_then_lex). Use this to get maps keyed by sets in
+/// this order, e.g. `VecMap<[(OrderedBy<VecSet<[u32; 4]>, LenThenLex>, u64); 4]>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LenThenLex;
+
+impl<T: Ord, A: Array<Item = T>> Comparator<VecSet<A>> for LenThenLex {
+    fn compare(a: &VecSet<A>, b: &VecSet<A>) -> Ordering {
+        a.cmp_by_len_then_lex(b)
+    }
+}
+
+/// A value that is ordered by the [Comparator] `C` instead of its own [Ord] instance.
+///
+/// Wrap the elements of a [VecSet](crate::VecSet) or the keys of a [VecMap](crate::VecMap) in this to get a
+/// collection with a custom order. Since all set and map operations just use the [Ord] instance of the elements,
+/// they stay correct and consistent.
+///
+/// Equality is also defined by the comparator, so two values that compare as equal are considered the same element.
+///
+/// # Example
+/// ```
+/// use vec_collections::{AbstractVecSet, CaseInsensitive, OrderedBy, VecSet};
+/// let a: VecSet<[OrderedBy<String, CaseInsensitive>; 2]> =
+///     vec!["Foo".to_string().into(), "bar".to_string().into()].into();
+/// assert!(a.contains(&OrderedBy::new("FOO".to_string())));
+/// assert_eq!(a.iter().next().map(|x| x.as_str()), Some("bar"));
+/// ```
+#[repr(transparent)]
+pub struct OrderedBy<T: ?Sized, C = NaturalOrder>(PhantomData<C>, T);
+
+impl<T, C> OrderedBy<T, C> {
+    /// Wrap a value
+    pub fn new(value: T) -> Self {
+        Self(PhantomData, value)
+    }
+
+    /// Unwrap the value
+    pub fn into_inner(self) -> T {
+        self.1
+    }
+}
+
+impl<T: ?Sized, C> OrderedBy<T, C> {
+    /// Wrap a reference to a value without cloning, e.g. for lookups
+    pub fn from_ref(value: &T) -> &Self {
+        // this is safe since OrderedBy is a repr(transparent) wrapper for T
+        unsafe { &*(value as *const T as *const Self) }
+    }
+}
+
+impl<T, C> From<T> for OrderedBy<T, C> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: ?Sized, C> Deref for OrderedBy<T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.1
+    }
+}
+
+impl<T: ?Sized, C> AsRef<T> for OrderedBy<T, C> {
+    fn as_ref(&self) -> &T {
+        &self.1
+    }
+}
+
+impl<T: fmt::Debug + ?Sized, C> fmt::Debug for OrderedBy<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.1.fmt(f)
+    }
+}
+
+impl<T: Clone, C> Clone for OrderedBy<T, C> {
+    fn clone(&self) -> Self {
+        Self::new(self.1.clone())
+    }
+}
+
+impl<T: Copy, C> Copy for OrderedBy<T, C> {}
+
+impl<T: Default, C> Default for OrderedBy<T, C> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized, C: Comparator<T>> PartialEq for OrderedBy<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        C::compare(&self.1, &other.1) == Ordering::Equal
+    }
+}
+
+impl<T: ?Sized, C: Comparator<T>> Eq for OrderedBy<T, C> {}
+
+impl<T: ?Sized, C: Comparator<T>> PartialOrd for OrderedBy<T, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: ?Sized, C: Comparator<T>> Ord for OrderedBy<T, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        C::compare(&self.1, &other.1)
+    }
+}
+
+/// Archives to an [OrderedBy] of the archived value with the same comparator.
+///
+/// So validation of an archived set or map checks the order with the same comparator as the unarchived collection,
+/// as long as the comparator is also implemented for the archived type, like [CaseInsensitive] for archived strings.
+#[cfg(feature = "rkyv")]
+impl<T: rkyv::Archive, C> rkyv::Archive for OrderedBy<T, C> {
+    type Archived = OrderedBy<T::Archived, C>;
+
+    type Resolver = T::Resolver;
+
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        // OrderedBy is a repr(transparent) wrapper, so the archived value is at the same position
+        self.1.resolve(pos, resolver, out as *mut T::Archived)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<S: rkyv::Fallible + ?Sized, T: rkyv::Serialize<S>, C> rkyv::Serialize<S> for OrderedBy<T, C> {
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        self.1.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<D: rkyv::Fallible + ?Sized, T: rkyv::Archive, C> rkyv::Deserialize<OrderedBy<T, C>, D>
+    for OrderedBy<T::Archived, C>
+where
+    T::Archived: rkyv::Deserialize<T, D>,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<OrderedBy<T, C>, D::Error> {
+        Ok(OrderedBy::new(self.1.deserialize(deserializer)?))
+    }
+}
+
+#[cfg(feature = "rkyv_validated")]
+impl<X: ?Sized, T: bytecheck::CheckBytes<X>, C> bytecheck::CheckBytes<X> for OrderedBy<T, C> {
+    type Error = T::Error;
+
+    unsafe fn check_bytes<'a>(value: *const Self, context: &mut X) -> Result<&'a Self, T::Error> {
+        T::check_bytes(value as *const T, context)?;
+        Ok(&*value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VecMap, VecSet};
+    use quickcheck::quickcheck;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    type Key = OrderedBy<String, CaseInsensitive>;
+
+    fn lowercase(x: &str) -> String {
+        x.chars().flat_map(char::to_lowercase).collect()
+    }
+
+    quickcheck! {
+        fn case_insensitive_set_ops(a: Vec<String>, b: Vec<String>) -> bool {
+            let sa: VecSet<[Key; 2]> = a.iter().cloned().map(Key::new).collect();
+            let sb: VecSet<[Key; 2]> = b.iter().cloned().map(Key::new).collect();
+            let ra: BTreeSet<String> = a.iter().map(|x| lowercase(x)).collect();
+            let rb: BTreeSet<String> = b.iter().map(|x| lowercase(x)).collect();
+            let check = |s: VecSet<[Key; 2]>, r: BTreeSet<String>| {
+                s.iter().map(|x| lowercase(x)).collect::<Vec<_>>() == r.into_iter().collect::<Vec<_>>()
+            };
+            check(&sa | &sb, &ra | &rb)
+                && check(&sa & &sb, &ra & &rb)
+                && check(&sa ^ &sb, &ra ^ &rb)
+                && check(&sa - &sb, &ra - &rb)
+        }
+
+        fn case_insensitive_map(entries: Vec<(String, u32)>) -> bool {
+            let map: VecMap<[(Key, u32); 2]> = entries.iter().cloned().map(|(k, v)| (Key::new(k), v)).collect();
+            let reference: BTreeMap<String, u32> = entries.iter().map(|(k, v)| (lowercase(k), *v)).collect();
+            entries.iter().all(|(k, _)| map.get(Key::from_ref(k)) == reference.get(&lowercase(k)))
+                && map.len() == reference.len()
+        }
+    }
+
+    quickcheck! {
+        fn total_order_floats(a: Vec<f64>, b: Vec<f64>) -> bool {
+            type F = OrderedBy<f64, TotalOrder>;
+            let sa: VecSet<[F; 4]> = a.iter().copied().map(F::new).collect();
+            let sb: VecSet<[F; 4]> = b.iter().copied().map(F::new).collect();
+            let bits = |s: &VecSet<[F; 4]>| s.iter().map(|x| x.to_bits()).collect::<BTreeSet<_>>();
+            let sorted = |s: &VecSet<[F; 4]>| s.iter().zip(s.iter().skip(1)).all(|(a, b)| a.total_cmp(b).is_lt());
+            let u = &sa | &sb;
+            sorted(&u) && bits(&u) == &bits(&sa) | &bits(&sb)
+        }
+    }
+
+    quickcheck! {
+        fn len_then_lex(sets: Vec<BTreeSet<u8>>) -> bool {
+            type S = OrderedBy<VecSet<[u8; 4]>, LenThenLex>;
+            let keys: BTreeSet<S> = sets.iter().map(|s| S::new(s.iter().copied().collect())).collect();
+            let mut expected: Vec<Vec<u8>> = sets.iter().map(|s| s.iter().copied().collect()).collect();
+            expected.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+            expected.dedup();
+            keys.iter().map(|k| k.to_vec()).collect::<Vec<_>>() == expected
+        }
+    }
+
+    #[cfg(feature = "rkyv_validated")]
+    #[test]
+    fn rkyv_validation_uses_comparator() {
+        use crate::{AbstractVecSet, ArchivedVecSetError};
+        use rkyv::{ser::Serializer, validation::CheckArchiveError};
+        fn archive<T: rkyv::Serialize<rkyv::ser::serializers::AllocSerializer<256>>>(
+            value: &T,
+        ) -> rkyv::AlignedVec {
+            let mut serializer = rkyv::ser::serializers::AllocSerializer::<256>::default();
+            serializer.serialize_value(value).unwrap();
+            serializer.into_serializer().into_inner()
+        }
+        // a set and a vec have the same archived representation
+        let bytes = archive(&vec!["B".to_string(), "a".to_string()]);
+        assert!(rkyv::check_archived_root::<VecSet<[String; 2]>>(&bytes).is_ok());
+        // sorted by the natural order of strings, but not case insensitively
+        assert!(matches!(
+            rkyv::check_archived_root::<VecSet<[Key; 2]>>(&bytes),
+            Err(CheckArchiveError::CheckBytesError(
+                ArchivedVecSetError::OrderCheckError { index: 1 }
+            ))
+        ));
+        let set: VecSet<[Key; 2]> = vec![Key::new("B".into()), Key::new("a".into())].into();
+        let bytes = archive(&set);
+        let archived = rkyv::check_archived_root::<VecSet<[Key; 2]>>(&bytes).unwrap();
+        let archived_strings: Vec<&str> = archived.as_slice().iter().map(|x| x.as_str()).collect();
+        assert_eq!(archived_strings, vec!["a", "B"]);
+        let deserialized: VecSet<[Key; 2]> =
+            rkyv::Deserialize::deserialize(archived, &mut rkyv::Infallible).unwrap();
+        assert_eq!(deserialized, set);
+    }
+
+    #[test]
+    fn natural_order() {
+        let a: VecSet<[OrderedBy<u32>; 4]> = vec![3u32, 1, 2, 1]
+            .into_iter()
+            .map(OrderedBy::new)
+            .collect();
+        assert_eq!(a.iter().map(|x| **x).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+}
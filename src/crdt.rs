@@ -0,0 +1,388 @@
+//! Building blocks for CRDTs: version vectors and causal contexts.
+//!
+//! A dot is a single event, identified by an `(actor, counter)` pair. Counters of each actor start at 1.
+//!
+//! Both types are thin wrappers around [VecMap] and [VecSet], so joins are in place merges of sorted sequences,
+//! and small contexts with few actors do not allocate.
+use crate::{total_vec_map::pointwise_cmp, vec_map::OuterJoinArg, VecMap, VecSet};
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
+use smallvec::Array;
+
+/// A version vector, mapping each actor to the number of events seen from it.
+///
+/// Actors that are not present map to 0. The partial order is the pointwise order, so two version vectors that
+/// are neither less than or equal to each other are concurrent.
+///
+/// # Example
+/// ```
+/// use vec_collections::crdt::VersionVector;
+/// let mut a: VersionVector<[(&str, u64); 2]> = VersionVector::empty();
+/// let mut b = a.clone();
+/// a.increment("a");
+/// b.increment("b");
+/// assert!(a.concurrent(&b));
+/// b.join_with(&a);
+/// assert!(a < b);
+/// assert_eq!(b.get(&"a"), 1);
+/// ```
+pub struct VersionVector<A: Array>(VecMap<A>);
+
+impl<A: Array> VersionVector<A> {
+    /// A version vector where all actors map to 0
+    pub fn empty() -> Self {
+        Self(VecMap::empty())
+    }
+
+    /// true if all actors map to 0
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The non-zero counters, sorted by actor
+    pub fn as_map(&self) -> &VecMap<A> {
+        &self.0
+    }
+
+    /// Returns the non-zero counters, sorted by actor
+    pub fn into_map(self) -> VecMap<A> {
+        self.0
+    }
+}
+
+impl<K: Ord + Clone + 'static, A: Array<Item = (K, u64)>> VersionVector<A> {
+    /// The counter for an actor. Time complexity is O(log N).
+    pub fn get(&self, actor: &K) -> u64 {
+        self.0.get(actor).cloned().unwrap_or_default()
+    }
+
+    /// Increment the counter for an actor, returning the dot for the new event
+    pub fn increment(&mut self, actor: K) -> (K, u64) {
+        let counter = match self.0.get_mut(&actor) {
+            Some(counter) => {
+                *counter += 1;
+                *counter
+            }
+            None => {
+                self.0.insert(actor.clone(), 1);
+                1
+            }
+        };
+        (actor, counter)
+    }
+
+    /// true if the event identified by the dot has been seen
+    pub fn contains(&self, dot: &(K, u64)) -> bool {
+        dot.1 <= self.get(&dot.0)
+    }
+
+    /// In place pointwise maximum with another version vector
+    pub fn join_with(&mut self, that: &Self) {
+        self.0.outer_join_with(&that.0, |arg| {
+            Some(match arg {
+                OuterJoinArg::Left(_, v) => v,
+                OuterJoinArg::Right(_, w) => *w,
+                OuterJoinArg::Both(_, v, w) => v.max(*w),
+            })
+        })
+    }
+
+    /// Pointwise maximum of two version vectors
+    pub fn join(&self, that: &Self) -> Self {
+        let mut res = self.clone();
+        res.join_with(that);
+        res
+    }
+
+    /// true if neither version vector is less than or equal to the other
+    pub fn concurrent(&self, that: &Self) -> bool {
+        self.partial_cmp(that).is_none()
+    }
+
+    /// Set the counter for an actor, which must not be 0
+    fn set(&mut self, actor: K, counter: u64) {
+        debug_assert!(counter != 0);
+        self.0.insert(actor, counter);
+    }
+}
+
+impl<K: Ord + 'static, A: Array<Item = (K, u64)>> From<VecMap<A>> for VersionVector<A> {
+    /// Creates a version vector from a map of counters, dropping all zero counters
+    fn from(mut map: VecMap<A>) -> Self {
+        map.retain(|(_, counter)| *counter != 0);
+        Self(map)
+    }
+}
+
+impl<K: Ord, A: Array<Item = (K, u64)>> PartialOrd for VersionVector<A> {
+    /// The pointwise order. Time complexity is O(N + M).
+    fn partial_cmp(&self, that: &Self) -> Option<Ordering> {
+        pointwise_cmp(self.0.as_slice(), &0, that.0.as_slice(), &0)
+    }
+}
+
+impl<T: PartialEq, A: Array<Item = T>> PartialEq for VersionVector<A> {
+    fn eq(&self, that: &Self) -> bool {
+        self.0 == that.0
+    }
+}
+
+impl<T: Eq, A: Array<Item = T>> Eq for VersionVector<A> {}
+
+impl<T: Hash, A: Array<Item = T>> Hash for VersionVector<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<T: Clone, A: Array<Item = T>> Clone for VersionVector<A> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<A: Array> Default for VersionVector<A> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T: fmt::Debug, A: Array<Item = T>> fmt::Debug for VersionVector<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A set of dots, stored as a version vector for the contiguous prefix of each actor, and a dot cloud for the rest.
+///
+/// Dots that extend the contiguous prefix are moved from the cloud to the version vector, so as long as the events
+/// of each actor are delivered without gaps, the cloud stays empty.
+///
+/// # Example
+/// ```
+/// use vec_collections::crdt::CausalContext;
+/// let mut ctx: CausalContext<[(&str, u64); 2]> = CausalContext::empty();
+/// ctx.insert(("a", 1));
+/// ctx.insert(("a", 3));
+/// assert!(ctx.contains(&("a", 3)) && !ctx.contains(&("a", 2)));
+/// assert_eq!(ctx.cloud().as_slice(), &[("a", 3)]);
+/// ctx.insert(("a", 2));
+/// assert!(ctx.cloud().is_empty());
+/// assert_eq!(ctx.version_vector().get(&"a"), 3);
+/// ```
+pub struct CausalContext<A: Array> {
+    compact: VersionVector<A>,
+    /// dots that are not contiguous with `compact`, sorted by actor and counter
+    cloud: VecSet<A>,
+}
+
+impl<A: Array> CausalContext<A> {
+    /// An empty causal context
+    pub fn empty() -> Self {
+        Self {
+            compact: VersionVector::empty(),
+            cloud: VecSet::empty(),
+        }
+    }
+
+    /// true if the context contains no dots
+    pub fn is_empty(&self) -> bool {
+        self.compact.is_empty() && self.cloud.is_empty()
+    }
+
+    /// The contiguous prefix of the dots of each actor
+    pub fn version_vector(&self) -> &VersionVector<A> {
+        &self.compact
+    }
+
+    /// The dots that are not part of the contiguous prefix, sorted by actor and counter
+    pub fn cloud(&self) -> &VecSet<A> {
+        &self.cloud
+    }
+}
+
+impl<K: Ord + Clone + 'static, A: Array<Item = (K, u64)>> CausalContext<A> {
+    /// true if the context contains the dot
+    pub fn contains(&self, dot: &(K, u64)) -> bool {
+        self.compact.contains(dot) || self.cloud.contains(dot)
+    }
+
+    /// Add a dot to the context. Returns false if it was already present.
+    pub fn insert(&mut self, dot: (K, u64)) -> bool {
+        if self.contains(&dot) {
+            return false;
+        }
+        self.cloud.insert(dot);
+        self.compact_cloud();
+        true
+    }
+
+    /// Create a dot for a new event of an actor, and add it to the context
+    pub fn next_dot(&mut self, actor: K) -> (K, u64) {
+        let last = self
+            .cloud
+            .iter()
+            .rev()
+            .find(|(a, _)| *a == actor)
+            .map(|(_, counter)| *counter)
+            .unwrap_or_else(|| self.compact.get(&actor));
+        let dot = (actor, last + 1);
+        self.insert(dot.clone());
+        dot
+    }
+
+    /// In place union with another causal context
+    pub fn join_with(&mut self, that: &Self) {
+        self.compact.join_with(&that.compact);
+        self.cloud.union_with(&that.cloud);
+        self.compact_cloud();
+    }
+
+    /// Union of two causal contexts
+    pub fn join(&self, that: &Self) -> Self {
+        let mut res = self.clone();
+        res.join_with(that);
+        res
+    }
+
+    /// Move all dots that are contiguous with the version vector from the cloud to the version vector.
+    ///
+    /// Since the cloud is sorted by actor and counter, this is a single pass.
+    fn compact_cloud(&mut self) {
+        let compact = &mut self.compact;
+        self.cloud.retain(|(actor, counter)| {
+            let current = compact.get(actor);
+            if *counter == current + 1 {
+                compact.set(actor.clone(), *counter);
+            }
+            *counter > current + 1
+        });
+    }
+}
+
+impl<T: PartialEq, A: Array<Item = T>> PartialEq for CausalContext<A> {
+    fn eq(&self, that: &Self) -> bool {
+        self.compact == that.compact && self.cloud == that.cloud
+    }
+}
+
+impl<T: Eq, A: Array<Item = T>> Eq for CausalContext<A> {}
+
+impl<T: Clone, A: Array<Item = T>> Clone for CausalContext<A> {
+    fn clone(&self) -> Self {
+        Self {
+            compact: self.compact.clone(),
+            cloud: self.cloud.clone(),
+        }
+    }
+}
+
+impl<A: Array> Default for CausalContext<A> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T: fmt::Debug, A: Array<Item = T>> fmt::Debug for CausalContext<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CausalContext")
+            .field("version_vector", &self.compact)
+            .field("cloud", &self.cloud)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    type Vv = VersionVector<[(u8, u64); 2]>;
+    type Ctx = CausalContext<[(u8, u64); 2]>;
+
+    fn vv(counters: BTreeMap<u8, u8>) -> Vv {
+        counters
+            .into_iter()
+            .map(|(actor, counter)| (actor % 4, u64::from(counter % 4)))
+            .collect::<VecMap<_>>()
+            .into()
+    }
+
+    /// dots with small actors and counters, so there are many contiguous runs
+    fn dots(dots: Vec<(u8, u8)>) -> BTreeSet<(u8, u64)> {
+        dots.into_iter()
+            .map(|(actor, counter)| (actor % 4, u64::from(counter % 8) + 1))
+            .collect()
+    }
+
+    fn ctx(dots: &BTreeSet<(u8, u64)>) -> Ctx {
+        let mut res = Ctx::empty();
+        for dot in dots {
+            res.insert(*dot);
+        }
+        res
+    }
+
+    fn all_dots(ctx: &Ctx) -> BTreeSet<(u8, u64)> {
+        let mut res: BTreeSet<(u8, u64)> = ctx.cloud().iter().cloned().collect();
+        for (actor, counter) in ctx.version_vector().as_map().iter() {
+            res.extend((1..=*counter).map(|c| (*actor, c)));
+        }
+        res
+    }
+
+    quickcheck! {
+        fn version_vector_join_cmp(a: BTreeMap<u8, u8>, b: BTreeMap<u8, u8>) -> bool {
+            let a = vv(a);
+            let b = vv(b);
+            let j = a.join(&b);
+            let actors = (0..4).collect::<Vec<u8>>();
+            let le = actors.iter().all(|x| a.get(x) <= b.get(x));
+            let ge = actors.iter().all(|x| a.get(x) >= b.get(x));
+            actors.iter().all(|x| j.get(x) == a.get(x).max(b.get(x)))
+                && a <= j
+                && b <= j
+                && (a <= b) == le
+                && (a >= b) == ge
+                && a.concurrent(&b) == (!le && !ge)
+        }
+
+        fn increment(a: BTreeMap<u8, u8>, actor: u8) -> bool {
+            let mut a = vv(a);
+            let before = a.clone();
+            let dot = a.increment(actor);
+            dot == (actor, before.get(&actor) + 1) && a.contains(&dot) && !before.contains(&dot) && before < a
+        }
+
+        fn causal_context(a: Vec<(u8, u8)>, b: Vec<(u8, u8)>) -> bool {
+            let a = dots(a);
+            let b = dots(b);
+            let ca = ctx(&a);
+            let cb = ctx(&b);
+            let joined = ca.join(&cb);
+            let union = a.union(&b).cloned().collect::<BTreeSet<_>>();
+            // the cloud never contains dots that could be part of the version vector
+            let compact = joined
+                .cloud()
+                .iter()
+                .all(|(actor, counter)| *counter > joined.version_vector().get(actor) + 1);
+            all_dots(&ca) == a
+                && all_dots(&joined) == union
+                && joined == ctx(&union)
+                && union.iter().all(|dot| joined.contains(dot))
+                && compact
+        }
+
+        fn next_dot(a: Vec<(u8, u8)>, actor: u8) -> bool {
+            let mut ctx = ctx(&dots(a));
+            let before = all_dots(&ctx);
+            let dot = ctx.next_dot(actor);
+            let max = before.iter().filter(|(a, _)| *a == actor).map(|(_, c)| *c).max().unwrap_or_default();
+            dot == (actor, max + 1) && ctx.contains(&dot)
+        }
+    }
+}
@@ -134,6 +134,68 @@ pub fn sort_dedup_by_key<I: Iterator, R: Seq<I::Item>, K: Ord, F: Fn(&I::Item) -
     sort_dedup_by(iter, keep, |a: &I::Item, b: &I::Item| key(a).cmp(key(b)))
 }
 
+/// Lazily deduplicate an iterator `I` of elements that are sorted by key, using a key fn.
+///
+/// Only consecutive elements with the same key are considered duplicates, so unlike
+/// [sort_dedup_by_key] this does not sort, but also never buffers more than one element.
+/// `keep` determines whether to keep the first or the last occurrence in case of duplicates
+///
+/// # Example
+/// ```
+/// use vec_collections::{dedup_by_key_keep, Keep};
+/// let sorted = vec![(1, "a"), (1, "b"), (2, "c"), (3, "d"), (3, "e")];
+/// let last: Vec<_> = dedup_by_key_keep(sorted, |x| &x.0, Keep::Last).collect();
+/// assert_eq!(last, vec![(1, "b"), (2, "c"), (3, "e")]);
+/// ```
+pub fn dedup_by_key_keep<I: IntoIterator, K: Eq, F: Fn(&I::Item) -> &K>(
+    iter: I,
+    key: F,
+    keep: Keep,
+) -> DedupByKeyKeep<I::IntoIter, F> {
+    DedupByKeyKeep {
+        iter: iter.into_iter(),
+        next: None,
+        key,
+        keep,
+    }
+}
+
+/// Iterator returned by [dedup_by_key_keep]
+#[derive(Debug, Clone)]
+pub struct DedupByKeyKeep<I: Iterator, F> {
+    iter: I,
+    /// the first element of the next run, if we have already taken it from the iterator
+    next: Option<I::Item>,
+    key: F,
+    keep: Keep,
+}
+
+impl<I: Iterator, K: Eq, F: Fn(&I::Item) -> &K> Iterator for DedupByKeyKeep<I, F> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let mut current = self.next.take().or_else(|| self.iter.next())?;
+        for elem in &mut self.iter {
+            if (self.key)(&elem) != (self.key)(&current) {
+                self.next = Some(elem);
+                break;
+            } else if self.keep == Keep::Last {
+                current = elem;
+            }
+        }
+        Some(current)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let buffered = if self.next.is_some() { 1 } else { 0 };
+        let (lower, upper) = self.iter.size_hint();
+        (
+            min(lower.saturating_add(buffered), 1),
+            upper.and_then(|upper| upper.checked_add(buffered)),
+        )
+    }
+}
+
 impl<I, T, F> SortAndDedup<I, T, F>
 where
     F: Fn(&T, &T) -> Ordering,
@@ -241,6 +303,27 @@ mod tests {
         unary_op(x, expected, actual)
     }
 
+    #[quickcheck]
+    fn dedup_by_key_keep_check(x: Vec<(i32, i32)>) -> bool {
+        let mut x = x;
+        x.sort_by_key(|x| x.0);
+        let first: Vec<(i32, i32)> = sort_dedup_by_key(x.iter().cloned(), Keep::First, |x| &x.0);
+        let last: Vec<(i32, i32)> = sort_dedup_by_key(x.iter().cloned(), Keep::Last, |x| &x.0);
+        let first_lazy: Vec<_> =
+            dedup_by_key_keep(x.iter().cloned(), |x| &x.0, Keep::First).collect();
+        let last_lazy: Vec<_> =
+            dedup_by_key_keep(x.iter().cloned(), |x| &x.0, Keep::Last).collect();
+        first == first_lazy && last == last_lazy
+    }
+
+    #[test]
+    fn dedup_by_key_keep_unbounded() {
+        // the inner iterator reports usize::MAX as its lower bound, and one element is buffered after next
+        let mut iter = dedup_by_key_keep(0u64.., |x| x, Keep::First);
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.size_hint(), (1, None));
+    }
+
     #[test]
     fn dedup_by() {
         let mut v: Vec<(i32, i32)> = vec![(0, 1), (0, 2), (0, 3)];
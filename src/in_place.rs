@@ -0,0 +1,173 @@
+use crate::merge_state::InPlaceBuilder;
+use inplace_vec_builder::{InPlaceSmallVecBuilder, InPlaceVecBuilder};
+use smallvec::{Array, SmallVec};
+
+/// In place transformation of a [Vec] or [SmallVec] via an iterator pipeline.
+///
+/// This uses the same machinery as the in place set and map operations: the elements are moved out of the
+/// front of the existing allocation, and the results are written to the already consumed part. A new
+/// allocation is only needed if the output gets larger than the input.
+///
+/// # Panic safety
+///
+/// If the transformation panics, the collection contains all elements that were produced so far, and all
+/// remaining input elements are dropped. No element is ever dropped twice or leaked.
+///
+/// # Example
+/// ```
+/// use vec_collections::TransformInPlace;
+/// let mut v = vec![1, 2, 3, 4];
+/// // remove odd numbers and duplicate even numbers
+/// v.flat_map_in_place(|x| if x % 2 == 0 { vec![x, x] } else { vec![] });
+/// assert_eq!(v, vec![2, 2, 4, 4]);
+/// ```
+pub trait TransformInPlace<T> {
+    /// Replace each element with the elements produced by `f`, in order, reusing the allocation.
+    ///
+    /// Since `f` can keep state, this can express any single pass pipeline like map, filter or dedup.
+    fn flat_map_in_place<I, F>(&mut self, f: F)
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(T) -> I;
+}
+
+impl<T> TransformInPlace<T> for Vec<T> {
+    fn flat_map_in_place<I, F>(&mut self, f: F)
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(T) -> I,
+    {
+        flat_map(InPlaceVecBuilder::from(self), f)
+    }
+}
+
+impl<A: Array> TransformInPlace<A::Item> for SmallVec<A> {
+    fn flat_map_in_place<I, F>(&mut self, f: F)
+    where
+        I: IntoIterator<Item = A::Item>,
+        F: FnMut(A::Item) -> I,
+    {
+        flat_map(InPlaceSmallVecBuilder::from(self), f)
+    }
+}
+
+fn flat_map<W, I, F>(mut builder: W, mut f: F)
+where
+    W: InPlaceBuilder,
+    I: IntoIterator<Item = W::Item>,
+    F: FnMut(W::Item) -> I,
+{
+    // the builder restores a valid vec on drop, even when unwinding
+    while let Some(value) = builder.pop_front() {
+        for value in f(value) {
+            builder.push(value);
+        }
+    }
+}
+
+/// Convert between SmallVecs with different inline capacities.
+///
+/// If the elements are on the heap, the heap buffer is reused, unless they fit into the inline storage of the
+/// target. Inline elements are moved, and only allocate if they don't fit into the inline storage of the target.
+pub(crate) fn cast_storage<T, A: Array<Item = T>, B: Array<Item = T>>(
+    elements: SmallVec<A>,
+) -> SmallVec<B> {
+    if elements.spilled() {
+        SmallVec::from_vec(elements.into_vec())
+    } else {
+        elements.into_iter().collect()
+    }
+}
+
+/// What to do with excess capacity after an operation that may have grown the underlying storage.
+///
+/// In place operations like [union_with](crate::VecSet::union_with) reuse the existing allocation and grow it as
+/// needed, so the capacity after a sequence of operations is the peak size. For long lived collections, use
+/// e.g. [VecSet::with_capacity_policy](crate::VecSet::with_capacity_policy) to release the excess memory.
+///
+/// The policy only looks at the length and the capacity, so the result is deterministic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CapacityPolicy {
+    /// Keep the capacity, so that the allocation can be reused by subsequent operations
+    #[default]
+    Keep,
+    /// Shrink to fit if the capacity is more than the given factor times the length
+    ShrinkAbove(usize),
+    /// Always shrink to fit
+    ShrinkToFit,
+}
+
+impl CapacityPolicy {
+    /// Apply the policy to a SmallVec. This will never shrink a SmallVec that is not spilled.
+    pub(crate) fn apply<A: Array>(self, v: &mut SmallVec<A>) {
+        let shrink = v.spilled()
+            && match self {
+                Self::Keep => false,
+                Self::ShrinkAbove(factor) => v.capacity() > v.len().saturating_mul(factor),
+                Self::ShrinkToFit => true,
+            };
+        if shrink {
+            v.shrink_to_fit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+    use std::{panic, rc::Rc};
+
+    fn reference(v: &[u8]) -> Vec<u8> {
+        v.iter().flat_map(|x| vec![*x; (*x % 4) as usize]).collect()
+    }
+
+    quickcheck! {
+        fn vec_flat_map(v: Vec<u8>) -> bool {
+            let mut actual = v.clone();
+            actual.flat_map_in_place(|x| vec![x; (x % 4) as usize]);
+            actual == reference(&v)
+        }
+
+        fn small_vec_flat_map(v: Vec<u8>) -> bool {
+            let mut actual: SmallVec<[u8; 4]> = v.iter().copied().collect();
+            actual.flat_map_in_place(|x| vec![x; (x % 4) as usize]);
+            actual.as_slice() == reference(&v).as_slice()
+        }
+    }
+
+    #[test]
+    fn capacity_policy() {
+        let mut v: SmallVec<[u32; 4]> = SmallVec::with_capacity(100);
+        v.extend(0..10);
+        CapacityPolicy::Keep.apply(&mut v);
+        assert_eq!(v.capacity(), 100);
+        CapacityPolicy::ShrinkAbove(10).apply(&mut v);
+        assert_eq!(v.capacity(), 100);
+        CapacityPolicy::ShrinkAbove(2).apply(&mut v);
+        assert_eq!(v.capacity(), 10);
+        v.truncate(3);
+        CapacityPolicy::ShrinkToFit.apply(&mut v);
+        assert!(!v.spilled());
+    }
+
+    #[test]
+    fn panic_safety() {
+        let item = Rc::new(());
+        let mut v = vec![item.clone(); 10];
+        let mut n = 0;
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            v.flat_map_in_place(|x| {
+                n += 1;
+                if n == 5 {
+                    panic!();
+                }
+                vec![x.clone(), x]
+            })
+        }));
+        assert!(res.is_err());
+        // the outputs of the first 4 elements remain, the rest has been dropped exactly once
+        assert_eq!(v.len(), 8);
+        assert_eq!(Rc::strong_count(&item), 9);
+    }
+}
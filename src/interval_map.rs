@@ -0,0 +1,755 @@
+use crate::VecSet;
+use core::{
+    cmp,
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::{FromIterator, Map},
+    ops::{Bound, Range},
+    slice,
+};
+use num_traits::{CheckedAdd, PrimInt, Zero};
+use smallvec::Array;
+
+/// A map from possibly overlapping half-open intervals to values.
+///
+/// The intervals are stored in a single vec, sorted by start and then end. In addition, for each position
+/// the maximum end of all intervals up to and including that position is stored. This allows stabbing and
+/// overlap queries that only look at intervals that start before the query end and whose prefix could still
+/// contain a match.
+///
+/// Like the other collections in this crate, this is meant to be built in bulk and then used read-only.
+/// Empty intervals (`start >= end`) can never match a query, so they are dropped when building the map.
+///
+/// # Example
+/// ```
+/// use vec_collections::IntervalMap;
+/// let map: IntervalMap<u32, &str> = vec![(0..10, "a"), (5..15, "b"), (20..30, "c")]
+///     .into_iter()
+///     .collect();
+/// let stabbed: Vec<_> = map.stab(&7).map(|(_, v)| *v).collect();
+/// assert_eq!(stabbed, vec!["a", "b"]);
+/// let overlapping: Vec<_> = map.overlapping(&(12..25)).map(|(_, v)| *v).collect();
+/// assert_eq!(overlapping, vec!["b", "c"]);
+/// ```
+pub struct IntervalMap<T, V> {
+    /// the intervals, sorted by start, then end
+    entries: Vec<(Range<T>, V)>,
+    /// max_end[i] is the maximum of the ends of entries[..=i]
+    max_end: Vec<T>,
+}
+
+impl<T, V> IntervalMap<T, V> {
+    /// An empty map
+    pub fn empty() -> Self {
+        Self {
+            entries: Vec::new(),
+            max_end: Vec::new(),
+        }
+    }
+
+    /// The number of intervals in the map
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// All intervals with their values, sorted by start and then end
+    pub fn iter(&self) -> impl Iterator<Item = (&Range<T>, &V)> {
+        self.entries.iter().map(|(r, v)| (r, v))
+    }
+
+    /// All intervals, sorted by start and then end
+    ///
+    /// For an `IntervalMap<T, ()>` built with [range_set_from_iter](IntervalMap::range_set_from_iter), these are the
+    /// disjoint ranges of the set.
+    pub fn ranges(&self) -> impl Iterator<Item = &Range<T>> {
+        self.entries.iter().map(|(r, _)| r)
+    }
+
+    /// All intervals as owned ranges, sorted by start and then end
+    pub fn into_ranges(self) -> impl Iterator<Item = Range<T>> {
+        self.entries.into_iter().map(|(r, _)| r)
+    }
+
+    /// The intervals with their values as a slice, sorted by start and then end
+    pub fn as_slice(&self) -> &[(Range<T>, V)] {
+        &self.entries
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        self.max_end.shrink_to_fit();
+    }
+
+    /// Returns the intervals and values as a vec, sorted by start and then end
+    pub fn into_inner(self) -> Vec<(Range<T>, V)> {
+        self.entries
+    }
+}
+
+impl<T: Ord + Clone, V> IntervalMap<T, V> {
+    /// Build an interval map from a vec of intervals and values, dropping empty intervals
+    ///
+    /// The order of intervals with identical bounds is preserved.
+    pub fn from_vec(mut entries: Vec<(Range<T>, V)>) -> Self {
+        entries.retain(|(r, _)| r.start < r.end);
+        // stable sort, so identical intervals keep their order
+        entries.sort_by(|(a, _), (b, _)| (&a.start, &a.end).cmp(&(&b.start, &b.end)));
+        let mut max_end: Vec<T> = Vec::with_capacity(entries.len());
+        for (r, _) in entries.iter() {
+            let end = match max_end.last() {
+                Some(max) if max > &r.end => max.clone(),
+                _ => r.end.clone(),
+            };
+            max_end.push(end);
+        }
+        Self { entries, max_end }
+    }
+
+    /// All intervals containing the given point, sorted by start and then end
+    pub fn stab<'a>(&'a self, point: &'a T) -> impl Iterator<Item = (&'a Range<T>, &'a V)> + 'a {
+        self.entries[self.candidate_range(point, |start| start <= point)]
+            .iter()
+            .filter(move |(r, _)| point < &r.end)
+            .map(|(r, v)| (r, v))
+    }
+
+    /// All intervals overlapping the given range, sorted by start and then end
+    ///
+    /// An empty query range does not overlap anything.
+    pub fn overlapping<'a>(
+        &'a self,
+        range: &'a Range<T>,
+    ) -> impl Iterator<Item = (&'a Range<T>, &'a V)> + 'a {
+        let candidates = if range.start < range.end {
+            &self.entries[self.candidate_range(&range.start, |start| start < &range.end)]
+        } else {
+            &[]
+        };
+        candidates
+            .iter()
+            .filter(move |(r, _)| range.start < r.end)
+            .map(|(r, v)| (r, v))
+    }
+
+    /// The maximal ranges within the given bounds that are not covered by any interval, in ascending order
+    ///
+    /// This is lazy and does not allocate, so it is cheap to e.g. find the first free slot of a given size.
+    pub fn gaps<'a>(&'a self, within: &'a Range<T>) -> impl Iterator<Item = Range<T>> + 'a {
+        let mut intervals = self.overlapping(within).map(|(r, _)| r);
+        // the start of the next gap, or None when done
+        let mut cursor = Some(within.start.clone());
+        core::iter::from_fn(move || loop {
+            let start = cursor.clone()?;
+            match intervals.next() {
+                Some(r) if r.start > start => {
+                    cursor = Some(r.end.clone());
+                    return Some(start..r.start.clone());
+                }
+                Some(r) => {
+                    if r.end > start {
+                        cursor = Some(r.end.clone());
+                    }
+                }
+                None => {
+                    cursor = None;
+                    return if start < within.end {
+                        Some(start..within.end.clone())
+                    } else {
+                        None
+                    };
+                }
+            }
+        })
+    }
+
+    /// The maximal ranges within the given bounds that are covered by at least one interval, in ascending order
+    ///
+    /// This is the complement of [gaps](IntervalMap::gaps) within the bounds.
+    pub fn covered(&self, within: &Range<T>) -> Vec<Range<T>> {
+        let mut res: Vec<Range<T>> = Vec::new();
+        for (r, _) in self.overlapping(within) {
+            let start = cmp::max(&r.start, &within.start).clone();
+            let end = cmp::min(&r.end, &within.end).clone();
+            match res.last_mut() {
+                // intervals are sorted by start, so we only have to extend the last range
+                Some(last) if last.end >= start => {
+                    if end > last.end {
+                        last.end = end;
+                    }
+                }
+                _ => res.push(start..end),
+            }
+        }
+        res
+    }
+
+    /// Split the given bounds into buckets of size `step`, and yield the start of each bucket together with the
+    /// ranges within the bucket that are covered by at least one interval
+    ///
+    /// The last bucket is truncated to the bounds. Buckets without coverage are included, so this can be used
+    /// e.g. for time series bucketing. Panics if `step` is not positive.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::IntervalMap;
+    /// let map: IntervalMap<u32, ()> = vec![(5..12, ()), (14..16, ())].into_iter().collect();
+    /// let buckets: Vec<_> = map.split_by(&(0..20), 10).collect();
+    /// assert_eq!(buckets, vec![(0, vec![5..10]), (10, vec![10..12, 14..16])]);
+    /// ```
+    pub fn split_by<'a>(
+        &'a self,
+        within: &'a Range<T>,
+        step: T,
+    ) -> impl Iterator<Item = (T, Vec<Range<T>>)> + 'a
+    where
+        T: CheckedAdd + Zero,
+    {
+        assert!(step > T::zero(), "step must be positive");
+        let mut start = within.start.clone();
+        core::iter::from_fn(move || {
+            if start >= within.end {
+                return None;
+            }
+            // the last bucket may end at the maximum value, so the next start can overflow
+            let next = match start.checked_add(&step) {
+                Some(next) => cmp::min(next, within.end.clone()),
+                None => within.end.clone(),
+            };
+            let bucket = start.clone()..next.clone();
+            let covered = self.covered(&bucket);
+            Some((core::mem::replace(&mut start, next), covered))
+        })
+    }
+
+    /// True if any interval contains the given point
+    pub fn contains_point(&self, point: &T) -> bool {
+        self.stab(point).next().is_some()
+    }
+
+    /// Merge overlapping or adjacent intervals with equal values into a canonical representation
+    ///
+    /// This does not change which values are found for a point, but each value is found at most once. Two normalized
+    /// maps are equal if and only if they map the same points to the same values. This is not done on construction,
+    /// since intervals with equal values are separate entries.
+    pub fn normalize(&mut self)
+    where
+        V: Ord,
+    {
+        let mut entries = core::mem::take(&mut self.entries);
+        entries.sort_by(|(a, v), (b, w)| v.cmp(w).then_with(|| a.start.cmp(&b.start)));
+        let mut res: Vec<(Range<T>, V)> = Vec::with_capacity(entries.len());
+        for (r, v) in entries {
+            match res.last_mut() {
+                // sorted by value and then start, so we only have to extend the last interval
+                Some((last, w)) if *w == v && last.end >= r.start => {
+                    if r.end > last.end {
+                        last.end = r.end;
+                    }
+                }
+                _ => res.push((r, v)),
+            }
+        }
+        *self = Self::from_vec(res);
+    }
+
+    /// True if both maps map the same points to the same values, no matter how the intervals are split
+    ///
+    /// This compares the [normalized](IntervalMap::normalize) representations, so it is O(N log N). Use `==` to
+    /// compare the representations.
+    pub fn eq_semantic(&self, other: &Self) -> bool
+    where
+        V: Ord + Clone,
+    {
+        let mut a = self.clone();
+        let mut b = other.clone();
+        a.normalize();
+        b.normalize();
+        a == b
+    }
+
+    /// The range of entries that start before the end of the query (as given by `starts_before`),
+    /// excluding the prefix where no interval ends after `after`.
+    fn candidate_range(&self, after: &T, starts_before: impl Fn(&T) -> bool) -> Range<usize> {
+        let to = self
+            .entries
+            .partition_point(|(r, _)| starts_before(&r.start));
+        // max_end is monotonic, so we can skip the prefix that ends before the query
+        let from = self.max_end[..to].partition_point(|end| end <= after);
+        from..to
+    }
+}
+
+impl<T: Ord + Clone, V> FromIterator<(Range<T>, V)> for IntervalMap<T, V> {
+    fn from_iter<I: IntoIterator<Item = (Range<T>, V)>>(iter: I) -> Self {
+        Self::from_vec(iter.into_iter().collect())
+    }
+}
+
+/// Build a set of ranges, keeping every non-empty range like [from_vec](IntervalMap::from_vec)
+///
+/// Use [range_set_from_iter](IntervalMap::range_set_from_iter) to merge overlapping or adjacent ranges.
+///
+/// # Example
+/// ```
+/// use vec_collections::IntervalMap;
+/// let set: IntervalMap<u32, ()> = vec![10..20, 0..5, 15..30, 5..7, 8..8].into_iter().collect();
+/// assert_eq!(set.ranges().cloned().collect::<Vec<_>>(), vec![0..5, 5..7, 10..20, 15..30]);
+/// ```
+impl<T: Ord + Clone> FromIterator<Range<T>> for IntervalMap<T, ()> {
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        iter.into_iter().map(|r| (r, ())).collect()
+    }
+}
+
+impl<T: Ord + Clone> IntervalMap<T, ()> {
+    /// Build a canonical set of ranges, with overlapping or adjacent ranges merged
+    ///
+    /// The ranges are sorted by start and then merged in a single sweep, so this is O(N log N), unlike adding the
+    /// ranges one by one. The result is the same as [normalize](IntervalMap::normalize) on the map of all ranges.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::IntervalMap;
+    /// let set = IntervalMap::range_set_from_iter(vec![10..20, 0..5, 15..30, 5..7, 8..8]);
+    /// assert_eq!(set.ranges().cloned().collect::<Vec<_>>(), vec![0..7, 10..30]);
+    /// ```
+    pub fn range_set_from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        let mut ranges: Vec<Range<T>> = iter.into_iter().filter(|r| r.start < r.end).collect();
+        ranges.sort_unstable_by(|a, b| a.start.cmp(&b.start));
+        let mut entries: Vec<(Range<T>, ())> = Vec::with_capacity(ranges.len());
+        for r in ranges {
+            match entries.last_mut() {
+                Some((last, _)) if last.end >= r.start => {
+                    if r.end > last.end {
+                        last.end = r.end;
+                    }
+                }
+                _ => entries.push((r, ())),
+            }
+        }
+        // the ranges are disjoint, so the max end is just the end
+        let max_end = entries.iter().map(|(r, _)| r.end.clone()).collect();
+        Self { entries, max_end }
+    }
+}
+
+impl<T: Ord + Clone, V> From<Vec<(Range<T>, V)>> for IntervalMap<T, V> {
+    fn from(value: Vec<(Range<T>, V)>) -> Self {
+        Self::from_vec(value)
+    }
+}
+
+impl<T: PrimInt, V> IntervalMap<T, V> {
+    /// All points that are contained in at least one interval, in ascending order
+    ///
+    /// This is lazy, but note that the number of points can be much larger than the number of intervals.
+    pub fn points(&self) -> impl Iterator<Item = T> + '_ {
+        let mut intervals = self.entries.iter().map(|(r, _)| r);
+        // the next point that has not been returned yet, and the end of the current run of intervals
+        let mut current = T::min_value()..T::min_value();
+        core::iter::from_fn(move || loop {
+            if current.start < current.end {
+                let point = current.start;
+                current.start = point + T::one();
+                return Some(point);
+            }
+            let r = intervals.next()?;
+            current = cmp::max(r.start, current.start)..r.end;
+        })
+    }
+
+    /// A uniformly random point that is covered by at least one of the ranges, or None if no point is covered.
+    ///
+    /// Points covered by several ranges are not more likely to be chosen. This takes two passes over the ranges,
+    /// but does not allocate.
+    ///
+    /// # Panics
+    ///
+    /// If the points do not fit into an i128.
+    #[cfg(feature = "rand")]
+    pub fn choose<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Option<T> {
+        let total: u128 = self
+            .covered_runs()
+            .map(|(start, end)| (end - start) as u128)
+            .sum();
+        if total == 0 {
+            return None;
+        }
+        let mut index = rng.gen_range(0, total);
+        for (start, end) in self.covered_runs() {
+            let len = (end - start) as u128;
+            if index < len {
+                return num_traits::NumCast::from(start + index as i128);
+            }
+            index -= len;
+        }
+        unreachable!()
+    }
+
+    /// The disjoint, non-empty runs of covered points as i128 half-open ranges, in ascending order
+    #[cfg(feature = "rand")]
+    fn covered_runs(&self) -> impl Iterator<Item = (i128, i128)> + '_ {
+        let to_i128 = |x: T| x.to_i128().expect("points must fit into an i128");
+        let mut intervals = self.entries.iter().map(|(r, _)| r);
+        let mut current: Option<(i128, i128)> = None;
+        core::iter::from_fn(move || loop {
+            match (intervals.next(), current) {
+                (Some(r), Some((start, end))) if to_i128(r.start) <= end => {
+                    current = Some((start, cmp::max(end, to_i128(r.end))));
+                }
+                (Some(r), prev) => {
+                    current =
+                        Some((to_i128(r.start), to_i128(r.end))).filter(|(start, end)| start < end);
+                    if prev.is_some() {
+                        return prev;
+                    }
+                }
+                (None, prev) => {
+                    current = None;
+                    return prev;
+                }
+            }
+        })
+    }
+}
+
+/// The error when converting a set of points to half-open ranges fails
+///
+/// The maximum value of the element type is not contained in any half-open range, so a set containing it can not
+/// be converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxValueError;
+
+impl fmt::Display for MaxValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the maximum value can not be represented as a half-open range"
+        )
+    }
+}
+
+impl std::error::Error for MaxValueError {}
+
+impl<T: PrimInt> IntervalMap<T, ()> {
+    /// Build a canonical set of ranges from pairs of start and end bounds, like the ones of [RangeBounds]
+    ///
+    /// The bounds are converted to half-open ranges. Empty ranges are dropped, and overlapping or adjacent ranges
+    /// are merged like in [range_set_from_iter](IntervalMap::range_set_from_iter). Fails if a non-empty range
+    /// contains the maximum value of `T`, e.g. because its end is unbounded.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::IntervalMap;
+    /// use std::ops::Bound::*;
+    /// let set = IntervalMap::<u32, ()>::from_bounds_iter(vec![
+    ///     (Included(0), Excluded(5)),
+    ///     (Excluded(4), Included(6)),
+    ///     (Included(9), Excluded(9)),
+    ///     (Unbounded, Included(1)),
+    /// ])
+    /// .unwrap();
+    /// assert_eq!(set.ranges().cloned().collect::<Vec<_>>(), vec![0..7]);
+    /// assert!(IntervalMap::<u32, ()>::from_bounds_iter(vec![(Included(3), Unbounded)]).is_err());
+    /// ```
+    ///
+    /// [RangeBounds]: core::ops::RangeBounds
+    pub fn from_bounds_iter<I: IntoIterator<Item = (Bound<T>, Bound<T>)>>(
+        iter: I,
+    ) -> Result<Self, MaxValueError> {
+        let mut ranges = Vec::new();
+        for (start, end) in iter {
+            let start = match start {
+                Bound::Included(x) => x,
+                // nothing is after the maximum value, so the range is empty
+                Bound::Excluded(x) if x == T::max_value() => continue,
+                Bound::Excluded(x) => x + T::one(),
+                Bound::Unbounded => T::min_value(),
+            };
+            let end = match end {
+                Bound::Excluded(x) => x,
+                Bound::Included(x) if x < start => continue,
+                Bound::Included(x) if x < T::max_value() => x + T::one(),
+                Bound::Included(_) | Bound::Unbounded => return Err(MaxValueError),
+            };
+            ranges.push(start..end);
+        }
+        Ok(Self::range_set_from_iter(ranges))
+    }
+}
+
+/// Converts a set of points to the canonical set of ranges, merging runs of consecutive points
+impl<T: PrimInt, A: Array<Item = T>> TryFrom<&VecSet<A>> for IntervalMap<T, ()> {
+    type Error = MaxValueError;
+
+    fn try_from(value: &VecSet<A>) -> Result<Self, Self::Error> {
+        let mut entries: Vec<(Range<T>, ())> = Vec::new();
+        for &point in value.iter() {
+            if point == T::max_value() {
+                return Err(MaxValueError);
+            }
+            match entries.last_mut() {
+                Some((last, _)) if last.end == point => last.end = point + T::one(),
+                _ => entries.push((point..point + T::one(), ())),
+            }
+        }
+        let max_end = entries.iter().map(|(r, _)| r.end).collect();
+        Ok(Self { entries, max_end })
+    }
+}
+
+/// Converts a set of ranges to the set of all points in the ranges, which is always possible but can be large
+impl<T: PrimInt, V, A: Array<Item = T>> From<&IntervalMap<T, V>> for VecSet<A> {
+    fn from(value: &IntervalMap<T, V>) -> Self {
+        VecSet::new_unsafe(value.points().collect())
+    }
+}
+
+impl<'a, T, V> IntoIterator for &'a IntervalMap<T, V> {
+    type Item = (&'a Range<T>, &'a V);
+    type IntoIter = Map<slice::Iter<'a, (Range<T>, V)>, fn(&'a (Range<T>, V)) -> Self::Item>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(r, v)| (r, v))
+    }
+}
+
+impl<T, V> IntoIterator for IntervalMap<T, V> {
+    type Item = (Range<T>, V);
+    type IntoIter = std::vec::IntoIter<(Range<T>, V)>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<T, V> Default for IntervalMap<T, V> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T: Clone, V: Clone> Clone for IntervalMap<T, V> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            max_end: self.max_end.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq, V: PartialEq> PartialEq for IntervalMap<T, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl<T: Eq, V: Eq> Eq for IntervalMap<T, V> {}
+
+impl<T: Hash, V: Hash> Hash for IntervalMap<T, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.entries.hash(state)
+    }
+}
+
+impl<T: fmt::Debug, V: fmt::Debug> fmt::Debug for IntervalMap<T, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    type Test = IntervalMap<u8, usize>;
+
+    fn mk(intervals: Vec<(u8, u8)>) -> (Test, Vec<(Range<u8>, usize)>) {
+        let entries: Vec<(Range<u8>, usize)> = intervals
+            .into_iter()
+            .enumerate()
+            .map(|(i, (a, b))| (a..b, i))
+            .collect();
+        let mut reference = entries.clone();
+        reference.retain(|(r, _)| r.start < r.end);
+        reference.sort_by_key(|(r, _)| (r.start, r.end));
+        (entries.into(), reference)
+    }
+
+    #[test]
+    fn iterate() {
+        let map = IntervalMap::range_set_from_iter(vec![4..6, 0..2, 1..3]);
+        let mut ranges = Vec::new();
+        for (r, _) in &map {
+            ranges.push(r.clone());
+        }
+        assert_eq!(ranges, vec![0..3, 4..6]);
+        assert!(map.ranges().eq(ranges.iter()));
+        assert!(map.clone().into_ranges().eq(ranges.clone()));
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            vec![(0..3, ()), (4..6, ())]
+        );
+    }
+
+    #[test]
+    fn split_by_max_value() {
+        let last = 245u8..255;
+        let map: IntervalMap<u8, ()> = core::iter::once(last.clone()).collect();
+        let buckets: Vec<_> = map.split_by(&(0..255), 10).collect();
+        assert_eq!(buckets.len(), 26);
+        assert_eq!(buckets[25].0, 250);
+        assert_eq!(buckets[25].1, vec![250..255]);
+        // a bucket that ends exactly at the maximum value
+        let buckets: Vec<_> = map.split_by(&(235..255), 10).collect();
+        assert_eq!(buckets, vec![(235, Vec::new()), (245, vec![last])]);
+    }
+
+    quickcheck! {
+        fn stab(intervals: Vec<(u8, u8)>, point: u8) -> bool {
+            let (map, reference) = mk(intervals);
+            let expected: Vec<_> = reference.iter().filter(|(r, _)| r.contains(&point)).map(|(_, v)| *v).collect();
+            let actual: Vec<_> = map.stab(&point).map(|(_, v)| *v).collect();
+            expected == actual && map.contains_point(&point) != expected.is_empty()
+        }
+
+        fn overlapping(intervals: Vec<(u8, u8)>, a: u8, b: u8) -> bool {
+            let (map, reference) = mk(intervals);
+            let query = a..b;
+            let expected: Vec<_> = reference
+                .iter()
+                .filter(|(r, _)| r.start.max(query.start) < r.end.min(query.end))
+                .map(|(_, v)| *v)
+                .collect();
+            let actual: Vec<_> = map.overlapping(&query).map(|(_, v)| *v).collect();
+            expected == actual
+        }
+
+        fn gaps(intervals: Vec<(u8, u8)>, a: u8, b: u8) -> bool {
+            let (map, _) = mk(intervals);
+            let within = a..b;
+            let gaps: Vec<_> = map.gaps(&within).collect();
+            // every point of within is either in a gap or covered by an interval
+            let partition = within.clone().all(|p| gaps.iter().any(|g| g.contains(&p)) != map.contains_point(&p));
+            // gaps are non-empty, ascending and maximal
+            let maximal = gaps.windows(2).all(|w| w[0].end < w[1].start);
+            partition && maximal && gaps.iter().all(|g| g.start < g.end)
+        }
+
+        fn normalize(intervals: Vec<(u8, u8, bool)>) -> bool {
+            let map: IntervalMap<u8, bool> = intervals.iter().map(|(a, b, v)| (*a..*b, *v)).collect();
+            // the same map, with each interval split in two
+            let split: IntervalMap<u8, bool> = intervals
+                .iter()
+                .flat_map(|(a, b, v)| {
+                    let mid = if a < b { a + (b - a) / 2 } else { *a };
+                    vec![(*a..mid, *v), (mid..*b, *v)]
+                })
+                .collect();
+            let mut normalized = map.clone();
+            normalized.normalize();
+            let stabbed = |m: &IntervalMap<u8, bool>, p: u8| m.stab(&p).map(|(_, v)| *v).collect::<Vec<_>>();
+            (0..=255u8).all(|p| {
+                let mut expected = stabbed(&map, p);
+                expected.sort();
+                expected.dedup();
+                let mut actual = stabbed(&normalized, p);
+                actual.sort();
+                expected == actual
+            }) && map.eq_semantic(&split)
+        }
+
+        fn from_bounds_iter(bounds: Vec<(u8, u8, u8)>) -> bool {
+            let bound = |kind: u8, x: u8| match kind % 3 {
+                0 => Bound::Included(x),
+                1 => Bound::Excluded(x),
+                _ => Bound::Unbounded,
+            };
+            let bounds: Vec<(Bound<u8>, Bound<u8>)> = bounds
+                .iter()
+                .map(|(kind, a, b)| (bound(kind / 3, *a), bound(*kind, *b)))
+                .collect();
+            let contains = |p: u8| bounds.iter().any(|r| core::ops::RangeBounds::contains(r, &p));
+            match IntervalMap::from_bounds_iter(bounds.iter().cloned()) {
+                Ok(set) => {
+                    let expected = IntervalMap::range_set_from_iter((0..255u8).filter(|p| contains(*p)).map(|p| p..p + 1));
+                    !contains(255) && set == expected
+                }
+                Err(MaxValueError) => contains(255),
+            }
+        }
+
+        fn covered(intervals: Vec<(u8, u8)>, a: u8, b: u8) -> bool {
+            let (map, _) = mk(intervals);
+            let within = a..b;
+            let covered = map.covered(&within);
+            let partition = within.clone().all(|p| covered.iter().any(|c| c.contains(&p)) == map.contains_point(&p));
+            let maximal = covered.windows(2).all(|w| w[0].end < w[1].start);
+            partition && maximal && covered.iter().all(|c| c.start < c.end)
+        }
+
+        fn split_by(intervals: Vec<(u8, u8)>, a: u8, b: u8, step: u8) -> bool {
+            let (map, _) = mk(intervals);
+            let within = a..b;
+            let step = step % 20 + 1;
+            let buckets: Vec<_> = map.split_by(&within, step).collect();
+            let starts_ok = buckets.iter().map(|(s, _)| *s).eq(within.clone().step_by(step as usize));
+            let covered_ok = buckets.iter().all(|(s, covered)| {
+                let bucket = *s..s.saturating_add(step).min(b);
+                *covered == map.covered(&bucket)
+            });
+            starts_ok && covered_ok
+        }
+
+        fn from_ranges(intervals: Vec<(u8, u8)>) -> bool {
+            let ranges = IntervalMap::range_set_from_iter(intervals.iter().map(|(a, b)| *a..*b));
+            let all: IntervalMap<u8, ()> = intervals.iter().map(|(a, b)| *a..*b).collect();
+            let mut expected: IntervalMap<u8, ()> = intervals.iter().map(|(a, b)| (*a..*b, ())).collect();
+            let kept = all == expected;
+            expected.normalize();
+            kept && ranges == expected
+        }
+
+        fn points(intervals: Vec<(u8, u8)>) -> bool {
+            let (map, _) = mk(intervals);
+            let expected: Vec<u8> = (0..=255u8).filter(|p| map.contains_point(p)).collect();
+            map.points().eq(expected.iter().cloned())
+        }
+
+        #[cfg(feature = "rand")]
+        fn choose(intervals: Vec<(u8, u8)>, seed: u64) -> bool {
+            use rand::SeedableRng;
+            let (map, _) = mk(intervals);
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let points: Vec<u8> = map.points().collect();
+            let chosen: Vec<Option<u8>> = (0..1000).map(|_| map.choose(&mut rng)).collect();
+            // with up to 10 covered points, 1000 samples hit all of them with overwhelming probability
+            let all_hit = points.len() > 10 || points.iter().all(|p| chosen.contains(&Some(*p)));
+            all_hit && chosen.iter().all(|p| match p {
+                Some(p) => map.contains_point(p),
+                None => points.is_empty(),
+            })
+        }
+
+        fn points_roundtrip(points: Vec<u8>) -> bool {
+            let set: VecSet<[u8; 4]> = points.into_iter().collect();
+            match IntervalMap::<u8, ()>::try_from(&set) {
+                Ok(ranges) => {
+                    let mut normalized = ranges.clone();
+                    normalized.normalize();
+                    normalized == ranges && VecSet::from(&ranges) == set
+                }
+                Err(MaxValueError) => set.contains(&255),
+            }
+        }
+
+        fn sorted(intervals: Vec<(u8, u8)>) -> bool {
+            let (map, reference) = mk(intervals);
+            map.len() == reference.len() && map.into_inner() == reference
+        }
+    }
+}
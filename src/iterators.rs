@@ -1,3 +1,5 @@
+use core::iter::FusedIterator;
+
 /// An interator that is guaranteed to be sorted by item
 pub struct VecSetIter<I> {
     i: I,
@@ -21,8 +23,32 @@ impl<I: Iterator> Iterator for VecSetIter<I> {
     fn size_hint(&self) -> (usize, Option<usize>) {
         self.i.size_hint()
     }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.i.nth(n)
+    }
+
+    fn last(self) -> Option<Self::Item> {
+        self.i.last()
+    }
+
+    fn count(self) -> usize {
+        self.i.count()
+    }
 }
 
+impl<I: DoubleEndedIterator> DoubleEndedIterator for VecSetIter<I> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.i.next_back()
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.i.nth_back(n)
+    }
+}
+
+impl<I: FusedIterator> FusedIterator for VecSetIter<I> {}
+
 /// An interator that is guaranteed to be sorted by key
 pub struct VecMapIter<I> {
     i: I,
@@ -46,8 +72,32 @@ impl<I: Iterator> Iterator for VecMapIter<I> {
     fn size_hint(&self) -> (usize, Option<usize>) {
         self.i.size_hint()
     }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.i.nth(n)
+    }
+
+    fn last(self) -> Option<Self::Item> {
+        self.i.last()
+    }
+
+    fn count(self) -> usize {
+        self.i.count()
+    }
 }
 
+impl<I: DoubleEndedIterator> DoubleEndedIterator for VecMapIter<I> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.i.next_back()
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.i.nth_back(n)
+    }
+}
+
+impl<I: FusedIterator> FusedIterator for VecMapIter<I> {}
+
 pub(crate) struct SliceIterator<'a, T>(pub &'a [T]);
 
 impl<'a, T> Iterator for SliceIterator<'a, T> {
@@ -21,6 +21,9 @@
 //! types with a cheap [Ord] instance, like primitive types, and small to medium sizes. Performance for insertion or removal of
 //! individual elements to/from large collections is bad, however. This is not the intended use case.
 //!
+//! With the `simd` feature, the `simd` module provides a faster intersection for sets of `u32` or `u64`. This is opt-in:
+//! the set operators keep using the generic merge, so call `simd::intersection` directly.
+//!
 //! # Collections overview
 //!
 //! ## [VecSet]
@@ -31,6 +34,23 @@
 //!
 //! Provides a map backed by a [SmallVec] of key value pairs.
 //!
+//! ## [VecBitSet]
+//!
+//! Provides a set of small integers (u8, u16) backed by a bitmap, with the same operations as a [VecSet].
+//!
+//! ## [StringVecSet]
+//!
+//! A read-only set of strings with prefix compression, for string sets with long shared prefixes like paths.
+//!
+//! ## [IntervalMap]
+//!
+//! A map from possibly overlapping intervals to values, with stabbing and overlap queries.
+//!
+//! ## Custom order
+//!
+//! Elements of a [VecSet] or keys of a [VecMap] can be wrapped in [OrderedBy] to use a custom [Comparator], e.g. for case insensitive strings
+//! or for floating point numbers using [TotalOrder].
+//!
 //! ## [RadixTree]
 //!
 //! A [RadixTree] that comes in different flavours.
@@ -47,9 +67,25 @@
 //!
 //! The in place operations use unsafe code. If that is a problem for you, let me know and I can hide them behind a feature.
 //!
+//! The same machinery is available for your own vec rewrites via the safe [TransformInPlace] trait.
+//!
+//! # Invariants
+//!
+//! All collections rely on their elements or keys being strictly sorted. An inconsistent [Ord] instance or an unchecked
+//! constructor like [VecSet::from_sorted_unchecked] can break this, which leads to wrong answers rather than a panic.
+//! To find the culprit, enable the `debug_invariants` feature. This checks the invariants on entry of most operations
+//! in debug builds. Use [VecSet::repair] or [VecMap::repair] to recover from known bad input.
+//!
 //! [SmallVec]: https://docs.rs/smallvec/1.4.1/smallvec/struct.SmallVec.html
 //! [VecSet]: struct.VecSet.html
 //! [VecMap]: struct.VecMap.html
+//! [VecBitSet]: struct.VecBitSet.html
+//! [StringVecSet]: struct.StringVecSet.html
+//! [IntervalMap]: struct.IntervalMap.html
+//! [TransformInPlace]: trait.TransformInPlace.html
+//! [OrderedBy]: struct.OrderedBy.html
+//! [Comparator]: trait.Comparator.html
+//! [TotalOrder]: struct.TotalOrder.html
 //! [TotalVecSet]: struct.TotalVecSet
 //! [TotalVecMap]: struct.TotalVecMap
 //! [RadixTree]: radix_tree/struct.RadixTree.html
@@ -72,8 +108,16 @@ extern crate maplit;
 extern crate sorted_iter;
 pub use sorted_iter::{SortedIterator, SortedPairIterator};
 
+mod bounded_vec_set;
+mod in_place;
+mod merge_actions;
 mod merge_state;
+mod string_vec_set;
+mod value_indexed_vec_map;
 
+mod interval_map;
+mod lookup_index;
+mod vec_bit_set;
 mod vec_map;
 mod vec_set;
 
@@ -86,16 +130,39 @@ pub mod total_vec_map;
 #[cfg(feature = "total")]
 pub mod total_vec_set;
 
+#[cfg(feature = "crdt")]
+pub mod crdt;
+
+#[cfg(feature = "size_stats")]
+mod size_stats;
+
 #[cfg(feature = "std_support")]
 pub mod btree_map;
 
+pub mod compact;
+
+#[cfg(feature = "simd")]
+pub mod simd;
+
+mod comparator;
 mod dedup;
 mod iterators;
 
 mod macros;
 
-pub use dedup::{sort_dedup, sort_dedup_by_key};
-pub use macros::*;
-pub use smallvec::Array;
+pub use binary_merge::{MergeOperation, MergeState};
+pub use bounded_vec_set::BoundedVecSet;
+pub use comparator::*;
+pub use dedup::{dedup_by_key_keep, sort_dedup, sort_dedup_by_key, DedupByKeyKeep, Keep};
+pub use in_place::{CapacityPolicy, TransformInPlace};
+pub use interval_map::{IntervalMap, MaxValueError};
+pub use lookup_index::VecSetIndex;
+pub use merge_actions::{merge_actions, MergeAction};
+#[cfg(feature = "size_stats")]
+pub use size_stats::SizeStats;
+pub use smallvec::{Array, CollectionAllocErr};
+pub use string_vec_set::{StringVecSet, StringVecSetIter};
+pub use value_indexed_vec_map::ValueIndexedVecMap;
+pub use vec_bit_set::*;
 pub use vec_map::*;
 pub use vec_set::*;
@@ -0,0 +1,174 @@
+use crate::VecSet;
+use core::hash::{Hash, Hasher};
+use smallvec::Array;
+
+/// A lookup accelerator for a large [VecSet].
+///
+/// This is a small bloom filter over the elements of the set, created with [VecSet::build_index]. Most negative
+/// membership tests are answered by looking at a single word of the filter, without touching the elements
+/// themselves. Positive tests and false positives fall back to a binary search.
+///
+/// The filter uses about one byte per element. It borrows the set, so it can not get out of sync with the
+/// elements, and the set itself stays as compact as before.
+///
+/// # Example
+/// ```
+/// use vec_collections::VecSet;
+/// let set: VecSet<[u64; 4]> = (0..10000).map(|x| x * 3).collect();
+/// let index = set.build_index();
+/// assert!(index.contains(&300));
+/// assert!(!index.contains(&301));
+/// ```
+pub struct VecSetIndex<'a, T> {
+    elements: &'a [T],
+    /// bloom filter bits. The length is a power of two.
+    bits: Vec<u64>,
+}
+
+impl<'a, T: Ord + Hash> VecSetIndex<'a, T> {
+    /// Creates an index for a slice, that must be sorted and deduplicated
+    fn new(elements: &'a [T]) -> Self {
+        // about 8 bits per element, rounded up to a power of two number of words
+        let words = (elements.len() / 8).max(1).next_power_of_two();
+        let mut bits = vec![0u64; words];
+        for element in elements {
+            let (word, mask) = Self::probe(element, words);
+            bits[word] |= mask;
+        }
+        Self { elements, bits }
+    }
+
+    /// The word and the bits within that word for a value
+    ///
+    /// Both probes of the filter are in the same word, so a lookup only touches a single cache line.
+    fn probe(value: &T, words: usize) -> (usize, u64) {
+        let mut hasher = FxHasher::default();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let word = (hash >> 32) as usize & (words - 1);
+        let mask = (1u64 << (hash & 63)) | (1u64 << ((hash >> 6) & 63));
+        (word, mask)
+    }
+
+    /// true if the indexed set contains the value
+    pub fn contains(&self, value: &T) -> bool {
+        self.may_contain(value) && self.elements.binary_search(value).is_ok()
+    }
+
+    /// false if the indexed set definitely does not contain the value
+    ///
+    /// If this returns true, the set contains the value with high probability.
+    pub fn may_contain(&self, value: &T) -> bool {
+        let (word, mask) = Self::probe(value, self.bits.len());
+        self.bits[word] & mask == mask
+    }
+}
+
+impl<'a, T> VecSetIndex<'a, T> {
+    /// The elements of the indexed set
+    pub fn as_slice(&self) -> &'a [T] {
+        self.elements
+    }
+
+    /// The size of the index in bytes, not including the set itself
+    pub fn index_size(&self) -> usize {
+        self.bits.len() * 8
+    }
+}
+
+impl<A: Array> VecSet<A>
+where
+    A::Item: Ord + Hash,
+{
+    /// Build a [VecSetIndex] to speed up negative membership tests.
+    ///
+    /// This is only worth it for large sets that are queried often, since building the index is O(N).
+    pub fn build_index(&self) -> VecSetIndex<'_, A::Item> {
+        VecSetIndex::new(self)
+    }
+}
+
+/// The hash function used by rustc. Very fast, and good enough for a bloom filter.
+#[derive(Default)]
+struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(Self::SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(chunk);
+            self.add_to_hash(u64::from_le_bytes(word));
+        }
+        for byte in chunks.remainder() {
+            self.add_to_hash(*byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        // the low bits of an fx hash are weak, so mix them with the high bits
+        self.hash ^ (self.hash >> 29)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    quickcheck! {
+        fn contains_consistent(elements: Vec<u32>, probes: Vec<u32>) -> bool {
+            let set: VecSet<[u32; 4]> = elements.into_iter().collect();
+            let index = set.build_index();
+            set.iter().all(|x| index.may_contain(x) && index.contains(x))
+                && probes.iter().all(|x| index.contains(x) == set.contains(x))
+        }
+
+        fn strings(elements: Vec<String>, probes: Vec<String>) -> bool {
+            let set: VecSet<[String; 1]> = elements.into_iter().collect();
+            let index = set.build_index();
+            probes.iter().chain(set.iter()).all(|x| index.contains(x) == set.contains(x))
+        }
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let set: VecSet<[u64; 4]> = (0..100000u64).map(|x| x * 2).collect();
+        let index = set.build_index();
+        let false_positives = (0..100000u64)
+            .map(|x| x * 2 + 1)
+            .filter(|x| index.may_contain(x))
+            .count();
+        assert!(false_positives < 20000, "{}", false_positives);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::iterators::SliceIterator;
+use binary_merge::{MergeOperation, MergeState};
+use core::cmp::Ordering;
+
+/// A single step of merging two sorted sequences `a` and `b`.
+///
+/// A sequence of actions describes how the elements of `a` and `b` interleave, so it can be used to debug a merge,
+/// or to replicate a merge on another copy of `a` given just the actions and the elements of `b`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeAction {
+    /// The next n elements of a are smaller than all remaining elements of b
+    FromA(usize),
+    /// The next n elements of b are smaller than all remaining elements of a
+    FromB(usize),
+    /// The next element of a is equal to the next element of b
+    Collision,
+}
+
+/// Run a merge operation on a merge state, and record the actions the operation performs.
+///
+/// Each call of [from_a](MergeOperation::from_a), [from_b](MergeOperation::from_b) or
+/// [collision](MergeOperation::collision) is recorded and then passed on to `op`, so `state` contains the result
+/// of the merge afterwards. If the operation returns false, the merge stops after the last recorded action.
+/// The merge uses the default algorithm of [MergeOperation] with the threshold of `op`.
+///
+/// The merge runs to completion before this returns, so the iterator is not lazy. Consecutive runs of
+/// [FromA](MergeAction::FromA) or [FromB](MergeAction::FromB) are combined, so the result is the same
+/// no matter which merge algorithm was used.
+///
+/// # Example
+/// ```
+/// use std::cmp::Ordering;
+/// use vec_collections::{merge_actions, MergeAction::*, MergeOperation, MergeState};
+///
+/// // a merge state that keeps the elements of a that are not in b
+/// struct Difference<'a> {
+///     a: &'a [u32],
+///     b: &'a [u32],
+///     res: Vec<u32>,
+/// }
+///
+/// impl<'a> MergeState for Difference<'a> {
+///     type A = u32;
+///     type B = u32;
+///     fn a_slice(&self) -> &[u32] {
+///         self.a
+///     }
+///     fn b_slice(&self) -> &[u32] {
+///         self.b
+///     }
+/// }
+///
+/// struct DifferenceOp;
+///
+/// impl<'a> MergeOperation<Difference<'a>> for DifferenceOp {
+///     fn cmp(&self, a: &u32, b: &u32) -> Ordering {
+///         a.cmp(b)
+///     }
+///     fn from_a(&self, m: &mut Difference<'a>, n: usize) -> bool {
+///         m.res.extend_from_slice(&m.a[..n]);
+///         m.a = &m.a[n..];
+///         true
+///     }
+///     fn from_b(&self, m: &mut Difference<'a>, n: usize) -> bool {
+///         m.b = &m.b[n..];
+///         true
+///     }
+///     fn collision(&self, m: &mut Difference<'a>) -> bool {
+///         m.a = &m.a[1..];
+///         m.b = &m.b[1..];
+///         true
+///     }
+/// }
+///
+/// let mut state = Difference { a: &[1, 2, 3, 7], b: &[3, 4, 5], res: Vec::new() };
+/// let actions: Vec<_> = merge_actions(&mut state, &DifferenceOp).collect();
+/// assert_eq!(actions, vec![FromA(2), Collision, FromB(2), FromA(1)]);
+/// assert_eq!(state.res, vec![1, 2, 7]);
+/// ```
+pub fn merge_actions<M, O>(state: &mut M, op: &O) -> std::vec::IntoIter<MergeAction>
+where
+    M: MergeState,
+    O: MergeOperation<M>,
+{
+    let mut recorder = Recorder {
+        inner: state,
+        actions: Vec::new(),
+    };
+    RecordOp(op).merge(&mut recorder);
+    recorder.actions.into_iter()
+}
+
+/// The actions to merge two sorted slices, using just the given comparison function.
+pub(crate) fn merge_actions_by<A, B, F>(a: &[A], b: &[B], cmp: F) -> std::vec::IntoIter<MergeAction>
+where
+    F: Fn(&A, &B) -> Ordering,
+{
+    let mut state = SliceMergeState {
+        a: SliceIterator(a),
+        b: SliceIterator(b),
+    };
+    merge_actions(&mut state, &SkipOp(cmp))
+}
+
+/// A merge state that wraps another state and records the actions
+struct Recorder<'a, M> {
+    inner: &'a mut M,
+    actions: Vec<MergeAction>,
+}
+
+impl<'a, M> Recorder<'a, M> {
+    fn record(&mut self, action: MergeAction) {
+        use MergeAction::*;
+        match (self.actions.last_mut(), action) {
+            (Some(FromA(m)), FromA(n)) => *m += n,
+            (Some(FromB(m)), FromB(n)) => *m += n,
+            _ => self.actions.push(action),
+        }
+    }
+}
+
+impl<'a, M: MergeState> MergeState for Recorder<'a, M> {
+    type A = M::A;
+    type B = M::B;
+    fn a_slice(&self) -> &[M::A] {
+        self.inner.a_slice()
+    }
+    fn b_slice(&self) -> &[M::B] {
+        self.inner.b_slice()
+    }
+}
+
+/// Records each action and then passes it on to the wrapped operation
+struct RecordOp<'o, O>(&'o O);
+
+impl<'a, 'o, M: MergeState, O: MergeOperation<M>> MergeOperation<Recorder<'a, M>>
+    for RecordOp<'o, O>
+{
+    fn cmp(&self, a: &M::A, b: &M::B) -> Ordering {
+        self.0.cmp(a, b)
+    }
+    fn from_a(&self, m: &mut Recorder<'a, M>, n: usize) -> bool {
+        m.record(MergeAction::FromA(n));
+        self.0.from_a(m.inner, n)
+    }
+    fn from_b(&self, m: &mut Recorder<'a, M>, n: usize) -> bool {
+        m.record(MergeAction::FromB(n));
+        self.0.from_b(m.inner, n)
+    }
+    fn collision(&self, m: &mut Recorder<'a, M>) -> bool {
+        m.record(MergeAction::Collision);
+        self.0.collision(m.inner)
+    }
+    const MCM_THRESHOLD: usize = O::MCM_THRESHOLD;
+}
+
+/// A merge state that just walks over two slices
+struct SliceMergeState<'a, A, B> {
+    a: SliceIterator<'a, A>,
+    b: SliceIterator<'a, B>,
+}
+
+impl<'a, A, B> MergeState for SliceMergeState<'a, A, B> {
+    type A = A;
+    type B = B;
+    fn a_slice(&self) -> &[A] {
+        self.a.as_slice()
+    }
+    fn b_slice(&self) -> &[B] {
+        self.b.as_slice()
+    }
+}
+
+/// An operation that skips over the elements without producing anything
+struct SkipOp<F>(F);
+
+impl<'a, A, B, F: Fn(&A, &B) -> Ordering> MergeOperation<SliceMergeState<'a, A, B>> for SkipOp<F> {
+    fn cmp(&self, a: &A, b: &B) -> Ordering {
+        (self.0)(a, b)
+    }
+    fn from_a(&self, m: &mut SliceMergeState<'a, A, B>, n: usize) -> bool {
+        m.a.drop_front(n);
+        true
+    }
+    fn from_b(&self, m: &mut SliceMergeState<'a, A, B>, n: usize) -> bool {
+        m.b.drop_front(n);
+        true
+    }
+    fn collision(&self, m: &mut SliceMergeState<'a, A, B>) -> bool {
+        m.a.drop_front(1);
+        m.b.drop_front(1);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VecSet;
+    use quickcheck::quickcheck;
+
+    type Test = VecSet<[u8; 2]>;
+
+    quickcheck! {
+        fn replay_union(a: Test, b: Test) -> bool {
+            // replay the actions to compute the union
+            let mut res: Vec<&u8> = Vec::new();
+            let (mut ai, mut bi) = (a.iter(), b.iter());
+            for action in a.merge_actions(&b) {
+                match action {
+                    MergeAction::FromA(n) => res.extend((&mut ai).take(n)),
+                    MergeAction::FromB(n) => res.extend((&mut bi).take(n)),
+                    MergeAction::Collision => {
+                        res.extend(ai.next());
+                        bi.next();
+                    }
+                }
+            }
+            let expected = &a | &b;
+            ai.next().is_none() && bi.next().is_none() && res.into_iter().eq(expected.iter())
+        }
+
+        fn stops_with_op(a: Test, b: Test) -> bool {
+            // an operation that stops at the first collision
+            struct UntilCollision;
+            impl<'a> MergeOperation<SliceMergeState<'a, u8, u8>> for UntilCollision {
+                fn cmp(&self, a: &u8, b: &u8) -> Ordering {
+                    a.cmp(b)
+                }
+                fn from_a(&self, m: &mut SliceMergeState<'a, u8, u8>, n: usize) -> bool {
+                    SkipOp(u8::cmp).from_a(m, n)
+                }
+                fn from_b(&self, m: &mut SliceMergeState<'a, u8, u8>, n: usize) -> bool {
+                    SkipOp(u8::cmp).from_b(m, n)
+                }
+                fn collision(&self, _: &mut SliceMergeState<'a, u8, u8>) -> bool {
+                    false
+                }
+            }
+            let mut state = SliceMergeState { a: SliceIterator(a.as_slice()), b: SliceIterator(b.as_slice()) };
+            let actions: Vec<_> = merge_actions(&mut state, &UntilCollision).collect();
+            let all: Vec<_> = a.merge_actions(&b).collect();
+            let expected = match all.iter().position(|x| *x == MergeAction::Collision) {
+                Some(i) => &all[..=i],
+                None => &all[..],
+            };
+            // the elements after the collision are still in the state
+            let consumed = expected.iter().map(|x| match x {
+                MergeAction::FromA(n) => *n,
+                _ => 0,
+            }).sum::<usize>();
+            actions == expected && state.a.as_slice().len() == a.len() - consumed
+        }
+
+        fn runs_combined(a: Test, b: Test) -> bool {
+            let actions: Vec<_> = a.merge_actions(&b).collect();
+            !actions.windows(2).any(|w| matches!(
+                w,
+                [MergeAction::FromA(_), MergeAction::FromA(_)] | [MergeAction::FromB(_), MergeAction::FromB(_)]
+            ))
+        }
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 use crate::iterators::SliceIterator;
 use binary_merge::{MergeOperation, MergeState};
-use core::{fmt, fmt::Debug};
+use core::{cmp::Ordering, fmt, fmt::Debug, iter::Peekable};
 use inplace_vec_builder::{InPlaceSmallVecBuilder, InPlaceVecBuilder};
 use smallvec::{Array, SmallVec};
 use std::marker::PhantomData;
@@ -78,106 +78,91 @@ impl<'a, A: Array, B: Array, C: Converter<B::Item, A::Item>> InPlaceMergeState<'
     }
 }
 
-/// An in place merge state where the rhs is a reference
-pub(crate) struct InPlaceSmallVecMergeStateRef<
-    'a,
-    A: Array,
-    B,
-    C: Converter<&'a B, A::Item> = NoConverter,
-> {
-    pub(crate) a: InPlaceSmallVecBuilder<'a, A>,
-    pub(crate) b: SliceIterator<'a, B>,
-    _c: PhantomData<C>,
+/// Common interface of the in place builders for [Vec] and [SmallVec]
+///
+/// A builder consists of a target part at the front and a source part at the back of the same allocation.
+/// Dropping the builder drops the remaining source part and leaves the target part.
+pub(crate) trait InPlaceBuilder {
+    type Item;
+    /// The remaining source part as a slice
+    fn source_slice(&self) -> &[Self::Item];
+    /// The remaining source part as a mutable slice
+    fn source_slice_mut(&mut self) -> &mut [Self::Item];
+    /// Consume `n` elements from the source, moving them to the target if `take` is true or dropping them otherwise
+    fn consume(&mut self, n: usize, take: bool);
+    /// Take at most `n` elements from `iter` to the target
+    fn extend_from_iter<I: Iterator<Item = Self::Item>>(&mut self, iter: I, n: usize);
+    /// Push a single value to the target
+    fn push(&mut self, value: Self::Item);
+    /// Take the next element from the source, if it exists
+    fn pop_front(&mut self) -> Option<Self::Item>;
 }
 
-impl<'a, A: Array, B, C: Converter<&'a B, A::Item>> InPlaceSmallVecMergeStateRef<'a, A, B, C> {
-    fn new(a: &'a mut SmallVec<A>, b: &'a impl AsRef<[B]>) -> Self {
-        Self {
-            a: a.into(),
-            b: SliceIterator(b.as_ref()),
-            _c: PhantomData,
-        }
+impl<'a, A: Array> InPlaceBuilder for InPlaceSmallVecBuilder<'a, A> {
+    type Item = A::Item;
+    fn source_slice(&self) -> &[A::Item] {
+        self.source_slice()
     }
-}
-
-impl<'a, A: Array, B, C: Converter<&'a B, A::Item>> MergeState
-    for InPlaceSmallVecMergeStateRef<'a, A, B, C>
-{
-    type A = A::Item;
-    type B = B;
-    fn a_slice(&self) -> &[A::Item] {
-        self.a.source_slice()
+    fn source_slice_mut(&mut self) -> &mut [A::Item] {
+        self.source_slice_mut()
     }
-    fn b_slice(&self) -> &[B] {
-        self.b.as_slice()
+    fn consume(&mut self, n: usize, take: bool) {
+        self.consume(n, take)
     }
-}
-
-impl<'a, A: Array, B, C: Converter<&'a B, A::Item>> MergeStateMut
-    for InPlaceSmallVecMergeStateRef<'a, A, B, C>
-where
-    A::Item: Clone,
-{
-    fn advance_a(&mut self, n: usize, take: bool) -> bool {
-        self.a.consume(n, take);
-        true
+    fn extend_from_iter<I: Iterator<Item = A::Item>>(&mut self, iter: I, n: usize) {
+        self.extend_from_iter(iter, n)
     }
-    fn advance_b(&mut self, n: usize, take: bool) -> bool {
-        if take {
-            self.a.extend_from_iter((&mut self.b).map(C::convert), n);
-        } else {
-            for _ in 0..n {
-                let _ = self.b.next();
-            }
-        }
-        true
+    fn push(&mut self, value: A::Item) {
+        self.push(value)
     }
-}
-
-impl<'a, A, B, C: Converter<&'a B, A::Item>> MutateInput
-    for InPlaceSmallVecMergeStateRef<'a, A, B, C>
-where
-    A: Array,
-    A::Item: Clone,
-{
-    fn source_slices_mut(&mut self) -> (&mut [Self::A], &[Self::B]) {
-        (self.a.source_slice_mut(), self.b.as_slice())
+    fn pop_front(&mut self) -> Option<A::Item> {
+        self.pop_front()
     }
 }
 
-impl<'a, A: Array, B: 'a, C: Converter<&'a B, A::Item>> InPlaceSmallVecMergeStateRef<'a, A, B, C> {
-    pub fn merge<O: MergeOperation<Self>>(
-        a: &'a mut SmallVec<A>,
-        b: &'a impl AsRef<[B]>,
-        o: O,
-        _: C,
-    ) {
-        let mut state = Self::new(a, b);
-        o.merge(&mut state);
+impl<'a, T> InPlaceBuilder for InPlaceVecBuilder<'a, T> {
+    type Item = T;
+    fn source_slice(&self) -> &[T] {
+        self.source_slice()
+    }
+    fn source_slice_mut(&mut self) -> &mut [T] {
+        self.source_slice_mut()
+    }
+    fn consume(&mut self, n: usize, take: bool) {
+        self.consume(n, take)
+    }
+    fn extend_from_iter<I: Iterator<Item = T>>(&mut self, iter: I, n: usize) {
+        self.extend_from_iter(iter, n)
+    }
+    fn push(&mut self, value: T) {
+        self.push(value)
+    }
+    fn pop_front(&mut self) -> Option<T> {
+        self.pop_front()
     }
 }
 
 /// An in place merge state where the rhs is a reference
-pub(crate) struct InPlaceVecMergeStateRef<'a, A, B, C: Converter<&'a B, A> = NoConverter> {
-    pub(crate) a: InPlaceVecBuilder<'a, A>,
+pub(crate) struct InPlaceMergeStateRef<'a, W, B, C = NoConverter> {
+    pub(crate) a: W,
     pub(crate) b: SliceIterator<'a, B>,
     _c: PhantomData<C>,
 }
 
-impl<'a, A, B, C: Converter<&'a B, A>> InPlaceVecMergeStateRef<'a, A, B, C> {
-    fn new(a: &'a mut Vec<A>, b: &'a impl AsRef<[B]>) -> Self {
-        Self {
-            a: a.into(),
-            b: SliceIterator(b.as_ref()),
-            _c: PhantomData,
-        }
-    }
-}
+/// An in place merge state for a [SmallVec] where the rhs is a reference
+pub(crate) type InPlaceSmallVecMergeStateRef<'a, A, B, C = NoConverter> =
+    InPlaceMergeStateRef<'a, InPlaceSmallVecBuilder<'a, A>, B, C>;
 
-impl<'a, A, B, C: Converter<&'a B, A>> MergeState for InPlaceVecMergeStateRef<'a, A, B, C> {
-    type A = A;
+/// An in place merge state for a [Vec] where the rhs is a reference
+pub(crate) type InPlaceVecMergeStateRef<'a, A, B, C = NoConverter> =
+    InPlaceMergeStateRef<'a, InPlaceVecBuilder<'a, A>, B, C>;
+
+impl<'a, W: InPlaceBuilder, B, C: Converter<&'a B, W::Item>> MergeState
+    for InPlaceMergeStateRef<'a, W, B, C>
+{
+    type A = W::Item;
     type B = B;
-    fn a_slice(&self) -> &[A] {
+    fn a_slice(&self) -> &[W::Item] {
         self.a.source_slice()
     }
     fn b_slice(&self) -> &[B] {
@@ -185,9 +170,8 @@ impl<'a, A, B, C: Converter<&'a B, A>> MergeState for InPlaceVecMergeStateRef<'a
     }
 }
 
-impl<'a, A, B, C: Converter<&'a B, A>> MergeStateMut for InPlaceVecMergeStateRef<'a, A, B, C>
-where
-    A: Clone,
+impl<'a, W: InPlaceBuilder, B, C: Converter<&'a B, W::Item>> MergeStateMut
+    for InPlaceMergeStateRef<'a, W, B, C>
 {
     fn advance_a(&mut self, n: usize, take: bool) -> bool {
         self.a.consume(n, take);
@@ -205,22 +189,69 @@ where
     }
 }
 
-impl<'a, A, B, C: Converter<&'a B, A>> MutateInput for InPlaceVecMergeStateRef<'a, A, B, C>
-where
-    A: Clone,
+impl<'a, W: InPlaceBuilder, B, C: Converter<&'a B, W::Item>> MutateInput
+    for InPlaceMergeStateRef<'a, W, B, C>
 {
     fn source_slices_mut(&mut self) -> (&mut [Self::A], &[Self::B]) {
         (self.a.source_slice_mut(), self.b.as_slice())
     }
 }
 
-impl<'a, A, B: 'a, C: Converter<&'a B, A>> InPlaceVecMergeStateRef<'a, A, B, C> {
-    pub fn merge<O: MergeOperation<Self>>(a: &'a mut Vec<A>, b: &'a impl AsRef<[B]>, o: O, _: C) {
-        let mut state = Self::new(a, b);
+impl<'a, W: InPlaceBuilder, B: 'a, C: Converter<&'a B, W::Item>> InPlaceMergeStateRef<'a, W, B, C> {
+    pub fn merge<O: MergeOperation<Self>>(a: impl Into<W>, b: &'a impl AsRef<[B]>, o: O, _: C) {
+        let mut state = Self {
+            a: a.into(),
+            b: SliceIterator(b.as_ref()),
+            _c: PhantomData,
+        };
         o.merge(&mut state);
     }
 }
 
+/// An in place merge state for a [SmallVec] where the rhs is a strictly sorted iterator
+///
+/// The rhs is not a slice, so this can not be used with a [MergeOperation]. It only supports a union, which is done
+/// as a tape merge, taking runs of elements from the lhs at once.
+pub(crate) struct InPlaceIterMergeState<'a, A: Array, I: Iterator> {
+    a: InPlaceSmallVecBuilder<'a, A>,
+    b: Peekable<I>,
+}
+
+impl<'a, A: Array, I: Iterator<Item = A::Item>> InPlaceIterMergeState<'a, A, I>
+where
+    A::Item: Ord,
+{
+    /// Merge the elements of `b` into `a`, keeping the element of `a` for elements that are in both
+    pub fn union(a: &'a mut SmallVec<A>, b: I) {
+        // make room for the rhs, so the lhs is moved at most once
+        a.reserve(b.size_hint().0);
+        let mut state = Self {
+            a: a.into(),
+            b: b.peekable(),
+        };
+        while let Some(b) = state.b.peek() {
+            let source = state.a.source_slice();
+            match source.first().map(|a| a.cmp(b)) {
+                Some(Ordering::Less) => {
+                    let n = source.partition_point(|a| a < b);
+                    state.a.consume(n, true);
+                }
+                Some(Ordering::Equal) => {
+                    state.a.consume(1, true);
+                    state.b.next();
+                }
+                Some(Ordering::Greater) | None => {
+                    let b = state.b.next().unwrap();
+                    state.a.push(b);
+                }
+            }
+        }
+        // dropping the builder drops the remaining source, so take the elements of a that are after all of b
+        let n = state.a.source_slice().len();
+        state.a.consume(n, true);
+    }
+}
+
 /// A merge state where we only track if elements have been produced, and abort as soon as the first element is produced
 pub(crate) struct BoolOpMergeState<'a, A, B> {
     a: SliceIterator<'a, A>,
@@ -290,6 +321,69 @@ impl<'a, A, B> MergeStateMut for BoolOpMergeState<'a, A, B> {
     }
 }
 
+/// A merge state where we only track which kinds of elements exist, and abort as soon as all kinds have been seen
+pub(crate) struct RelationMergeState<'a, A, B> {
+    a: SliceIterator<'a, A>,
+    b: SliceIterator<'a, B>,
+    /// there are elements only in a
+    pub a_only: bool,
+    /// there are elements only in b
+    pub b_only: bool,
+    /// there are elements in both a and b
+    pub both: bool,
+}
+
+impl<'a, A, B> RelationMergeState<'a, A, B> {
+    pub fn merge<O: MergeOperation<Self>>(a: &'a [A], b: &'a [B], o: O) -> Self {
+        let mut state = Self {
+            a: SliceIterator(a),
+            b: SliceIterator(b),
+            a_only: false,
+            b_only: false,
+            both: false,
+        };
+        o.merge(&mut state);
+        state
+    }
+
+    /// Consume n elements of a that are not in b
+    pub fn advance_a(&mut self, n: usize) -> bool {
+        self.a.drop_front(n);
+        self.a_only = true;
+        !self.done()
+    }
+
+    /// Consume n elements of b that are not in a
+    pub fn advance_b(&mut self, n: usize) -> bool {
+        self.b.drop_front(n);
+        self.b_only = true;
+        !self.done()
+    }
+
+    /// Consume one element of both a and b
+    pub fn advance_both(&mut self) -> bool {
+        self.a.drop_front(1);
+        self.b.drop_front(1);
+        self.both = true;
+        !self.done()
+    }
+
+    fn done(&self) -> bool {
+        self.a_only && self.b_only && self.both
+    }
+}
+
+impl<'a, A, B> MergeState for RelationMergeState<'a, A, B> {
+    type A = A;
+    type B = B;
+    fn a_slice(&self) -> &[A] {
+        self.a.as_slice()
+    }
+    fn b_slice(&self) -> &[B] {
+        self.b.as_slice()
+    }
+}
+
 pub trait Converter<A, B> {
     fn convert(value: A) -> B;
 }
@@ -3,7 +3,7 @@ use internals::{AbstractRadixTreeMut as _, Fragment};
 use lazy_static::lazy_static;
 use std::{collections::BTreeMap, sync::Arc};
 
-use super::{location, offset_from, AbstractRadixTree, RadixTree, TKey, TValue};
+use super::{location, offset_from, AbstractRadixTree, RadixTree, TArchivedKey, TValue};
 use rkyv::{
     de::SharedDeserializeRegistry,
     ser::{ScratchSpace, Serializer, SharedSerializeRegistry},
@@ -34,7 +34,7 @@ fn wrap_in_arc<T>(data: Vec<T>) -> Arc<Vec<T>> {
 #[derive(Clone)]
 pub struct ArcRadixTree<K, V>
 where
-    K: TKey,
+    K: TArchivedKey,
     V: TValue,
 {
     prefix: Fragment<K>,
@@ -42,7 +42,7 @@ where
     children: Arc<Vec<Self>>,
 }
 
-impl<K: TKey, V: TValue> Default for ArcRadixTree<K, V> {
+impl<K: TArchivedKey, V: TValue> Default for ArcRadixTree<K, V> {
     fn default() -> Self {
         Self {
             prefix: Default::default(),
@@ -52,7 +52,7 @@ impl<K: TKey, V: TValue> Default for ArcRadixTree<K, V> {
     }
 }
 
-impl<K: TKey, V: TValue> AbstractRadixTree<K, V> for ArcRadixTree<K, V> {
+impl<K: TArchivedKey, V: TValue> AbstractRadixTree<K, V> for ArcRadixTree<K, V> {
     type Materialized = ArcRadixTree<K, V>;
 
     fn prefix(&self) -> &[K] {
@@ -68,7 +68,7 @@ impl<K: TKey, V: TValue> AbstractRadixTree<K, V> for ArcRadixTree<K, V> {
     }
 }
 
-impl<K: TKey, V: TValue> internals::AbstractRadixTreeMut<K, V> for ArcRadixTree<K, V> {
+impl<K: TArchivedKey, V: TValue> internals::AbstractRadixTreeMut<K, V> for ArcRadixTree<K, V> {
     fn new(prefix: Fragment<K>, value: Option<V>, children: Vec<Self>) -> Self {
         let children = wrap_in_arc(children);
         Self {
@@ -96,7 +96,7 @@ impl<K: TKey, V: TValue> internals::AbstractRadixTreeMut<K, V> for ArcRadixTree<
     }
 }
 
-impl<K: TKey, V: TValue> From<RadixTree<K, V>> for ArcRadixTree<K, V> {
+impl<K: TArchivedKey, V: TValue> From<RadixTree<K, V>> for ArcRadixTree<K, V> {
     fn from(value: RadixTree<K, V>) -> Self {
         let RadixTree {
             prefix,
@@ -108,7 +108,13 @@ impl<K: TKey, V: TValue> From<RadixTree<K, V>> for ArcRadixTree<K, V> {
     }
 }
 
-impl<K: TKey, V: TValue> ArcRadixTree<K, V> {
+impl<K: TArchivedKey, V: TValue> From<&ArcRadixTree<K, V>> for RadixTree<K, V> {
+    fn from(value: &ArcRadixTree<K, V>) -> Self {
+        value.to_flavour()
+    }
+}
+
+impl<K: TArchivedKey, V: TValue> ArcRadixTree<K, V> {
     fn children_arc(&self) -> &Arc<Vec<Self>> {
         &self.children
     }
@@ -117,6 +123,54 @@ impl<K: TKey, V: TValue> ArcRadixTree<K, V> {
         &mut self.children
     }
 
+    /// Memory in bytes that is owned exclusively by this tree, and would be freed if the tree was dropped.
+    ///
+    /// This includes the root node itself. A node is owned exclusively if none of the arcs on the path from
+    /// the root to the node is shared with another tree, e.g. a snapshot. Heap memory owned by the keys and values
+    /// themselves is not included.
+    pub fn deep_size_unique(&self) -> usize {
+        self.deep_size().0
+    }
+
+    /// Memory in bytes that is shared with other trees, e.g. snapshots, so it would not be freed if the tree was dropped.
+    ///
+    /// Heap memory owned by the keys and values themselves is not included.
+    pub fn deep_size_shared(&self) -> usize {
+        self.deep_size().1
+    }
+
+    /// memory split into (unique, shared)
+    fn deep_size(&self) -> (usize, usize) {
+        let mut res = (std::mem::size_of::<Self>(), 0);
+        self.add_heap_size(false, &mut res);
+        res
+    }
+
+    fn add_heap_size(&self, shared: bool, res: &mut (usize, usize)) {
+        let mut add = |shared: bool, size: usize| {
+            if shared {
+                res.1 += size
+            } else {
+                res.0 += size
+            }
+        };
+        add(shared, self.prefix.heap_size());
+        let children = self.children_arc();
+        // empty children do not need an allocation of their own, since they are all the same static arc
+        if children.capacity() > 0 {
+            let shared = shared || Arc::strong_count(children) > 1;
+            // the arc allocation consists of the two reference counts and the vec
+            let arc_size = 2 * std::mem::size_of::<usize>() + std::mem::size_of::<Vec<Self>>();
+            add(
+                shared,
+                arc_size + children.capacity() * std::mem::size_of::<Self>(),
+            );
+            for child in children.iter() {
+                child.add_heap_size(shared, res);
+            }
+        }
+    }
+
     /// copy all arcs that are used internally in this tree, and store them in a BTreeMap
     ///
     /// as long as the BTreeMap exists, this will have the effect of disabling reuse for
@@ -130,7 +184,7 @@ impl<K: TKey, V: TValue> ArcRadixTree<K, V> {
     }
 }
 
-impl<K: TKey, V: TValue + Archive<Archived = V>> From<&ArchivedArcRadixTree<K, V>>
+impl<K: TArchivedKey, V: TValue + Archive<Archived = V>> From<&ArchivedArcRadixTree<K, V>>
     for ArcRadixTree<K, V>
 {
     fn from(value: &ArchivedArcRadixTree<K, V>) -> Self {
@@ -144,7 +198,7 @@ impl<K: TKey, V: TValue + Archive<Archived = V>> From<&ArchivedArcRadixTree<K, V
     }
 }
 
-impl<K: TKey, V: TValue + Archive<Archived = V>> AbstractRadixTree<K, V>
+impl<K: TArchivedKey, V: TValue + Archive<Archived = V>> AbstractRadixTree<K, V>
     for ArchivedArcRadixTree<K, V>
 {
     type Materialized = ArcRadixTree<K, V>;
@@ -162,7 +216,7 @@ impl<K: TKey, V: TValue + Archive<Archived = V>> AbstractRadixTree<K, V>
     }
 }
 
-pub struct ArcRadixTreeResolver<K: TKey, V: TValue> {
+pub struct ArcRadixTreeResolver<K: TArchivedKey, V: TValue + Archive> {
     prefix: Resolver<Vec<K>>,
     value: Resolver<Option<V>>,
     children: Resolver<Arc<Vec<ArcRadixTree<K, V>>>>,
@@ -171,15 +225,15 @@ pub struct ArcRadixTreeResolver<K: TKey, V: TValue> {
 #[repr(C)]
 pub struct ArchivedArcRadixTree<K, V>
 where
-    K: TKey,
-    V: TValue,
+    K: TArchivedKey,
+    V: TValue + Archive,
 {
     prefix: Archived<Vec<K>>,
     value: Archived<Option<V>>,
     children: Archived<Arc<Vec<ArcRadixTree<K, V>>>>,
 }
 
-impl<'a, K: TKey, V: TValue> Archive for ArcRadixTree<K, V> {
+impl<'a, K: TArchivedKey, V: TValue + Archive> Archive for ArcRadixTree<K, V> {
     type Archived = ArchivedArcRadixTree<K, V>;
 
     type Resolver = ArcRadixTreeResolver<K, V>;
@@ -204,7 +258,7 @@ impl<'a, K: TKey, V: TValue> Archive for ArcRadixTree<K, V> {
 
 impl<S, K, V> Serialize<S> for ArcRadixTree<K, V>
 where
-    K: TKey + Serialize<S>,
+    K: TArchivedKey + Serialize<S>,
     V: TValue + Serialize<S>,
     S: ScratchSpace + Serializer + SharedSerializeRegistry,
 {
@@ -225,8 +279,8 @@ where
 impl<D, K, V> Deserialize<ArcRadixTree<K, V>, D> for ArchivedArcRadixTree<K, V>
 where
     D: SharedDeserializeRegistry,
-    K: TKey,
-    V: TValue,
+    K: TArchivedKey,
+    V: TValue + Archive,
     Archived<K>: Deserialize<K, D>,
     Archived<V>: Deserialize<V, D>,
 {
@@ -244,12 +298,12 @@ where
 
 #[cfg(feature = "rkyv_validated")]
 mod validation_support {
-    use super::{TKey, TValue};
+    use super::{TArchivedKey, TValue};
     use bytecheck::CheckBytes;
     use core::fmt;
     use rkyv::{
         validation::{ArchiveContext, SharedContext},
-        Archived,
+        Archive, Archived,
     };
 
     use super::ArchivedArcRadixTree;
@@ -278,8 +332,8 @@ mod validation_support {
     where
         C: ?Sized + ArchiveContext + SharedContext,
         C::Error: std::error::Error,
-        K: TKey,
-        V: TValue,
+        K: TArchivedKey,
+        V: TValue + Archive,
         Archived<Vec<K>>: bytecheck::CheckBytes<C>,
         Archived<Option<V>>: bytecheck::CheckBytes<C>,
     {
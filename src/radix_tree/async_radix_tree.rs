@@ -0,0 +1,303 @@
+use super::{internals::Fragment, AbstractRadixTreeMut, IterKey, RadixTree, TKey, TValue};
+use futures::{
+    future::BoxFuture,
+    stream::{self, Stream, StreamExt},
+};
+use std::sync::Arc;
+
+/// Loads the stored children of an [AsyncRadixTree], e.g. from network or object storage
+pub trait AsyncLoader<K, V> {
+    /// Identifies a stored list of children
+    type Id: Clone;
+    /// Error when loading children
+    type Error;
+
+    /// Load the children identified by `id`
+    ///
+    /// The children must be canonical, like the children of any other radix tree: sorted by their first key
+    /// component, which must be unique, with non-empty prefixes, and each containing at least one value.
+    #[allow(clippy::type_complexity)]
+    fn load<'a>(
+        &'a self,
+        id: &'a Self::Id,
+    ) -> BoxFuture<'a, Result<Vec<AsyncRadixTree<K, V, Self::Id>>, Self::Error>>;
+}
+
+#[derive(Debug, Clone)]
+enum Children<K, V, I> {
+    Loaded(Arc<Vec<AsyncRadixTree<K, V, I>>>),
+    Stored(I),
+}
+
+/// A radix tree whose children are loaded asynchronously, on demand
+///
+/// Unlike `LazyRadixTree`, which materializes children synchronously from an in memory
+/// archive, children are loaded by an [AsyncLoader] that returns futures, so the tree can be backed by network or
+/// object storage without blocking the executor. Iteration is exposed as a [Stream].
+///
+/// Loaded children are not cached in the tree. Use a caching loader if subtrees are visited repeatedly, or
+/// [materialize](AsyncRadixTree::materialize) the part of the tree you are interested in.
+#[derive(Debug, Clone)]
+pub struct AsyncRadixTree<K, V, I> {
+    prefix: Fragment<K>,
+    value: Option<V>,
+    children: Children<K, V, I>,
+}
+
+impl<K, V, I> Default for AsyncRadixTree<K, V, I> {
+    fn default() -> Self {
+        Self {
+            prefix: Fragment::default(),
+            value: None,
+            children: Children::Loaded(Arc::new(Vec::new())),
+        }
+    }
+}
+
+impl<K: TKey, V: TValue, I> From<RadixTree<K, V>> for AsyncRadixTree<K, V, I> {
+    fn from(value: RadixTree<K, V>) -> Self {
+        let RadixTree {
+            prefix,
+            value,
+            children,
+        } = value;
+        let children = children.into_iter().map(Self::from).collect::<Vec<_>>();
+        Self {
+            prefix,
+            value,
+            children: Children::Loaded(Arc::new(children)),
+        }
+    }
+}
+
+impl<K: TKey, V: TValue, I: Clone> AsyncRadixTree<K, V, I> {
+    /// A node with the given prefix and value, whose children are stored and will be loaded on demand
+    pub fn stored(prefix: &[K], value: Option<V>, children: I) -> Self {
+        Self {
+            prefix: prefix.into(),
+            value,
+            children: Children::Stored(children),
+        }
+    }
+
+    /// A node without children
+    pub fn leaf(prefix: &[K], value: V) -> Self {
+        Self {
+            prefix: prefix.into(),
+            value: Some(value),
+            children: Children::Loaded(Arc::new(Vec::new())),
+        }
+    }
+
+    /// The prefix of this node
+    pub fn prefix(&self) -> &[K] {
+        &self.prefix
+    }
+
+    /// The value of this node
+    pub fn value(&self) -> Option<&V> {
+        self.value.as_ref()
+    }
+
+    /// The children of this node, if they do not have to be loaded
+    pub fn loaded_children(&self) -> Option<&[Self]> {
+        match &self.children {
+            Children::Loaded(children) => Some(children),
+            Children::Stored(_) => None,
+        }
+    }
+
+    /// The children of this node, loading them if necessary
+    pub async fn children<L>(&self, loader: &L) -> Result<Arc<Vec<Self>>, L::Error>
+    where
+        L: AsyncLoader<K, V, Id = I>,
+    {
+        match &self.children {
+            Children::Loaded(children) => Ok(children.clone()),
+            Children::Stored(id) => Ok(Arc::new(loader.load(id).await?)),
+        }
+    }
+
+    /// A stream of all elements
+    pub fn iter<'a, L>(
+        &'a self,
+        loader: &'a L,
+    ) -> impl Stream<Item = Result<(IterKey<K>, V), L::Error>> + 'a
+    where
+        L: AsyncLoader<K, V, Id = I>,
+    {
+        self.scan_prefix(loader, &[])
+    }
+
+    /// A stream of all elements with a certain prefix
+    ///
+    /// Only the children of nodes along the prefix and below it are loaded. The stream ends after the first error.
+    pub fn scan_prefix<'a, L>(
+        &'a self,
+        loader: &'a L,
+        prefix: &'a [K],
+    ) -> impl Stream<Item = Result<(IterKey<K>, V), L::Error>> + 'a
+    where
+        L: AsyncLoader<K, V, Id = I>,
+    {
+        let state = ScanState {
+            loader,
+            prefix,
+            path: IterKey::new(&[]),
+            stack: vec![(Children::Loaded(Arc::new(vec![self.clone()])), 0, 0)],
+        };
+        stream::unfold(state, |mut state| async move {
+            match state.next().await {
+                Ok(Some(item)) => Some((Ok(item), state)),
+                Ok(None) => None,
+                Err(cause) => {
+                    state.stack.clear();
+                    Some((Err(cause), state))
+                }
+            }
+        })
+    }
+
+    /// Get the value for the given key
+    ///
+    /// This only loads the children of the nodes along the key.
+    pub async fn get<L>(&self, loader: &L, key: &[K]) -> Result<Option<V>, L::Error>
+    where
+        L: AsyncLoader<K, V, Id = I>,
+    {
+        // an exact match sorts before all other keys with the same prefix
+        let first = Box::pin(self.scan_prefix(loader, key))
+            .next()
+            .await
+            .transpose()?;
+        Ok(first.and_then(|(k, v)| (*k == *key).then_some(v)))
+    }
+
+    /// Load all elements with a certain prefix into a [RadixTree]
+    pub async fn materialize<L>(
+        &self,
+        loader: &L,
+        prefix: &[K],
+    ) -> Result<RadixTree<K, V>, L::Error>
+    where
+        L: AsyncLoader<K, V, Id = I>,
+    {
+        let mut res = RadixTree::default();
+        let mut elements = Box::pin(self.scan_prefix(loader, prefix));
+        while let Some((k, v)) = elements.next().await.transpose()? {
+            res.insert(&k, v);
+        }
+        Ok(res)
+    }
+}
+
+#[allow(clippy::type_complexity)]
+struct ScanState<'a, K, V, L: AsyncLoader<K, V>> {
+    loader: &'a L,
+    prefix: &'a [K],
+    path: IterKey<K>,
+    // the children of visited nodes, the position of the next child, and the prefix length of the node
+    stack: Vec<(Children<K, V, L::Id>, usize, usize)>,
+}
+
+impl<'a, K: TKey, V: TValue, L: AsyncLoader<K, V>> ScanState<'a, K, V, L> {
+    async fn next(&mut self) -> Result<Option<(IterKey<K>, V)>, L::Error> {
+        let Self {
+            loader,
+            prefix,
+            path,
+            stack,
+        } = self;
+        while let Some((children, pos, n)) = stack.last_mut() {
+            let nodes = match children {
+                Children::Loaded(nodes) => nodes.clone(),
+                Children::Stored(id) => {
+                    let nodes = Arc::new(loader.load(id).await?);
+                    *children = Children::Loaded(nodes.clone());
+                    nodes
+                }
+            };
+            if *pos == nodes.len() {
+                path.pop(*n);
+                stack.pop();
+                continue;
+            }
+            let node = &nodes[*pos];
+            *pos += 1;
+            path.append(node.prefix());
+            // skip nodes that diverge from the prefix, without loading their children
+            let m = path.len().min(prefix.len());
+            if path[..m] != prefix[..m] {
+                path.pop(node.prefix().len());
+                continue;
+            }
+            stack.push((node.children.clone(), 0, node.prefix().len()));
+            if path.len() >= prefix.len() {
+                if let Some(value) = node.value() {
+                    return Ok(Some((path.clone(), value.clone())));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::radix_tree::AbstractRadixTree;
+    use futures::executor::block_on;
+    use quickcheck::quickcheck;
+    use std::{collections::BTreeMap, convert::Infallible};
+
+    type Test = AsyncRadixTree<u8, u8, usize>;
+
+    /// a loader that has all child lists in memory
+    #[derive(Default)]
+    struct Store(Vec<Vec<Test>>);
+
+    impl AsyncLoader<u8, u8> for Store {
+        type Id = usize;
+        type Error = Infallible;
+
+        fn load<'a>(&'a self, id: &'a usize) -> BoxFuture<'a, Result<Vec<Test>, Infallible>> {
+            Box::pin(async move { Ok(self.0[*id].clone()) })
+        }
+    }
+
+    impl Store {
+        fn store(&mut self, tree: &RadixTree<u8, u8>) -> Test {
+            if tree.is_empty() {
+                Test::default()
+            } else if tree.children().is_empty() {
+                Test::leaf(tree.prefix(), *tree.value().unwrap())
+            } else {
+                let children = tree.children().iter().map(|c| self.store(c)).collect();
+                self.0.push(children);
+                Test::stored(tree.prefix(), tree.value().cloned(), self.0.len() - 1)
+            }
+        }
+    }
+
+    quickcheck! {
+        fn scan_prefix(a: BTreeMap<Vec<u8>, u8>, prefix: Vec<u8>) -> bool {
+            let a: BTreeMap<Vec<u8>, u8> = a.into_iter().map(|(k, v)| (k.iter().map(|x| x % 4).collect(), v)).collect();
+            let prefix: Vec<u8> = prefix.iter().map(|x| x % 4).take(2).collect();
+            let mut tree: RadixTree<u8, u8> = RadixTree::default();
+            for (k, v) in &a {
+                tree.insert(k, *v);
+            }
+            let mut store = Store::default();
+            let stored = store.store(&tree);
+            let actual: Vec<(Vec<u8>, u8)> = block_on(stored.scan_prefix(&store, &prefix).collect::<Vec<_>>())
+                .into_iter()
+                .map(|x| x.map(|(k, v)| (k.to_vec(), v)).unwrap())
+                .collect();
+            let expected: Vec<(Vec<u8>, u8)> = a.iter().filter(|(k, _)| k.starts_with(&prefix)).map(|(k, v)| (k.clone(), *v)).collect();
+            let all: Vec<_> = block_on(stored.iter(&store).collect::<Vec<_>>());
+            let materialized = block_on(stored.materialize(&store, &[])).unwrap();
+            let get = block_on(stored.get(&store, &prefix)).unwrap();
+            actual == expected && all.len() == a.len() && materialized == tree && get == a.get(&prefix).cloned()
+        }
+    }
+}
@@ -3,13 +3,32 @@ use std::fmt::Debug;
 use std::iter::FromIterator;
 
 /// A generic radix tree
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct RadixTree<K, V> {
     pub(crate) prefix: Fragment<K>,
     pub(crate) value: Option<V>,
     pub(crate) children: Vec<Self>,
 }
 
+impl<K: Clone, V: Clone> Clone for RadixTree<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            prefix: self.prefix.clone(),
+            value: self.value.clone(),
+            children: self.children.clone(),
+        }
+    }
+
+    /// Reuses the allocations of `self` where possible, so refreshing a working copy from a snapshot
+    /// does not have to reallocate every node.
+    fn clone_from(&mut self, source: &Self) {
+        self.prefix.clone_from(&source.prefix);
+        self.value.clone_from(&source.value);
+        // this uses clone_from for the common prefix of the children
+        self.children.clone_from(&source.children);
+    }
+}
+
 impl<K: TKey, V: TValue> AbstractRadixTree<K, V> for RadixTree<K, V> {
     type Materialized = RadixTree<K, V>;
 
@@ -70,8 +89,8 @@ impl<K: Clone, V> Default for RadixTree<K, V> {
 
 #[cfg(feature = "rkyv")]
 mod rkyv_support {
-    use super::super::{internals, offset_from};
-    use super::{AbstractRadixTree, Fragment, RadixTree, TKey, TValue};
+    use super::super::{internals, offset_from, TArchivedKey};
+    use super::{AbstractRadixTree, Fragment, RadixTree, TValue};
     use internals::AbstractRadixTreeMut as _;
     use rkyv::{
         ser::{ScratchSpace, Serializer},
@@ -80,25 +99,25 @@ mod rkyv_support {
     };
 
     #[repr(C)]
-    pub struct ArchivedRadixTree<K: TKey, V: TValue> {
+    pub struct ArchivedRadixTree<K: TArchivedKey, V: TValue + Archive> {
         prefix: Archived<Vec<K>>,
         value: Archived<Option<V>>,
         children: Archived<Vec<RadixTree<K, V>>>,
     }
 
-    impl<K: TKey, V: TValue> std::fmt::Debug for ArchivedRadixTree<K, V> {
+    impl<K: TArchivedKey, V: TValue + Archive> std::fmt::Debug for ArchivedRadixTree<K, V> {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             f.debug_struct("ArchivedRadixTree").finish_non_exhaustive()
         }
     }
 
-    pub struct RadixTreeResolver<K: TKey, V: TValue> {
+    pub struct RadixTreeResolver<K: TArchivedKey, V: TValue + Archive> {
         prefix: Resolver<Vec<K>>,
         value: Resolver<Option<V>>,
         children: Resolver<Vec<RadixTree<K, V>>>,
     }
 
-    impl<K: TKey, V: TValue + Archive<Archived = V>> AbstractRadixTree<K, V>
+    impl<K: TArchivedKey, V: TValue + Archive<Archived = V>> AbstractRadixTree<K, V>
         for ArchivedRadixTree<K, V>
     {
         fn prefix(&self) -> &[K] {
@@ -118,7 +137,7 @@ mod rkyv_support {
 
     impl<K, V> Archive for RadixTree<K, V>
     where
-        K: TKey + Archive,
+        K: TArchivedKey + Archive,
         V: TValue + Archive,
     {
         type Archived = ArchivedRadixTree<K, V>;
@@ -154,7 +173,7 @@ mod rkyv_support {
 
     impl<S, K, V> Serialize<S> for RadixTree<K, V>
     where
-        K: TKey + Serialize<S>,
+        K: TArchivedKey + Serialize<S>,
         V: TValue + Serialize<S>,
         S: ScratchSpace + Serializer,
     {
@@ -173,8 +192,8 @@ mod rkyv_support {
     impl<D, K, V> Deserialize<RadixTree<K, V>, D> for ArchivedRadixTree<K, V>
     where
         D: Fallible + ?Sized,
-        K: TKey,
-        V: TValue,
+        K: TArchivedKey,
+        V: TValue + Archive,
         Archived<K>: Deserialize<K, D>,
         Archived<V>: Deserialize<V, D>,
     {
@@ -192,10 +211,10 @@ mod rkyv_support {
 
     #[cfg(feature = "rkyv_validated")]
     mod validation_support {
-        use super::{TKey, TValue};
+        use super::{TArchivedKey, TValue};
         use bytecheck::CheckBytes;
         use core::fmt;
-        use rkyv::{validation::ArchiveContext, Archived};
+        use rkyv::{validation::ArchiveContext, Archive, Archived};
 
         use super::ArchivedRadixTree;
 
@@ -223,8 +242,8 @@ mod rkyv_support {
         where
             C: ?Sized + ArchiveContext,
             C::Error: std::error::Error,
-            K: TKey,
-            V: TValue,
+            K: TArchivedKey,
+            V: TValue + Archive,
             Archived<Vec<K>>: bytecheck::CheckBytes<C>,
             Archived<Option<V>>: bytecheck::CheckBytes<C>,
         {
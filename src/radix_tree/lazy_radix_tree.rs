@@ -1,4 +1,6 @@
-use super::{internals, location, offset_from, AbstractRadixTree, Fragment, RadixTree, TKey};
+use super::{
+    internals, location, offset_from, AbstractRadixTree, Fragment, RadixTree, TArchivedKey,
+};
 use rkyv::{
     ser::{ScratchSpace, Serializer, SharedSerializeRegistry},
     vec::ArchivedVec,
@@ -20,7 +22,7 @@ impl<T: Debug + Clone + Archive<Archived = Self> + Send + Sync + 'static> TValue
 #[derive(Clone)]
 pub struct LazyRadixTree<'a, K, V>
 where
-    K: TKey,
+    K: TArchivedKey,
     V: TValue,
 {
     prefix: Fragment<K>,
@@ -29,7 +31,7 @@ where
     children: Lazy<&'a [Archived<LazyRadixTree<'a, K, V>>], Arc<Vec<Self>>>,
 }
 
-impl<'a, K: TKey, V: TValue> Default for LazyRadixTree<'a, K, V> {
+impl<'a, K: TArchivedKey, V: TValue> Default for LazyRadixTree<'a, K, V> {
     fn default() -> Self {
         Self {
             prefix: Default::default(),
@@ -39,7 +41,7 @@ impl<'a, K: TKey, V: TValue> Default for LazyRadixTree<'a, K, V> {
     }
 }
 
-impl<'a, K: TKey, V: TValue> AbstractRadixTree<K, V> for LazyRadixTree<'a, K, V> {
+impl<'a, K: TArchivedKey, V: TValue> AbstractRadixTree<K, V> for LazyRadixTree<'a, K, V> {
     type Materialized = LazyRadixTree<'a, K, V>;
 
     fn prefix(&self) -> &[K] {
@@ -55,7 +57,9 @@ impl<'a, K: TKey, V: TValue> AbstractRadixTree<K, V> for LazyRadixTree<'a, K, V>
     }
 }
 
-impl<'a, K: TKey, V: TValue> internals::AbstractRadixTreeMut<K, V> for LazyRadixTree<'a, K, V> {
+impl<'a, K: TArchivedKey, V: TValue> internals::AbstractRadixTreeMut<K, V>
+    for LazyRadixTree<'a, K, V>
+{
     fn new(prefix: Fragment<K>, value: Option<V>, children: Vec<Self>) -> Self {
         let children = Lazy::initialized(Arc::new(children));
         Self {
@@ -78,7 +82,7 @@ impl<'a, K: TKey, V: TValue> internals::AbstractRadixTreeMut<K, V> for LazyRadix
     }
 }
 
-impl<K: TKey, V: TValue> From<RadixTree<K, V>> for LazyRadixTree<'static, K, V> {
+impl<K: TArchivedKey, V: TValue> From<RadixTree<K, V>> for LazyRadixTree<'static, K, V> {
     fn from(value: RadixTree<K, V>) -> Self {
         let RadixTree {
             prefix,
@@ -95,7 +99,13 @@ impl<K: TKey, V: TValue> From<RadixTree<K, V>> for LazyRadixTree<'static, K, V>
     }
 }
 
-impl<'a, K: TKey, V: TValue> LazyRadixTree<'a, K, V> {
+impl<'a, K: TArchivedKey, V: TValue> From<&LazyRadixTree<'a, K, V>> for RadixTree<K, V> {
+    fn from(value: &LazyRadixTree<'a, K, V>) -> Self {
+        value.to_flavour()
+    }
+}
+
+impl<'a, K: TArchivedKey, V: TValue> LazyRadixTree<'a, K, V> {
     fn children_arc(&self) -> &Arc<Vec<Self>> {
         self.children.get_or_create(materialize_shallow)
     }
@@ -119,7 +129,7 @@ impl<'a, K: TKey, V: TValue> LazyRadixTree<'a, K, V> {
     }
 }
 
-impl<'a, K: TKey + Archive<Archived = K>, V: TValue + Archive<Archived = V>>
+impl<'a, K: TArchivedKey + Archive<Archived = K>, V: TValue + Archive<Archived = V>>
     From<&'a ArchivedLazyRadixTree<K, V>> for LazyRadixTree<'a, K, V>
 {
     fn from(value: &'a ArchivedLazyRadixTree<K, V>) -> Self {
@@ -133,7 +143,7 @@ impl<'a, K: TKey + Archive<Archived = K>, V: TValue + Archive<Archived = V>>
     }
 }
 
-impl<K: TKey, V: TValue> AbstractRadixTree<K, V> for ArchivedLazyRadixTree<K, V> {
+impl<K: TArchivedKey, V: TValue> AbstractRadixTree<K, V> for ArchivedLazyRadixTree<K, V> {
     type Materialized = LazyRadixTree<'static, K, V>;
 
     fn prefix(&self) -> &[K] {
@@ -149,7 +159,7 @@ impl<K: TKey, V: TValue> AbstractRadixTree<K, V> for ArchivedLazyRadixTree<K, V>
     }
 }
 
-fn materialize_shallow<K: TKey, V: TValue>(
+fn materialize_shallow<K: TArchivedKey, V: TValue>(
     children: &[ArchivedLazyRadixTree<K, V>],
 ) -> Arc<Vec<LazyRadixTree<K, V>>> {
     Arc::new(
@@ -164,7 +174,7 @@ fn materialize_shallow<K: TKey, V: TValue>(
     )
 }
 
-pub struct LazyRadixTreeResolver<K: TKey + Archive, V: TValue + Archive> {
+pub struct LazyRadixTreeResolver<K: TArchivedKey + Archive, V: TValue + Archive> {
     prefix: Resolver<Vec<K>>,
     value: Resolver<Option<V>>,
     children: Resolver<Arc<Vec<LazyRadixTree<'static, K, V>>>>,
@@ -173,7 +183,7 @@ pub struct LazyRadixTreeResolver<K: TKey + Archive, V: TValue + Archive> {
 #[repr(C)]
 pub struct ArchivedLazyRadixTree<K, V>
 where
-    K: TKey,
+    K: TArchivedKey,
     V: TValue,
 {
     prefix: Archived<Vec<K>>,
@@ -181,7 +191,7 @@ where
     children: Archived<Arc<Vec<LazyRadixTree<'static, K, V>>>>,
 }
 
-impl<'a, K: TKey, V: TValue> Archive for LazyRadixTree<'a, K, V> {
+impl<'a, K: TArchivedKey, V: TValue> Archive for LazyRadixTree<'a, K, V> {
     type Archived = ArchivedLazyRadixTree<K, V>;
 
     type Resolver = LazyRadixTreeResolver<K, V>;
@@ -206,7 +216,7 @@ impl<'a, K: TKey, V: TValue> Archive for LazyRadixTree<'a, K, V> {
 
 impl<'a, S, K, V> Serialize<S> for LazyRadixTree<'a, K, V>
 where
-    K: TKey + Serialize<S>,
+    K: TArchivedKey + Serialize<S>,
     V: TValue + Serialize<S>,
     S: ScratchSpace + Serializer + SharedSerializeRegistry,
 {
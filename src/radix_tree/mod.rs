@@ -7,46 +7,79 @@
 //!
 //! Radix trees allow very quick (O(log n)) filtering by prefix, as well as very fast (O(1)) prepending a prefix.
 //!
-//! Radix trees in this crate come in three flavours:
+//! Radix trees in this crate come in four flavours:
 //! - [RadixTree](RadixTree) is the most straightforward flavour. It does not contain any indirection.
 //!   use this for short lived objects.
 //! - [ArcRadixTree](ArcRadixTree) allows cheap snapshots and has copy on write semantics.
 //!   use this for a longer lived in memory tree that evolves over time
+//! - [RcRadixTree](RcRadixTree) is like [ArcRadixTree](ArcRadixTree), but uses non-atomic reference counting.
+//!   use this instead of an [ArcRadixTree](ArcRadixTree) if the tree does not need to be shared between threads
 //! - [LazyRadixTree](LazyRadixTree) allows cheap snapshots, copy on write semantics, and lazy loading.
 //!   use this for e.g. memory mapping a giant radix tree from a large file, that does not fit in memory.
+//! - [AsyncRadixTree](AsyncRadixTree) loads children on demand from an asynchronous [AsyncLoader](AsyncLoader).
+//!   use this for a tree that is backed by network or object storage.
 //!
 //! No attempt is made to hide the internal structure. E.g. if you want to use a RadixTree as a set,
 //! this is possible by using unit as value type, but probably not very convenient.
-use std::{borrow::Borrow, cmp::Ordering, fmt::Debug, marker::PhantomData, ops::Deref, sync::Arc};
+use std::{
+    any::Any, borrow::Borrow, cell::RefCell, cmp::Ordering, fmt::Debug, marker::PhantomData,
+    ops::Deref, sync::Arc,
+};
 
 /// Trait for everything that is needed for a component to be a radix tree key component
-pub trait TKey: Debug + Ord + Copy + Archive<Archived = Self> + Send + Sync + 'static {}
+///
+/// Key components do not have to be `Copy`, so e.g. a [RadixTree] with `String` path segments as
+/// key components is possible. Flavours that need more, like serialization via rkyv, add the required
+/// bounds themselves.
+pub trait TKey: Ord + Clone + 'static {}
 
-impl<T: Debug + Ord + Copy + Archive<Archived = T> + Send + Sync + 'static> TKey for T {}
+impl<T: Ord + Clone + 'static> TKey for T {}
+
+/// Trait for key components of radix tree flavours that can be archived with rkyv
+///
+/// The archived form of a key component must be the component itself, so prefixes of archived trees can be used directly.
+#[cfg(feature = "rkyv")]
+pub trait TArchivedKey: TKey + Archive<Archived = Self> {}
+
+#[cfg(feature = "rkyv")]
+impl<T: TKey + Archive<Archived = T>> TArchivedKey for T {}
 
 /// Trait for everything that is needed for a component to be a radix tree value
-pub trait TValue: Debug + Clone + Archive + Send + Sync + 'static {}
+///
+/// This is deliberately minimal, so e.g. values containing an `Rc` can be used with a plain [RadixTree].
+/// Flavours that need more, like serialization via rkyv, add the required bounds themselves.
+pub trait TValue: Clone + 'static {}
 
-impl<T: Debug + Clone + Archive + Send + Sync + 'static> TValue for T {}
+impl<T: Clone + 'static> TValue for T {}
 
+#[cfg(feature = "rkyv")]
 use rkyv::Archive;
 #[cfg(feature = "lazy_radixtree")]
 mod lazy_radix_tree;
 #[cfg(feature = "lazy_radixtree")]
 pub use lazy_radix_tree::LazyRadixTree;
+#[cfg(feature = "async_radixtree")]
+mod async_radix_tree;
+#[cfg(feature = "async_radixtree")]
+pub use async_radix_tree::{AsyncLoader, AsyncRadixTree};
 #[cfg(feature = "rkyv")]
 mod arc_radix_tree;
+use crate::VecSet;
 #[cfg(feature = "rkyv")]
 pub use arc_radix_tree::ArcRadixTree;
-use smallvec::SmallVec;
+#[cfg(feature = "rkyv")]
+pub mod snapshot;
+use smallvec::{Array, SmallVec};
 use sorted_iter::sorted_pair_iterator::SortedByKey;
 mod flat_radix_tree;
+mod rc_radix_tree;
 use crate::merge_state::{
     BoolOpMergeState, Converter, InPlaceVecMergeStateRef, MergeStateMut, MutateInput, NoConverter,
     VecMergeState,
 };
 use binary_merge::MergeOperation;
 pub use flat_radix_tree::RadixTree;
+pub use rc_radix_tree::RcRadixTree;
 
 // common prefix of two slices.
 fn common_prefix<'a, T: Eq>(a: &'a [T], b: &'a [T]) -> usize {
@@ -57,9 +90,19 @@ pub(crate) mod internals {
     use super::*;
 
     /// A path fragment
-    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[derive(Debug, PartialEq, Eq)]
     pub struct Fragment<T>(SmallVec<[T; 16]>);
 
+    impl<T: Clone> Clone for Fragment<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+
+        fn clone_from(&mut self, source: &Self) {
+            self.0.clone_from(&source.0)
+        }
+    }
+
     impl<T> AsRef<[T]> for Fragment<T> {
         fn as_ref(&self) -> &[T] {
             self.0.as_ref()
@@ -104,6 +147,18 @@ pub(crate) mod internals {
         }
     }
 
+    #[cfg(feature = "rkyv")]
+    impl<T> Fragment<T> {
+        /// heap memory used by the fragment, 0 if the fragment is stored inline
+        pub fn heap_size(&self) -> usize {
+            if self.0.spilled() {
+                self.0.capacity() * std::mem::size_of::<T>()
+            } else {
+                0
+            }
+        }
+    }
+
     impl<T> Default for Fragment<T> {
         fn default() -> Self {
             Self(SmallVec::new())
@@ -159,53 +214,264 @@ pub(crate) mod internals {
         fn prepend0(&mut self, prefix: &[K]) {
             if !prefix.is_empty() && !self.is_empty() {
                 let mut prefix1 = SmallVec::new();
-                prefix1.extend_from_slice(prefix);
-                prefix1.extend_from_slice(self.prefix());
+                prefix1.extend(prefix.iter().cloned());
+                prefix1.extend(self.prefix().iter().cloned());
                 *self.prefix_mut() = prefix1.into();
             }
         }
 
-        fn outer_combine_children_with<R, F>(&mut self, rhs: &[R], f: F)
+        fn outer_combine_children_with<R, F>(&mut self, rhs: &[R], key: &mut Vec<K>, f: F)
         where
             R: AbstractRadixTree<K, V, Materialized = Self::Materialized>,
-            F: Fn(&mut V, &V) -> bool + Copy,
+            F: Fn(&[K], &mut V, &V) -> bool + Copy,
         {
             InPlaceVecMergeStateRef::merge(
                 self.children_mut(),
                 &rhs,
-                OuterCombineOp(f, PhantomData),
+                OuterCombineWithOp(f, RefCell::new(key), PhantomData),
                 RadixTreeConverter(PhantomData),
             );
         }
 
-        fn inner_combine_children_with<W, R, F>(&mut self, rhs: &[R], f: F)
+        fn inner_combine_children_with<W, R, F>(&mut self, rhs: &[R], key: &mut Vec<K>, f: F)
         where
             W: TValue,
             R: AbstractRadixTree<K, W>,
-            F: Fn(&mut V, &W) -> bool + Copy,
+            F: Fn(&[K], &mut V, &W) -> bool + Copy,
         {
             InPlaceVecMergeStateRef::merge(
                 self.children_mut(),
                 &rhs,
-                InnerCombineOp(f, PhantomData),
+                InnerCombineWithOp(f, RefCell::new(key), PhantomData),
                 NoConverter,
             );
         }
 
-        fn left_combine_children_with<W, R, F>(&mut self, rhs: &[R], f: F)
+        fn left_combine_children_with<W, R, F>(&mut self, rhs: &[R], key: &mut Vec<K>, f: F)
         where
             W: TValue,
             R: AbstractRadixTree<K, W>,
-            F: Fn(&mut V, &W) -> bool + Copy,
+            F: Fn(&[K], &mut V, &W) -> bool + Copy,
         {
             InPlaceVecMergeStateRef::merge(
                 self.children_mut(),
                 &rhs,
-                LeftCombineOp(f, PhantomData),
+                LeftCombineWithOp(f, RefCell::new(key), PhantomData),
                 NoConverter,
             );
         }
 
+        /// collect the full keys of all values for which `f` returns false, in order, without modifying the tree
+        fn rejected_keys0(
+            &self,
+            key: &mut Vec<K>,
+            f: &mut impl FnMut(&[K], &V) -> bool,
+            rejected: &mut Vec<Vec<K>>,
+        ) {
+            let len = key.len();
+            key.extend_from_slice(self.prefix());
+            if let Some(v) = self.value() {
+                if !f(key, v) {
+                    rejected.push(key.clone());
+                }
+            }
+            for child in self.children() {
+                child.rejected_keys0(key, f, rejected);
+            }
+            key.truncate(len);
+        }
+
+        /// remove the given keys, where all `keys` are in this node and start with the `depth` key components above it
+        ///
+        /// only the nodes on the paths to the keys are modified, so nodes that are shared with other trees are not
+        /// copied otherwise.
+        fn remove_keys0(&mut self, depth: usize, keys: &[Vec<K>]) {
+            let depth = depth + self.prefix().len();
+            let mut keys = keys;
+            // an exact match sorts before all longer keys
+            if keys.first().map(|k| k.len() == depth).unwrap_or_default() {
+                *self.value_mut() = None;
+                keys = &keys[1..];
+            }
+            if !keys.is_empty() {
+                for child in self.children_mut() {
+                    let first = &child.prefix()[0];
+                    // all remaining keys are in this or a later child
+                    let end = keys.partition_point(|k| &k[depth] == first);
+                    if end > 0 {
+                        child.remove_keys0(depth, &keys[..end]);
+                        keys = &keys[end..];
+                    }
+                }
+            }
+            self.unsplit();
+        }
+
+        /// retain only the given keys, where all `keys` start with the `depth` key components above this node
+        fn retain_keys0<S: AsRef<[K]>>(&mut self, depth: usize, keys: &[S]) {
+            if keys.is_empty() {
+                *self.value_mut() = None;
+                self.children_mut().clear();
+            } else {
+                // the keys matching the prefix of this node are a contiguous range
+                let prefix = self.prefix();
+                let n = prefix.len();
+                fn fragment<K, S: AsRef<[K]>>(k: &S, depth: usize, n: usize) -> &[K] {
+                    let k = &k.as_ref()[depth..];
+                    &k[..n.min(k.len())]
+                }
+                let start = keys.partition_point(|k| fragment(k, depth, n) < prefix);
+                let end =
+                    start + keys[start..].partition_point(|k| fragment(k, depth, n) == prefix);
+                let mut keys = &keys[start..end];
+                let depth = depth + n;
+                // an exact match sorts before all longer keys
+                if keys.first().is_some_and(|k| k.as_ref().len() == depth) {
+                    keys = &keys[1..];
+                } else {
+                    *self.value_mut() = None;
+                }
+                for child in self.children_mut() {
+                    let first = &child.prefix()[0];
+                    let start = keys.partition_point(|k| &k.as_ref()[depth] < first);
+                    let end =
+                        start + keys[start..].partition_point(|k| &k.as_ref()[depth] == first);
+                    child.retain_keys0(depth, &keys[start..end]);
+                    keys = &keys[end..];
+                }
+            }
+            self.unsplit();
+        }
+
+        /// outer combine, keeping track of the key of the parent node in `key`
+        fn outer_combine_with_key0(
+            &mut self,
+            that: &impl AbstractRadixTree<K, V, Materialized = Self::Materialized>,
+            key: &mut Vec<K>,
+            f: impl Fn(&[K], &mut V, &V) -> bool + Copy,
+        ) {
+            let n = common_prefix(self.prefix(), that.prefix());
+            let len = key.len();
+            key.extend_from_slice(&self.prefix()[..n]);
+            if n == self.prefix().len() && n == that.prefix().len() {
+                // prefixes are identical
+                if let Some(w) = that.value() {
+                    if let Some(v) = &mut self.value_mut() {
+                        if !f(key, v, w) {
+                            *self.value_mut() = None;
+                        }
+                    } else {
+                        *self.value_mut() = Some(w.clone())
+                    }
+                }
+                self.outer_combine_children_with(that.children(), key, f);
+            } else if n == self.prefix().len() {
+                // self is a prefix of that
+                let that = that.materialize_shortened(n);
+                self.outer_combine_children_with(&[that], key, f);
+            } else if n == that.prefix().len() {
+                // that is a prefix of self
+                // split at the offset, then merge in that
+                // we must not swap sides!
+                self.split(n);
+                // self now has the same prefix as that, so just repeat the code
+                // from where prefixes are identical
+                if let Some(w) = that.value() {
+                    if let Some(v) = &mut self.value_mut() {
+                        if !f(key, v, w) {
+                            *self.value_mut() = None;
+                        }
+                    } else {
+                        *self.value_mut() = Some(w.clone())
+                    }
+                }
+                self.outer_combine_children_with(that.children(), key, f);
+            } else {
+                // disjoint
+                self.split(n);
+                self.children_mut().push(that.materialize_shortened(n));
+                self.children_mut()
+                    .sort_by(|a, b| a.prefix()[0].cmp(&b.prefix()[0]));
+            }
+            key.truncate(len);
+            self.unsplit();
+        }
+
+        /// inner combine, keeping track of the key of the parent node in `key`
+        fn inner_combine_with_key0<W: TValue>(
+            &mut self,
+            that: &impl AbstractRadixTree<K, W>,
+            key: &mut Vec<K>,
+            f: impl Fn(&[K], &mut V, &W) -> bool + Copy,
+        ) {
+            let n = common_prefix(self.prefix(), that.prefix());
+            let len = key.len();
+            key.extend_from_slice(&self.prefix()[..n]);
+            if n == self.prefix().len() && n == that.prefix().len() {
+                // prefixes are identical
+                if let (Some(v), Some(w)) = (self.value_mut(), that.value()) {
+                    if !f(key, v, w) {
+                        *self.value_mut() = None;
+                    }
+                } else {
+                    *self.value_mut() = None;
+                }
+                self.inner_combine_children_with(that.children(), key, f);
+            } else if n == self.prefix().len() {
+                // self is a prefix of that
+                *self.value_mut() = None;
+                let that = that.materialize_shortened(n);
+                self.inner_combine_children_with(&[that], key, f);
+            } else if n == that.prefix().len() {
+                // that is a prefix of self
+                // split at the offset, then merge in that
+                // we must not swap sides!
+                self.split(n);
+                self.inner_combine_children_with(that.children(), key, f);
+            } else {
+                // disjoint
+                *self.value_mut() = None;
+                self.children_mut().clear();
+            }
+            key.truncate(len);
+            self.unsplit();
+        }
+
+        /// left combine, keeping track of the key of the parent node in `key`
+        fn left_combine_with_key0<W: TValue>(
+            &mut self,
+            that: &impl AbstractRadixTree<K, W>,
+            key: &mut Vec<K>,
+            f: impl Fn(&[K], &mut V, &W) -> bool + Copy,
+        ) {
+            let n = common_prefix(self.prefix(), that.prefix());
+            let len = key.len();
+            key.extend_from_slice(&self.prefix()[..n]);
+            if n == self.prefix().len() && n == that.prefix().len() {
+                // prefixes are identical
+                if let Some(w) = that.value() {
+                    if let Some(v) = self.value_mut() {
+                        if !f(key, v, w) {
+                            *self.value_mut() = None;
+                        }
+                    }
+                }
+                self.left_combine_children_with(that.children(), key, f);
+            } else if n == self.prefix().len() {
+                // self is a prefix of that
+                let that = that.materialize_shortened(n);
+                self.left_combine_children_with(&[that], key, f);
+            } else if n == that.prefix().len() {
+                // that is a prefix of self
+                self.split(n);
+                self.left_combine_children_with(that.children(), key, f);
+            } else {
+                // disjoint, nothing to do
+            }
+            key.truncate(len);
+            self.unsplit();
+        }
+
         fn retain_prefix_children_with<W, R>(&mut self, rhs: &[R], f: impl Fn(&W) -> bool + Copy)
         where
             W: TValue,
@@ -264,6 +530,19 @@ pub trait AbstractRadixTreeMut<K: TKey, V: TValue>: internals::AbstractRadixTree
         })
     }
 
+    /// Get an optional mutable reference to the value for the given key
+    ///
+    /// For the copy on write flavours such as [ArcRadixTree], only the nodes along the path to the key are
+    /// copied if they are shared, so this is much cheaper than inserting a modified value. Nothing is
+    /// copied if the key is not present.
+    fn get_mut(&mut self, key: &[K]) -> Option<&mut V> {
+        if self.get(key).is_some() {
+            get_mut_unchecked(self, key)
+        } else {
+            None
+        }
+    }
+
     /// Return the subtree with the given prefix. Will return an empty tree in case there is no match.
     fn filter_prefix(&self, prefix: &[K]) -> Self {
         match find(self, prefix) {
@@ -283,12 +562,59 @@ pub trait AbstractRadixTreeMut<K: TKey, V: TValue>: internals::AbstractRadixTree
         }
     }
 
+    /// Return the subtree with the given prefix, or an error if there is no key with the given prefix.
+    ///
+    /// Unlike [filter_prefix](AbstractRadixTreeMut::filter_prefix), this can distinguish a prefix that is not
+    /// present from a prefix that is present, but has an empty subtree.
+    fn try_filter_prefix(&self, prefix: &[K]) -> Result<Self, PrefixNotFoundError> {
+        match find(self, prefix) {
+            FindResult::NotFound { rp, .. } => Err(PrefixNotFoundError {
+                matched: prefix.len() - rp,
+            }),
+            _ => Ok(self.filter_prefix(prefix)),
+        }
+    }
+
+    /// Return the subtree with the given prefix, with the prefix removed from all keys.
+    ///
+    /// This is the inverse of [prepend](AbstractRadixTreeMut::prepend). Returns None if there are no keys with
+    /// the given prefix.
+    fn strip_prefix(&self, prefix: &[K]) -> Option<Self> {
+        let res = match find(self, prefix) {
+            FindResult::Found(tree) => {
+                let mut res = tree.clone();
+                *res.prefix_mut() = Fragment::default();
+                res
+            }
+            FindResult::Prefix { tree, rt } => {
+                let mut res = tree.clone();
+                let p = res.prefix();
+                *res.prefix_mut() = Fragment::from(&p[p.len() - rt..]);
+                res
+            }
+            FindResult::NotFound { .. } => return None,
+        };
+        if res.is_empty() {
+            None
+        } else {
+            Some(res)
+        }
+    }
+
+    /// Apply a transformation to each key component, preserving the structure of the tree.
+    ///
+    /// The transformation must be strictly monotone, so that the order of the keys does not change and distinct
+    /// components remain distinct. This is checked for the first component of siblings in debug builds.
+    fn rekey_components(&self, f: impl Fn(&K) -> K) -> Self {
+        rekey_components(self, &f)
+    }
+
     /// Prepend a prefix to the tree
     fn prepend(&mut self, prefix: &[K]) {
         if !prefix.is_empty() && !self.is_empty() {
             let mut prefix1 = SmallVec::new();
-            prefix1.extend_from_slice(prefix);
-            prefix1.extend_from_slice(self.prefix());
+            prefix1.extend(prefix.iter().cloned());
+            prefix1.extend(self.prefix().iter().cloned());
             *self.prefix_mut() = prefix1.into();
         }
     }
@@ -346,47 +672,45 @@ pub trait AbstractRadixTreeMut<K: TKey, V: TValue>: internals::AbstractRadixTree
         that: &impl AbstractRadixTree<K, V, Materialized = Self::Materialized>,
         f: impl Fn(&mut V, &V) -> bool + Copy,
     ) {
-        let n = common_prefix(self.prefix(), that.prefix());
-        if n == self.prefix().len() && n == that.prefix().len() {
-            // prefixes are identical
-            if let Some(w) = that.value() {
-                if let Some(v) = &mut self.value_mut() {
-                    if !f(v, w) {
-                        *self.value_mut() = None;
-                    }
-                } else {
-                    *self.value_mut() = Some(w.clone())
-                }
-            }
-            self.outer_combine_children_with(that.children(), f);
-        } else if n == self.prefix().len() {
-            // self is a prefix of that
-            let that = that.materialize_shortened(n);
-            self.outer_combine_children_with(&[that], f);
-        } else if n == that.prefix().len() {
-            // that is a prefix of self
-            // split at the offset, then merge in that
-            // we must not swap sides!
-            self.split(n);
-            // self now has the same prefix as that, so just repeat the code
-            // from where prefixes are identical
-            if let Some(w) = that.value() {
-                if let Some(v) = &mut self.value_mut() {
-                    if !f(v, w) {
-                        *self.value_mut() = None;
-                    }
-                } else {
-                    *self.value_mut() = Some(w.clone())
-                }
-            }
-            self.outer_combine_children_with(that.children(), f);
-        } else {
-            // disjoint
-            self.split(n);
-            self.children_mut().push(that.materialize_shortened(n));
-            self.children_mut().sort_by_key(|x| x.prefix()[0]);
+        self.outer_combine_with_key(that, move |_, v, w| f(v, w))
+    }
+
+    /// Retain only the elements for which `f` returns true, given the full key and the value
+    ///
+    /// This walks the tree once without modifying it, and then only modifies the nodes on the paths to the removed
+    /// elements. So nodes that are shared with other trees are only copied if something below them is removed.
+    /// It is much cheaper than building a tree of the keys to remove and using
+    /// [difference_with](AbstractRadixTreeMut::difference_with).
+    fn retain(&mut self, mut f: impl FnMut(&[K], &V) -> bool) {
+        let mut rejected = Vec::new();
+        self.rejected_keys0(&mut Vec::new(), &mut f, &mut rejected);
+        if !rejected.is_empty() {
+            self.remove_keys0(0, &rejected);
         }
-        self.unsplit();
+    }
+
+    /// Retain only the elements whose key is contained in `keys`
+    ///
+    /// This walks the tree and the sorted keys together, narrowing the range of keys at each node, instead of
+    /// doing a lookup for each key. The ordering of the key type must agree with the ordering of `[K]`, which
+    /// is the case for e.g. `Vec<K>` and `SmallVec<[K; N]>`.
+    fn retain_keys<A: Array>(&mut self, keys: &VecSet<A>)
+    where
+        A::Item: AsRef<[K]>,
+    {
+        self.retain_keys0(0, keys.as_ref())
+    }
+
+    /// outer combine of `self` tree with `that` tree, with access to the key
+    ///
+    /// Same as [outer_combine_with](AbstractRadixTreeMut::outer_combine_with), except that `f` also gets
+    /// the full key of the elements to be combined.
+    fn outer_combine_with_key(
+        &mut self,
+        that: &impl AbstractRadixTree<K, V, Materialized = Self::Materialized>,
+        f: impl Fn(&[K], &mut V, &V) -> bool + Copy,
+    ) {
+        self.outer_combine_with_key0(that, &mut Vec::new(), f)
     }
 
     /// inner combine of `self` tree with `that` tree
@@ -399,34 +723,19 @@ pub trait AbstractRadixTreeMut<K: TKey, V: TValue>: internals::AbstractRadixTree
         that: &impl AbstractRadixTree<K, W>,
         f: impl Fn(&mut V, &W) -> bool + Copy,
     ) {
-        let n = common_prefix(self.prefix(), that.prefix());
-        if n == self.prefix().len() && n == that.prefix().len() {
-            // prefixes are identical
-            if let (Some(v), Some(w)) = (self.value_mut(), that.value()) {
-                if !f(v, w) {
-                    *self.value_mut() = None;
-                }
-            } else {
-                *self.value_mut() = None;
-            }
-            self.inner_combine_children_with(that.children(), f);
-        } else if n == self.prefix().len() {
-            // self is a prefix of that
-            *self.value_mut() = None;
-            let that = that.materialize_shortened(n);
-            self.inner_combine_children_with(&[that], f);
-        } else if n == that.prefix().len() {
-            // that is a prefix of self
-            // split at the offset, then merge in that
-            // we must not swap sides!
-            self.split(n);
-            self.inner_combine_children_with(that.children(), f);
-        } else {
-            // disjoint
-            *self.value_mut() = None;
-            self.children_mut().clear();
-        }
-        self.unsplit();
+        self.inner_combine_with_key(that, move |_, v, w| f(v, w))
+    }
+
+    /// inner combine of `self` tree with `that` tree, with access to the key
+    ///
+    /// Same as [inner_combine_with](AbstractRadixTreeMut::inner_combine_with), except that `f` also gets
+    /// the full key of the elements to be combined.
+    fn inner_combine_with_key<W: TValue>(
+        &mut self,
+        that: &impl AbstractRadixTree<K, W>,
+        f: impl Fn(&[K], &mut V, &W) -> bool + Copy,
+    ) {
+        self.inner_combine_with_key0(that, &mut Vec::new(), f)
     }
 
     /// Left combine of `self` tree with `that` tree
@@ -441,29 +750,19 @@ pub trait AbstractRadixTreeMut<K: TKey, V: TValue>: internals::AbstractRadixTree
         that: &impl AbstractRadixTree<K, W>,
         f: impl Fn(&mut V, &W) -> bool + Copy,
     ) {
-        let n = common_prefix(self.prefix(), that.prefix());
-        if n == self.prefix().len() && n == that.prefix().len() {
-            // prefixes are identical
-            if let Some(w) = that.value() {
-                if let Some(v) = self.value_mut() {
-                    if !f(v, w) {
-                        *self.value_mut() = None;
-                    }
-                }
-            }
-            self.left_combine_children_with(that.children(), f);
-        } else if n == self.prefix().len() {
-            // self is a prefix of that
-            let that = that.materialize_shortened(n);
-            self.left_combine_children_with(&[that], f);
-        } else if n == that.prefix().len() {
-            // that is a prefix of self
-            self.split(n);
-            self.left_combine_children_with(that.children(), f);
-        } else {
-            // disjoint, nothing to do
-        }
-        self.unsplit();
+        self.left_combine_with_key(that, move |_, v, w| f(v, w))
+    }
+
+    /// Left combine of `self` tree with `that` tree, with access to the key
+    ///
+    /// Same as [left_combine_with](AbstractRadixTreeMut::left_combine_with), except that `f` also gets
+    /// the full key of the elements to be combined.
+    fn left_combine_with_key<W: TValue>(
+        &mut self,
+        that: &impl AbstractRadixTree<K, W>,
+        f: impl Fn(&[K], &mut V, &W) -> bool + Copy,
+    ) {
+        self.left_combine_with_key0(that, &mut Vec::new(), f)
     }
 
     /// Remove all parts of the tree for which that contains a prefix.
@@ -543,6 +842,53 @@ pub trait AbstractRadixTreeMut<K: TKey, V: TValue>: internals::AbstractRadixTree
         }
         self.unsplit();
     }
+
+    /// Remove all keys starting with the given prefix.
+    ///
+    /// This is equivalent to [remove_prefix_with](AbstractRadixTreeMut::remove_prefix_with) with a single
+    /// element tree, but just walks down to the subtree instead of merging.
+    fn remove_prefix(&mut self, prefix: &[K]) {
+        let n = common_prefix(self.prefix(), prefix);
+        if n == prefix.len() {
+            // prefix is a prefix of self, remove everything
+            *self.value_mut() = None;
+            self.children_mut().clear();
+        } else if n == self.prefix().len() {
+            // self is a prefix of prefix
+            let rest = &prefix[n..];
+            if let Ok(index) = child_position(self.children(), &rest[0]) {
+                self.children_mut()[index].remove_prefix(rest);
+            }
+        } else {
+            // disjoint, nothing to do
+        }
+        self.unsplit();
+    }
+
+    /// Retain only the keys starting with the given prefix.
+    ///
+    /// This is equivalent to [retain_prefix_with](AbstractRadixTreeMut::retain_prefix_with) with a single
+    /// element tree, but just walks down to the subtree instead of merging.
+    fn retain_prefix(&mut self, prefix: &[K]) {
+        let n = common_prefix(self.prefix(), prefix);
+        if n == prefix.len() {
+            // prefix is a prefix of self, keep it all
+        } else if n == self.prefix().len() {
+            // self is a prefix of prefix
+            let rest = &prefix[n..];
+            *self.value_mut() = None;
+            self.children_mut()
+                .retain(|child| child.prefix()[0] == rest[0]);
+            if let Some(child) = self.children_mut().first_mut() {
+                child.retain_prefix(rest);
+            }
+        } else {
+            // disjoint, nuke it
+            *self.value_mut() = None;
+            self.children_mut().clear();
+        }
+        self.unsplit();
+    }
 }
 
 /// Implement the public AbstractRadixTreeMut for everything that has internals::AbstractRadixTreeMut implemented,
@@ -552,6 +898,18 @@ impl<K: TKey, V: TValue, T: internals::AbstractRadixTreeMut<K, V>> AbstractRadix
 {
 }
 
+/// A visitor for the node structure of a radix tree, see [visit](AbstractRadixTree::visit).
+pub trait TreeVisitor<K, V> {
+    /// Called when entering a node, with the prefix of the node relative to its parent, the optional value,
+    /// and the number of children.
+    ///
+    /// Return false to skip the children of this node.
+    fn enter_node(&mut self, prefix: &[K], value: Option<&V>, children: usize) -> bool;
+
+    /// Called when leaving a node, after all children have been visited or skipped.
+    fn leave_node(&mut self, _prefix: &[K]) {}
+}
+
 /// Trait to abstract over radix trees.
 ///
 /// This is mostly for DRYing the various flavours of radix trees in this crate as well as their rkyved versions.
@@ -591,6 +949,17 @@ pub trait AbstractRadixTree<K: TKey, V: TValue>: Sized {
         Iter::new(self, IterKey::new(self.prefix()))
     }
 
+    /// iterate over all elements with keys strictly greater than the given key
+    ///
+    /// The iterator is positioned by walking down the tree along the key, so this can be used to resume iteration
+    /// at the last returned key, e.g. for paging, without skipping all previous elements.
+    fn iter_after<'a>(&'a self, key: &[K]) -> Iter<'a, K, V, Self>
+    where
+        K: 'a,
+    {
+        Iter::after(self, key)
+    }
+
     /// iterate over all elements
     fn into_iter(self) -> ObjAndIter<Self, Iter<'static, K, V, Self>> {
         ObjAndIter::new(Box::new(self), |x| x.iter())
@@ -604,6 +973,14 @@ pub trait AbstractRadixTree<K: TKey, V: TValue>: Sized {
         Values::new(self)
     }
 
+    /// iterate over all keys - for set-like trees this avoids handing out references to the unit values
+    fn keys<'a>(&'a self) -> Keys<'a, K, V, Self>
+    where
+        K: 'a,
+    {
+        Keys(self.iter())
+    }
+
     /// True if key is contained in this set
     fn contains_key(&self, key: &[K]) -> bool {
         // if we find a tree at exactly the location, and it has a value, we have a hit
@@ -693,29 +1070,283 @@ pub trait AbstractRadixTree<K: TKey, V: TValue>: Sized {
             FindResult::NotFound { .. } => Iter::empty(),
         }
     }
+
+    /// An iterator for all keys with a certain prefix
+    fn scan_prefix_keys<'a>(&'a self, prefix: &'a [K]) -> Keys<'a, K, V, Self> {
+        Keys(self.scan_prefix(prefix))
+    }
+
+    /// All keys starting with the given prefix, as a set
+    ///
+    /// The keys are produced in order, so the set is built without sorting.
+    fn prefix_key_set<A: Array>(&self, prefix: &[K]) -> VecSet<A>
+    where
+        A::Item: for<'x> From<&'x [K]>,
+    {
+        let elements = self
+            .scan_prefix_keys(prefix)
+            .map(|key| A::Item::from(&key))
+            .collect();
+        VecSet::new_unsafe(elements)
+    }
+
+    /// True if there is at least one key starting with the given prefix
+    ///
+    /// This is a single walk down the tree, without creating an iterator.
+    fn contains_prefix(&self, prefix: &[K]) -> bool {
+        match find(self, prefix) {
+            FindResult::Found(tree) | FindResult::Prefix { tree, .. } => !tree.is_empty(),
+            FindResult::NotFound { .. } => false,
+        }
+    }
+
+    /// The longest key that is a prefix of the given key, as a prefix of the given key, and its value.
+    ///
+    /// This is the lookup of a routing table or a namespace resolver. The key itself counts as a prefix of itself.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::radix_tree::{AbstractRadixTree, RadixTree};
+    /// let routes: RadixTree<u8, &str> = vec![("/", "root"), ("/api/", "api")].into_iter().collect();
+    /// assert_eq!(routes.longest_prefix_match(b"/api/users"), Some((&b"/api/"[..], &"api")));
+    /// assert_eq!(routes.longest_prefix_match(b"/apx"), Some((&b"/"[..], &"root")));
+    /// assert_eq!(routes.longest_prefix_match(b"api"), None);
+    /// ```
+    fn longest_prefix_match<'a, 'k>(&'a self, key: &'k [K]) -> Option<(&'k [K], &'a V)> {
+        longest_prefix_match(self, key).map(|(n, value)| (&key[..n], value))
+    }
+
+    /// True if there is at least one key that is a prefix of the given key
+    fn contains_prefix_of(&self, key: &[K]) -> bool {
+        longest_prefix_match(self, key).is_some()
+    }
+
+    /// Find the node for a prefix, with information about how far the prefix matched.
+    ///
+    /// This is the building block for [filter_prefix](AbstractRadixTreeMut::filter_prefix) and
+    /// [scan_prefix](AbstractRadixTree::scan_prefix), which just return nothing if there is no match.
+    fn find_prefix(&self, prefix: &[K]) -> FindResult<&Self> {
+        find(self, prefix)
+    }
+
+    /// Walk the node structure of the tree in key order, see [TreeVisitor].
+    ///
+    /// Unlike [iter](AbstractRadixTree::iter), this exposes the prefixes of the individual nodes, e.g. for exporting
+    /// the tree to other formats.
+    fn visit(&self, visitor: &mut impl TreeVisitor<K, V>) {
+        if visitor.enter_node(self.prefix(), self.value(), self.children().len()) {
+            for child in self.children() {
+                child.visit(visitor);
+            }
+        }
+        visitor.leave_node(self.prefix());
+    }
+
+    /// Fold over all values with keys starting with the given prefix.
+    ///
+    /// This does not have to build the keys, so it is much cheaper than folding over [scan_prefix](AbstractRadixTree::scan_prefix).
+    fn fold_prefix<R>(&self, prefix: &[K], init: R, f: impl FnMut(R, &V) -> R) -> R {
+        match find(self, prefix) {
+            FindResult::Found(tree) | FindResult::Prefix { tree, .. } => {
+                Values::new(tree).fold(init, f)
+            }
+            FindResult::NotFound { .. } => init,
+        }
+    }
+
+    /// Aggregate all values by the first `n` components of their keys.
+    ///
+    /// Returns the group keys and the aggregated values, in key order. Values with keys shorter than `n` form a group
+    /// of their own. Only the group keys are built, so this is much cheaper than aggregating over [iter](AbstractRadixTree::iter).
+    fn aggregate_by_depth<R: Clone>(
+        &self,
+        n: usize,
+        init: R,
+        mut f: impl FnMut(R, &V) -> R,
+    ) -> Vec<(Vec<K>, R)> {
+        let mut res = Vec::new();
+        let mut path = Vec::new();
+        aggregate_by_depth(self, n, &mut path, &init, &mut f, &mut res);
+        res
+    }
+
+    /// Convert this tree into another flavour, copying the node structure.
+    ///
+    /// This does not have to rebuild the tree from the individual entries, so it is much cheaper than
+    /// collecting from [iter](AbstractRadixTree::iter). For lazy trees, all nodes will be loaded.
+    fn to_flavour<T: AbstractRadixTreeMut<K, V>>(&self) -> T {
+        internals::AbstractRadixTreeMut::new(
+            self.prefix().into(),
+            self.value().cloned(),
+            self.children()
+                .iter()
+                .map(|child| child.to_flavour())
+                .collect(),
+        )
+    }
+
+    /// Convert this tree into a flat [RadixTree]
+    fn to_flat(&self) -> RadixTree<K, V> {
+        self.to_flavour()
+    }
+
+    /// Convert this tree into an [RcRadixTree], e.g. to get cheap snapshots of a tree that was built mutably.
+    fn to_rc(&self) -> RcRadixTree<K, V> {
+        self.to_flavour()
+    }
+
+    /// Convert this tree into an [ArcRadixTree], e.g. to share a tree that was built mutably between threads.
+    #[cfg(feature = "rkyv")]
+    fn to_arc(&self) -> ArcRadixTree<K, V>
+    where
+        K: TArchivedKey,
+    {
+        self.to_flavour()
+    }
+}
+
+fn aggregate_by_depth<K: TKey, V: TValue, T: AbstractRadixTree<K, V>, R: Clone>(
+    tree: &T,
+    n: usize,
+    path: &mut Vec<K>,
+    init: &R,
+    f: &mut impl FnMut(R, &V) -> R,
+    res: &mut Vec<(Vec<K>, R)>,
+) {
+    let len = path.len();
+    path.extend(tree.prefix().iter().cloned());
+    if path.len() >= n {
+        // the entire subtree belongs to a single group. Siblings differ in the first element of
+        // their prefix, which is at a depth < n, so there is exactly one subtree per group.
+        if !tree.is_empty() {
+            let value = Values::new(tree).fold(init.clone(), &mut *f);
+            res.push((path[..n].to_vec(), value));
+        }
+    } else {
+        if let Some(value) = tree.value() {
+            res.push((path.clone(), f(init.clone(), value)));
+        }
+        for child in tree.children() {
+            aggregate_by_depth(child, n, path, init, f, res);
+        }
+    }
+    path.truncate(len);
 }
 
-enum FindResult<T> {
-    // Found an exact match
+/// Result of looking up a prefix in a radix tree, see [find_prefix](AbstractRadixTree::find_prefix)
+///
+/// This allows distinguishing between a prefix that is not present at all, and a prefix that is present
+/// but has no values below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindResult<T> {
+    /// Found a node whose key is exactly the prefix
     Found(T),
-    // found a tree for which the path is a prefix, with n remaining chars in the prefix of T
+    /// Found a node of which the searched prefix is a prefix
     Prefix {
-        // a tree of which the searched path is a prefix
+        /// a tree of which the searched prefix is a prefix
         tree: T,
-        // number of remaining elements in the prefix of the tree
+        /// number of remaining elements in the prefix of the tree
         rt: usize,
     },
-    // did not find anything, T is the closest match, with n remaining (unmatched) in the prefix of T
+    /// Did not find anything
     NotFound {
-        // the closest match
+        /// the closest match
         closest: T,
-        // number of remaining elements in the prefix of the tree
+        /// number of remaining elements in the prefix of the tree
         rt: usize,
-        // number of remaining elements in the search prefix
+        /// number of remaining elements in the search prefix
         rp: usize,
     },
 }
 
+/// Error when a prefix is not present in a radix tree, see [try_filter_prefix](AbstractRadixTreeMut::try_filter_prefix)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixNotFoundError {
+    matched: usize,
+}
+
+impl PrefixNotFoundError {
+    /// The number of elements of the prefix that matched a key before the search failed
+    pub fn matched(&self) -> usize {
+        self.matched
+    }
+}
+
+impl std::fmt::Display for PrefixNotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "prefix not found, matched {} elements", self.matched)
+    }
+}
+
+impl std::error::Error for PrefixNotFoundError {}
+
+/// Position of the child whose prefix starts with `c`, with the same result as a binary search
+///
+/// Nodes with a large fanout are typically dense, e.g. for byte keys. So for byte keys, if the first components
+/// of the children form a contiguous run, the position is computed directly instead.
+fn child_position<K: TKey, V: TValue, T: AbstractRadixTree<K, V>>(
+    children: &[T],
+    c: &K,
+) -> Result<usize, usize> {
+    if let (Some(c), Some(first), Some(last)) = (
+        as_byte(c),
+        children.first().and_then(|x| as_byte(&x.prefix()[0])),
+        children.last().and_then(|x| as_byte(&x.prefix()[0])),
+    ) {
+        if usize::from(last - first) + 1 == children.len() {
+            return if c < first {
+                Err(0)
+            } else if c > last {
+                Err(children.len())
+            } else {
+                Ok(usize::from(c - first))
+            };
+        }
+    }
+    children.binary_search_by(|e| e.prefix()[0].cmp(c))
+}
+
+/// Mutable access to the value for a key that is known to be present, so the path can be copied on the way down
+fn get_mut_unchecked<'a, K: TKey, V: TValue, T: AbstractRadixTreeMut<K, V>>(
+    tree: &'a mut T,
+    key: &[K],
+) -> Option<&'a mut V> {
+    let rest = &key[tree.prefix().len()..];
+    if rest.is_empty() {
+        tree.value_mut().as_mut()
+    } else {
+        let index = child_position(tree.children(), &rest[0]).ok()?;
+        get_mut_unchecked(&mut tree.children_mut()[index], rest)
+    }
+}
+
+/// The key component as a byte, if it is a byte. This is resolved at compile time.
+fn as_byte<K: 'static>(c: &K) -> Option<u8> {
+    (c as &dyn Any).downcast_ref::<u8>().copied()
+}
+
+/// walk down the tree along the key, remembering the length of the last key with a value
+fn longest_prefix_match<'a, K: TKey, V: TValue, T: AbstractRadixTree<K, V>>(
+    mut tree: &'a T,
+    key: &[K],
+) -> Option<(usize, &'a V)> {
+    let mut rest = key;
+    let mut best = None;
+    loop {
+        if !rest.starts_with(tree.prefix()) {
+            return best;
+        }
+        rest = &rest[tree.prefix().len()..];
+        if let Some(value) = tree.value() {
+            best = Some((key.len() - rest.len(), value));
+        }
+        match rest.first().map(|c| child_position(tree.children(), c)) {
+            Some(Ok(index)) => tree = &tree.children()[index],
+            _ => return best,
+        }
+    }
+}
+
 /// find a prefix in a tree. Will either return
 /// - Found(tree) if we found the tree exactly,
 /// - Prefix if we found a tree of which prefix is a prefix
@@ -738,7 +1369,7 @@ fn find<'a, K: TKey, V: TValue, T: AbstractRadixTree<K, V>>(
     } else if rt == 0 {
         // prefix is a subtree of tree
         let c = &prefix[n];
-        if let Ok(index) = tree.children().binary_search_by(|e| e.prefix()[0].cmp(c)) {
+        if let Ok(index) = child_position(tree.children(), c) {
             let child = &tree.children()[index];
             find(child, &prefix[n..])
         } else {
@@ -758,6 +1389,25 @@ fn find<'a, K: TKey, V: TValue, T: AbstractRadixTree<K, V>>(
     }
 }
 
+fn rekey_components<K: TKey, V: TValue, T: AbstractRadixTreeMut<K, V>>(
+    tree: &T,
+    f: &impl Fn(&K) -> K,
+) -> T {
+    let prefix: SmallVec<[K; 16]> = tree.prefix().iter().map(f).collect();
+    let children: Vec<T> = tree
+        .children()
+        .iter()
+        .map(|child| rekey_components(child, f))
+        .collect();
+    debug_assert!(
+        children
+            .windows(2)
+            .all(|w| w[0].prefix()[0] < w[1].prefix()[0]),
+        "rekey_components requires a strictly monotone transformation"
+    );
+    T::new(prefix.into(), tree.value().cloned(), children)
+}
+
 fn materialize<T, K: TKey, V: TValue>(tree: &T) -> T::Materialized
 where
     K: Clone,
@@ -904,6 +1554,48 @@ impl<'a, K: TKey, V: TValue, T: AbstractRadixTree<K, V>> Iter<'a, K, V, T> {
         }
     }
 
+    /// An iterator over all elements of `tree` with keys strictly greater than `key`
+    fn after(tree: &'a T, key: &[K]) -> Self {
+        let mut res = Self::empty();
+        let mut node = tree;
+        let mut rest = key;
+        loop {
+            let prefix = node.prefix();
+            let n = common_prefix(prefix, rest);
+            if n == prefix.len() && n == rest.len() {
+                // exact match, skip the value but not the children
+                res.push(node, 1);
+                break;
+            } else if n == prefix.len() {
+                // the value of node is before the key
+                rest = &rest[n..];
+                match child_position(node.children(), &rest[0]) {
+                    Ok(index) => {
+                        // continue with the next child once the child containing the key is done
+                        res.push(node, index + 2);
+                        node = &node.children()[index];
+                    }
+                    Err(index) => {
+                        res.push(node, index + 1);
+                        break;
+                    }
+                }
+            } else {
+                // either the key is a prefix of the node, or they diverge
+                if n == rest.len() || prefix[n] > rest[n] {
+                    res.push(node, 0);
+                }
+                break;
+            }
+        }
+        res
+    }
+
+    fn push(&mut self, tree: &'a T, pos: usize) {
+        self.path.append(tree.prefix());
+        self.stack.push((tree, pos));
+    }
+
     fn tree(&self) -> &'a T {
         self.stack.last().unwrap().0
     }
@@ -940,6 +1632,36 @@ impl<'a, K: TKey, V: 'a + TValue, T: AbstractRadixTree<K, V>> Iterator for Iter<
     }
 }
 
+/// An iterator over the keys of a radix tree
+///
+/// Nodes are only checked for the presence of a value, so no value references are produced.
+pub struct Keys<'a, K, V, T>(Iter<'a, K, V, T>);
+
+impl<'a, K: TKey, V: TValue, T: AbstractRadixTree<K, V>> SortedByKey for Keys<'a, K, V, T> {}
+
+impl<'a, K: TKey, V: 'a + TValue, T: AbstractRadixTree<K, V>> Iterator for Keys<'a, K, V, T> {
+    type Item = IterKey<K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let iter = &mut self.0;
+        while !iter.stack.is_empty() {
+            if let Some(pos) = iter.inc() {
+                if pos < iter.tree().children().len() {
+                    let child = &iter.tree().children()[pos];
+                    iter.path.append(child.prefix());
+                    iter.stack.push((child, 0));
+                } else {
+                    iter.path.pop(iter.tree().prefix().len());
+                    iter.stack.pop();
+                }
+            } else if iter.tree().value().is_some() {
+                return Some(iter.path.clone());
+            }
+        }
+        None
+    }
+}
+
 struct RadixTreeConverter<K, V>(PhantomData<(K, V)>);
 
 impl<T: AbstractRadixTree<K, V>, K: TKey, V: TValue> Converter<&T, T::Materialized>
@@ -1083,7 +1805,7 @@ fn outer_combine<
         // disjoint
         children.push(a.materialize_shortened(n));
         children.push(b.materialize_shortened(n));
-        children.sort_by_key(|x| x.prefix()[0]);
+        children.sort_by(|a, b| a.prefix()[0].cmp(&b.prefix()[0]));
     }
     let mut res = R::new(prefix, value, children);
     res.unsplit();
@@ -1257,15 +1979,20 @@ where
     }
 }
 
-/// In place merge operation
+/// Merge operation
 struct OuterCombineOp<F, P>(F, PhantomData<P>);
 
-impl<'a, F, K, V, A, B, C> MergeOperation<InPlaceVecMergeStateRef<'a, A, B, C>>
-    for OuterCombineOp<F, (K, V)>
+/// In place merge operation, like [OuterCombineOp] but passing the full key to `f`.
+///
+/// The key of the parent node is kept in the [RefCell], since the merge operation is only borrowed immutably.
+struct OuterCombineWithOp<'k, F, K, P>(F, RefCell<&'k mut Vec<K>>, PhantomData<P>);
+
+impl<'a, 'k, F, K, V, A, B, C> MergeOperation<InPlaceVecMergeStateRef<'a, A, B, C>>
+    for OuterCombineWithOp<'k, F, K, V>
 where
     K: TKey,
     V: TValue,
-    F: Fn(&mut V, &V) -> bool + Copy,
+    F: Fn(&[K], &mut V, &V) -> bool + Copy,
     B: AbstractRadixTree<K, V, Materialized = A>,
     C: Converter<&'a B, A>,
     A: AbstractRadixTreeMut<K, V, Materialized = A>,
@@ -1283,7 +2010,7 @@ where
         let (a, b) = m.source_slices_mut();
         let av = &mut a[0];
         let bv = &b[0];
-        av.outer_combine_with(bv, self.0);
+        av.outer_combine_with_key0(bv, &mut self.1.borrow_mut(), self.0);
         // we have modified av in place. We are only going to take it over if it
         // is non-empty, otherwise we skip it.
         let take = !av.is_empty();
@@ -1333,15 +2060,18 @@ where
     }
 }
 
-/// In place intersection operation
+/// Intersection operation
 struct InnerCombineOp<F, P>(F, PhantomData<P>);
 
-impl<'a, K, V, W, F, I, R> MergeOperation<I> for InnerCombineOp<F, (K, V, W)>
+/// In place intersection operation, like [InnerCombineOp] but passing the full key to `f`
+struct InnerCombineWithOp<'k, F, K, P>(F, RefCell<&'k mut Vec<K>>, PhantomData<P>);
+
+impl<'a, 'k, K, V, W, F, I, R> MergeOperation<I> for InnerCombineWithOp<'k, F, K, (V, W)>
 where
     K: TKey,
     V: TValue,
     W: TValue,
-    F: Fn(&mut V, &W) -> bool + Copy,
+    F: Fn(&[K], &mut V, &W) -> bool + Copy,
     I: MutateInput<A = R>,
     I::B: AbstractRadixTree<K, W>,
     R: AbstractRadixTreeMut<K, V, Materialized = R>,
@@ -1359,7 +2089,7 @@ where
         let (a, b) = m.source_slices_mut();
         let av = &mut a[0];
         let bv = &b[0];
-        av.inner_combine_with(bv, self.0);
+        av.inner_combine_with_key0(bv, &mut self.1.borrow_mut(), self.0);
         // we have modified av in place. We are only going to take it over if it
         // is non-empty, otherwise we skip it.
         let take = !av.is_empty();
@@ -1410,15 +2140,18 @@ where
     }
 }
 
-/// In place intersection operation
+/// Left combine operation
 struct LeftCombineOp<F, P>(F, PhantomData<P>);
 
-impl<'a, K, V, W, F, I, R> MergeOperation<I> for LeftCombineOp<F, (K, V, W)>
+/// In place left combine operation, like [LeftCombineOp] but passing the full key to `f`
+struct LeftCombineWithOp<'k, F, K, P>(F, RefCell<&'k mut Vec<K>>, PhantomData<P>);
+
+impl<'a, 'k, K, V, W, F, I, R> MergeOperation<I> for LeftCombineWithOp<'k, F, K, (V, W)>
 where
     K: TKey,
     V: TValue,
     W: TValue,
-    F: Fn(&mut V, &W) -> bool + Copy,
+    F: Fn(&[K], &mut V, &W) -> bool + Copy,
     I: MutateInput<A = R>,
     I::B: AbstractRadixTree<K, W>,
     R: AbstractRadixTreeMut<K, V, Materialized = R>,
@@ -1436,7 +2169,7 @@ where
         let (a, b) = m.source_slices_mut();
         let av = &mut a[0];
         let bv = &b[0];
-        av.left_combine_with(bv, self.0);
+        av.left_combine_with_key0(bv, &mut self.1.borrow_mut(), self.0);
         // we have modified av in place. We are only going to take it over if it
         // is non-empty, otherwise we skip it.
         let take = !av.is_empty();
@@ -1557,7 +2290,7 @@ where
 
 #[cfg(test)]
 mod test {
-    use std::collections::BTreeSet;
+    use std::collections::{BTreeMap, BTreeSet};
 
     use super::*;
     use maplit::btreeset;
@@ -1603,6 +2336,34 @@ mod test {
 
     quickcheck! {
 
+        fn aggregate_by_depth_sample(a: Reference, n: u8) -> bool {
+            let n = (n % 4) as usize;
+            let tree = r2t(&a);
+            let mut expected: BTreeMap<Vec<u8>, usize> = BTreeMap::new();
+            for key in a.iter() {
+                *expected.entry(key[..key.len().min(n)].to_vec()).or_default() += 1;
+            }
+            let actual = tree.aggregate_by_depth(n, 0, |c, _| c + 1);
+            let prefix_count_ok = expected.keys().all(|prefix| {
+                tree.fold_prefix(prefix, 0, |c, _| c + 1) == a.iter().filter(|k| k.starts_with(prefix)).count()
+            });
+            actual == expected.into_iter().collect::<Vec<_>>() && prefix_count_ok
+        }
+
+        fn strip_prefix_sample(a: Reference, prefix: Vec<u8>) -> bool {
+            let prefix = &prefix[..prefix.len().min(2)];
+            let expected: Reference = a
+                .iter()
+                .filter_map(|key| key.strip_prefix(prefix).map(|rest| rest.to_vec()))
+                .collect();
+            let actual = r2t(&a).strip_prefix(prefix);
+            if expected.is_empty() {
+                actual.is_none()
+            } else {
+                actual == Some(r2t(&expected))
+            }
+        }
+
         fn is_disjoint_sample(a: Test, b: Test) -> bool {
             binary_property_test(&a, &b, a.is_disjoint(&b), |a, b| !(a & b))
         }
@@ -1726,6 +2487,63 @@ mod test {
             expected == r1
         }
 
+        fn prefix_queries(a: Reference, key: Vec<u8>) -> bool {
+            // small alphabet and short keys, so there are many prefix relations
+            let shorten = |k: &Vec<u8>| k.iter().map(|x| x % 4).take(4).collect::<Vec<u8>>();
+            let a: Reference = a.iter().map(shorten).collect();
+            let key = shorten(&key);
+            let tree = r2t(&a);
+            let contains_prefix = a.iter().any(|k| k.starts_with(&key));
+            let longest = a.iter().filter(|k| key.starts_with(k)).map(|k| k.len()).max();
+            tree.contains_prefix(&key) == contains_prefix
+                && tree.longest_prefix_match(&key).map(|(m, _)| m.len()) == longest
+                && tree.longest_prefix_match(&key).into_iter().all(|(m, v)| key.starts_with(m) && tree.get(m) == Some(v))
+                && tree.contains_prefix_of(&key) == longest.is_some()
+        }
+
+        fn iter_after(a: Reference, key: Vec<u8>) -> bool {
+            use std::ops::Bound;
+            let tree = r2t(&a);
+            let actual: Vec<Vec<u8>> = tree.iter_after(&key).map(|(k, _)| k.to_vec()).collect();
+            let expected: Vec<Vec<u8>> = a.range((Bound::Excluded(key), Bound::Unbounded)).cloned().collect();
+            // resume from every key
+            let resumed = expected.windows(2).all(|w| tree.iter_after(&w[0]).next().map(|(k, _)| k.to_vec()).as_ref() == Some(&w[1]));
+            actual == expected && resumed
+        }
+
+        fn keys(a: Reference, prefix: Vec<u8>) -> bool {
+            let a: Reference = a.iter().map(|k| k.iter().map(|x| x % 4).collect()).collect();
+            let prefix: Vec<u8> = prefix.iter().map(|x| x % 4).take(2).collect();
+            let tree = r2t(&a);
+            let keys: Vec<Vec<u8>> = tree.keys().map(|k| k.to_vec()).collect();
+            let prefix_keys: Vec<Vec<u8>> = tree.scan_prefix_keys(&prefix).map(|k| k.to_vec()).collect();
+            let expected_prefix_keys: Vec<Vec<u8>> = a.iter().filter(|k| k.starts_with(&prefix)).cloned().collect();
+            keys == a.iter().cloned().collect::<Vec<_>>() && prefix_keys == expected_prefix_keys
+        }
+
+        fn key_set(a: Reference, b: Reference, prefix: Vec<u8>) -> bool {
+            let shorten = |k: &Vec<u8>| k.iter().map(|x| x % 4).take(4).collect::<Vec<u8>>();
+            let a: Reference = a.iter().map(shorten).collect();
+            let b: Reference = b.iter().map(shorten).collect();
+            let prefix: Vec<u8> = prefix.iter().map(|x| x % 4).take(2).collect();
+            let tree = r2t(&a);
+            let set: VecSet<[SmallVec<[u8; 16]>; 1]> = tree.prefix_key_set(&prefix);
+            let expected: Vec<&[u8]> = a.iter().filter(|k| k.starts_with(&prefix)).map(|k| k.as_ref()).collect();
+            let keys: VecSet<[Vec<u8>; 4]> = b.iter().cloned().collect();
+            let mut retained = tree.clone();
+            retained.retain_keys(&keys);
+            let expected_retained = r2t(&a.intersection(&b).cloned().collect());
+            set.iter().map(|k| k.as_ref()).eq(expected) && retained == expected_retained
+        }
+
+        fn retain(a: Reference, removed: u8) -> bool {
+            let keep = |key: &[u8]| !key.contains(&removed);
+            let mut actual = r2t(&a);
+            actual.retain(|key, _| keep(key));
+            let expected = r2t(&a.iter().filter(|key| keep(key)).cloned().collect());
+            actual == expected
+        }
+
         fn retain_prefix(a: Reference, b: Reference) -> bool {
             let a = a.into_iter().collect();
             let b = b.into_iter().collect();
@@ -1744,6 +2562,68 @@ mod test {
             expected == r1
         }
 
+        fn child_position(a: BTreeSet<u8>, lo: u8, n: u8, c: u8) -> bool {
+            // a sparse node, and a dense node with a contiguous run of children
+            let dense: BTreeSet<u8> = (lo..=lo.saturating_add(n)).collect();
+            [a, dense].iter().all(|keys| {
+                let t: RadixTree<u8, ()> = keys.iter().map(|k| ([*k, 0], ())).collect();
+                let binary = t.children().binary_search_by(|e| e.prefix()[0].cmp(&c));
+                super::child_position(t.children(), &c) == binary && t.contains_key(&[c, 0]) == keys.contains(&c)
+            })
+        }
+
+        fn clone_from(a: Test, b: Test) -> bool {
+            let mut a = a;
+            a.clone_from(&b);
+            a == b
+        }
+
+        fn try_filter_prefix(a: Reference, prefix: Vec<u8>) -> bool {
+            let t = r2t(&a);
+            let max_match = a.iter().map(|k| common_prefix(k, &prefix)).max().unwrap_or(0);
+            match t.try_filter_prefix(&prefix) {
+                Ok(res) => max_match == prefix.len() && res == t.filter_prefix(&prefix),
+                Err(e) => max_match < prefix.len() && e.matched() == max_match,
+            }
+        }
+
+        fn combine_with_key(a: Reference, b: Reference) -> bool {
+            // use the key as the value, so we can check that the closure gets the right key
+            let with_keys = |r: &Reference| r.iter().map(|k| (k.clone(), k.clone())).collect::<RadixTree<u8, Vec<u8>>>();
+            let (a1, b1) = (with_keys(&a), with_keys(&b));
+            let check = |k: &[u8], v: &mut Vec<u8>, w: &Vec<u8>| k == v.as_slice() && k == w.as_slice() && !k.is_empty();
+            let mut outer = a1.clone();
+            outer.outer_combine_with_key(&b1, check);
+            let mut inner = a1.clone();
+            inner.inner_combine_with_key(&b1, check);
+            let mut left = a1;
+            left.left_combine_with_key(&b1, check);
+            // all collisions except the empty key are kept, so the results are like the set operations
+            let mut expected_outer: Reference = a.union(&b).cloned().collect();
+            let mut expected_inner: Reference = a.intersection(&b).cloned().collect();
+            let mut expected_left = a.clone();
+            if a.contains(&Vec::new()) && b.contains(&Vec::new()) {
+                expected_outer.remove(&Vec::new());
+                expected_inner.remove(&Vec::new());
+                expected_left.remove(&Vec::new());
+            }
+            outer == with_keys(&expected_outer) && inner == with_keys(&expected_inner) && left == with_keys(&expected_left)
+        }
+
+        fn remove_retain_prefix_slice(a: Reference, prefix: Vec<u8>) -> bool {
+            let a1: Test = r2t(&a);
+            let mut removed = a1.clone();
+            removed.remove_prefix(&prefix);
+            let mut retained = a1.clone();
+            retained.retain_prefix(&prefix);
+            let single = Test::single(&prefix, ());
+            let mut removed_with = a1.clone();
+            removed_with.remove_prefix_with(&single, |_| true);
+            let mut retained_with = a1;
+            retained_with.retain_prefix_with(&single, |_| true);
+            removed == removed_with && retained == retained_with
+        }
+
         fn is_disjoint(a: Reference, b: Reference) -> bool {
             let a1: Test = r2t(&a);
             let b1: Test = r2t(&b);
@@ -1885,6 +2765,243 @@ mod test {
         let expected = test_tree(&["aa", "aaa", "bc", "bcd", "eeeee", "eeeef"]);
         assert_eq!(test, expected);
     }
+
+    #[test]
+    fn visit() {
+        struct Dot(Vec<String>, Vec<usize>, usize);
+        impl TreeVisitor<u8, ()> for Dot {
+            fn enter_node(&mut self, prefix: &[u8], value: Option<&()>, _: usize) -> bool {
+                let id = self.2;
+                self.2 += 1;
+                if let Some(parent) = self.1.last() {
+                    self.0.push(format!("{} -> {}", parent, id));
+                }
+                let label = std::str::from_utf8(prefix).unwrap();
+                let shape = if value.is_some() { "box" } else { "ellipse" };
+                self.0
+                    .push(format!("{} [label={:?} shape={}]", id, label, shape));
+                self.1.push(id);
+                // do not descend into the subtree of "b"
+                prefix != b"b"
+            }
+            fn leave_node(&mut self, _: &[u8]) {
+                self.1.pop();
+            }
+        }
+        let tree = test_tree(&["aa", "ab", "b", "bc"]);
+        let mut dot = Dot(Vec::new(), Vec::new(), 0);
+        tree.visit(&mut dot);
+        assert!(dot.1.is_empty());
+        assert_eq!(
+            dot.0,
+            vec![
+                "0 [label=\"\" shape=ellipse]",
+                "0 -> 1",
+                "1 [label=\"a\" shape=ellipse]",
+                "1 -> 2",
+                "2 [label=\"a\" shape=box]",
+                "1 -> 3",
+                "3 [label=\"b\" shape=box]",
+                "0 -> 4",
+                "4 [label=\"b\" shape=box]",
+            ]
+        );
+    }
+
+    #[test]
+    fn fold_and_aggregate() {
+        let mut tree: RadixTree<u8, u32> = RadixTree::default();
+        for (k, v) in [
+            ("a", 1),
+            ("ab", 2),
+            ("abc", 3),
+            ("abd", 4),
+            ("b", 5),
+            ("bcd", 6),
+        ] {
+            tree.insert(k.as_bytes(), v);
+        }
+        let sum = |prefix: &str| tree.fold_prefix(prefix.as_bytes(), 0, |a, b| a + b);
+        assert_eq!(sum(""), 21);
+        assert_eq!(sum("ab"), 9);
+        assert_eq!(sum("bc"), 6);
+        assert_eq!(sum("x"), 0);
+        let count = |n| {
+            tree.aggregate_by_depth(n, 0, |a, _| a + 1)
+                .into_iter()
+                .map(|(k, v)| (String::from_utf8(k).unwrap(), v))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(count(0), vec![("".into(), 6)]);
+        assert_eq!(count(1), vec![("a".into(), 4), ("b".into(), 2)]);
+        assert_eq!(
+            count(2),
+            vec![
+                ("a".into(), 1),
+                ("ab".into(), 3),
+                ("b".into(), 1),
+                ("bc".into(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn string_keys() {
+        let path = |p: &str| p.split('/').map(String::from).collect::<Vec<_>>();
+        let mut res: RadixTree<String, u32> = RadixTree::default();
+        res.insert(&path("usr/local/bin"), 1);
+        res.insert(&path("usr/local/lib"), 2);
+        res.insert(&path("usr/bin"), 3);
+        res.insert(&path("etc"), 4);
+        assert_eq!(res.get(&path("usr/local/lib")), Some(&2));
+        assert_eq!(res.get(&path("usr/local")), None);
+        let usr_local = res.filter_prefix(&path("usr/local"));
+        assert_eq!(usr_local.values().copied().collect::<Vec<_>>(), vec![1, 2]);
+        res.remove_prefix_with(&RadixTree::single(&path("usr"), ()), |_| true);
+        assert_eq!(res.values().copied().collect::<Vec<_>>(), vec![4]);
+    }
+
+    #[test]
+    fn non_send_values() {
+        // neither Send, Sync, Debug nor Archive
+        #[derive(Clone)]
+        struct Value(std::rc::Rc<u32>);
+        let mut res: RadixTree<u8, Value> = RadixTree::default();
+        res.insert(b"ab", Value(std::rc::Rc::new(1)));
+        res.insert(b"abc", Value(std::rc::Rc::new(2)));
+        res.union_with(&RadixTree::single(b"b", Value(std::rc::Rc::new(3))));
+        assert_eq!(res.get(b"abc").map(|v| *v.0), Some(2));
+        assert_eq!(
+            res.values().map(|v| *v.0).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rkyv")]
+    fn arc_deep_size() {
+        let mut a: ArcRadixTree<u8, u32> = ArcRadixTree::default();
+        for i in 0..1000u32 {
+            a.insert(i.to_string().as_bytes(), i);
+        }
+        let root = std::mem::size_of::<ArcRadixTree<u8, u32>>();
+        let total = a.deep_size_unique();
+        assert!(total > root);
+        assert_eq!(a.deep_size_shared(), 0);
+        // everything but the root is shared with the snapshot
+        let snapshot = a.clone();
+        assert_eq!(a.deep_size_unique(), root);
+        assert_eq!(a.deep_size_shared(), total - root);
+        // modification copies the path to the modified node
+        a.insert(b"1000", 1000);
+        assert!(a.deep_size_unique() > root);
+        assert!(a.deep_size_shared() < total - root);
+        assert_eq!(
+            snapshot.deep_size_unique() + snapshot.deep_size_shared(),
+            total
+        );
+        drop(snapshot);
+        assert_eq!(a.deep_size_shared(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "rkyv")]
+    fn arc_get_mut() {
+        let mut a: ArcRadixTree<u8, u32> = ArcRadixTree::default();
+        for i in 0..1000u32 {
+            a.insert(i.to_string().as_bytes(), i);
+        }
+        let total = a.deep_size_unique();
+        let snapshot = a.clone();
+        // a miss does not copy anything
+        assert!(a.get_mut(b"1000").is_none());
+        assert!(a.get_mut(b"12a").is_none());
+        assert_eq!(a.deep_size_shared() + a.deep_size_unique(), total);
+        *a.get_mut(b"123").unwrap() += 1000;
+        assert_eq!(a.get(b"123"), Some(&1123));
+        assert_eq!(snapshot.get(b"123"), Some(&123));
+        // only the path to the modified node is copied
+        assert!(a.deep_size_unique() < total / 10);
+        assert_eq!(a.iter().count(), 1000);
+    }
+
+    #[test]
+    #[cfg(feature = "rkyv")]
+    fn arc_retain_shared() {
+        let mut a: ArcRadixTree<u8, u32> = ArcRadixTree::default();
+        for i in 0..1000u32 {
+            a.insert(i.to_string().as_bytes(), i);
+        }
+        let total = a.deep_size_unique();
+        let snapshot = a.clone();
+        let root = a.deep_size_unique();
+        // retaining everything does not copy anything
+        a.retain(|_, _| true);
+        assert_eq!(a.deep_size_unique(), root);
+        assert_eq!(a.deep_size_shared() + a.deep_size_unique(), total);
+        // only the path to the removed element is copied
+        a.retain(|key, _| key != b"123");
+        assert!(a.deep_size_unique() < total / 10);
+        assert_eq!(a.get(b"123"), None);
+        assert_eq!(snapshot.get(b"123"), Some(&123));
+        assert_eq!(a.iter().count(), 999);
+    }
+
+    #[test]
+    fn find_prefix() {
+        let t = test_tree(&["aabc", "aabd"]);
+        assert!(matches!(t.find_prefix(b"aab"), FindResult::Found(_)));
+        assert!(matches!(
+            t.find_prefix(b"a"),
+            FindResult::Prefix { rt: 2, .. }
+        ));
+        assert!(matches!(
+            t.find_prefix(b"aax"),
+            FindResult::NotFound { rt: 1, rp: 1, .. }
+        ));
+        assert_eq!(t.try_filter_prefix(b"aaby").unwrap_err().matched(), 3);
+        // present, but empty
+        let empty = RadixTree::<u8, ()>::default();
+        assert_eq!(empty.try_filter_prefix(b""), Ok(empty.clone()));
+        assert!(empty.try_filter_prefix(b"a").is_err());
+    }
+
+    #[test]
+    fn strip_prefix_and_rekey() {
+        let tree = test_tree(&["ns/a", "ns/ab", "ns/b", "other"]);
+        let stripped = tree.strip_prefix(b"ns/").unwrap();
+        assert_eq!(stripped, test_tree(&["a", "ab", "b"]));
+        let mut prepended = stripped.clone();
+        prepended.prepend(b"ns/");
+        assert_eq!(prepended, tree.filter_prefix(b"ns/"));
+        // the prefix ends in the middle of a node prefix
+        assert_eq!(tree.strip_prefix(b"ot").unwrap(), test_tree(&["her"]));
+        assert_eq!(tree.strip_prefix(b"").unwrap(), tree);
+        assert!(tree.strip_prefix(b"x").is_none());
+        assert!(tree.strip_prefix(b"other/").is_none());
+        let upper = tree.rekey_components(|c| c.to_ascii_uppercase());
+        assert_eq!(upper, test_tree(&["NS/A", "NS/AB", "NS/B", "OTHER"]));
+    }
+
+    #[test]
+    fn flavour_conversions() {
+        let flat = test_tree(&["a", "ab", "abc", "b", "ba", "bcd"]);
+        let rc = flat.to_rc();
+        assert_eq!(rc.values().count(), 6);
+        assert_eq!(rc.get(b"bcd"), Some(&()));
+        assert_eq!(rc.to_flat(), flat);
+        #[cfg(feature = "rkyv")]
+        {
+            let arc = rc.to_arc();
+            assert_eq!(arc.get(b"bcd"), Some(&()));
+            assert_eq!(RadixTree::from(&arc), flat);
+        }
+        #[cfg(feature = "lazy_radixtree")]
+        {
+            let lazy = LazyRadixTree::from(flat.clone());
+            assert_eq!(RadixTree::from(&lazy), flat);
+        }
+    }
 }
 
 fn offset_from<T, U>(base: *const T, p: *const U) -> usize {
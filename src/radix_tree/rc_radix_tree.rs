@@ -0,0 +1,137 @@
+use super::{internals, AbstractRadixTree, Fragment, RadixTree, TKey, TValue};
+use std::rc::Rc;
+
+/// A generic radix tree with structural sharing and copy on write, for single threaded use
+///
+/// This is the same as an [ArcRadixTree](super::ArcRadixTree), except that the children are shared using an [Rc]
+/// instead of an [Arc](std::sync::Arc). This avoids the overhead of atomic reference counting when the tree
+/// does not have to be shared between threads, and does not require rkyv.
+///
+/// Snapshots are cheap (O(1)) clones. Modifying a tree will only copy the nodes on the path to the modification
+/// that are shared with another snapshot.
+#[derive(Clone)]
+pub struct RcRadixTree<K, V> {
+    prefix: Fragment<K>,
+    value: Option<V>,
+    /// None for a node without children, so leaves do not need an allocation
+    children: Option<Rc<Vec<Self>>>,
+}
+
+impl<K: TKey, V: TValue> Default for RcRadixTree<K, V> {
+    fn default() -> Self {
+        Self {
+            prefix: Default::default(),
+            value: Default::default(),
+            children: None,
+        }
+    }
+}
+
+impl<K: TKey, V: TValue> AbstractRadixTree<K, V> for RcRadixTree<K, V> {
+    type Materialized = RcRadixTree<K, V>;
+
+    fn prefix(&self) -> &[K] {
+        &self.prefix
+    }
+
+    fn value(&self) -> Option<&V> {
+        self.value.as_ref()
+    }
+
+    fn children(&self) -> &[Self] {
+        self.children
+            .as_ref()
+            .map(|x| x.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+impl<K: TKey, V: TValue> internals::AbstractRadixTreeMut<K, V> for RcRadixTree<K, V> {
+    fn new(prefix: Fragment<K>, value: Option<V>, children: Vec<Self>) -> Self {
+        let children = if children.is_empty() {
+            None
+        } else {
+            Some(Rc::new(children))
+        };
+        Self {
+            prefix,
+            value,
+            children,
+        }
+    }
+
+    fn value_mut(&mut self) -> &mut Option<V> {
+        &mut self.value
+    }
+
+    fn prefix_mut(&mut self) -> &mut Fragment<K> {
+        &mut self.prefix
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Self> {
+        // copy on write, like for the ArcRadixTree. If we are the sole owner, this is very cheap.
+        Rc::make_mut(self.children.get_or_insert_with(Default::default))
+    }
+}
+
+impl<K: TKey, V: TValue + PartialEq> PartialEq for RcRadixTree<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.prefix() == other.prefix()
+            && self.value() == other.value()
+            && self.children() == other.children()
+    }
+}
+
+impl<K: TKey, V: TValue + Eq> Eq for RcRadixTree<K, V> {}
+
+impl<K: TKey + std::fmt::Debug, V: TValue + std::fmt::Debug> std::fmt::Debug for RcRadixTree<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RcRadixTree")
+            .field("prefix", &self.prefix())
+            .field("value", &self.value())
+            .field("children", &self.children())
+            .finish()
+    }
+}
+
+impl<K: TKey, V: TValue> From<RadixTree<K, V>> for RcRadixTree<K, V> {
+    fn from(value: RadixTree<K, V>) -> Self {
+        let RadixTree {
+            prefix,
+            value,
+            children,
+        } = value;
+        let children = children.into_iter().map(Self::from).collect::<Vec<_>>();
+        internals::AbstractRadixTreeMut::new(prefix, value, children)
+    }
+}
+
+impl<K: TKey, V: TValue> From<&RcRadixTree<K, V>> for RadixTree<K, V> {
+    fn from(value: &RcRadixTree<K, V>) -> Self {
+        value.to_flavour()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::AbstractRadixTreeMut;
+    use super::*;
+
+    #[test]
+    fn snapshots() {
+        let mut a: RcRadixTree<u8, u32> = RcRadixTree::default();
+        for i in 0..100u32 {
+            a.insert(i.to_string().as_bytes(), i);
+        }
+        let snapshot = a.clone();
+        a.insert(b"1000", 1000);
+        a.remove_prefix_with(&RcRadixTree::single(b"5", ()), |_| true);
+        assert_eq!(snapshot.values().count(), 100);
+        assert_eq!(snapshot.get(b"55"), Some(&55));
+        assert_eq!(a.get(b"55"), None);
+        assert_eq!(a.get(b"1000"), Some(&1000));
+        // roundtrip via the flat tree
+        let flat = RadixTree::from(&a);
+        assert_eq!(RcRadixTree::from(flat), a);
+    }
+}
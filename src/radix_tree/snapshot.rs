@@ -0,0 +1,279 @@
+//! Incremental persistence of [ArcRadixTree] snapshots
+//!
+//! Snapshots of an evolving [ArcRadixTree] share most of their nodes with previous snapshots. A [SnapshotWriter]
+//! remembers where the children of all previously written nodes are, so each new snapshot is written as a segment
+//! containing only the nodes that changed since the previous snapshots, plus a new root. Unchanged subtrees are
+//! referenced by their position in earlier segments.
+//!
+//! Segments must be appended to each other in the order they were written. The concatenation of all segments up to
+//! and including a snapshot is an rkyv archive of that snapshot, which can be loaded with
+//! [SnapshotWriter::load_unchecked].
+//!
+//! Since later segments point back into earlier ones, the archive does not have the strictly nested layout that rkyv
+//! validation requires, so segments can not be validated with `check_archived_root`. Only load trusted segments.
+use super::{ArcRadixTree, TArchivedKey, TValue};
+use rkyv::{
+    archived_root,
+    de::{SharedDeserializeRegistry, SharedPointer},
+    ser::{
+        serializers::{
+            AllocScratch, CompositeSerializer, FallbackScratch, HeapScratch, WriteSerializer,
+        },
+        Serializer, SharedSerializeRegistry,
+    },
+    AlignedVec, Archive, Archived, Deserialize, Fallible, Serialize,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Arc,
+};
+
+/// The serializer used to write snapshot segments
+pub type SnapshotSerializer = CompositeSerializer<
+    WriteSerializer<AlignedVec>,
+    FallbackScratch<HeapScratch<256>, AllocScratch>,
+    SnapshotRegistry,
+>;
+
+/// Error when writing or loading snapshot segments
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// error while serializing a segment
+    Serialize(String),
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serialize(cause) => write!(f, "error while serializing: {}", cause),
+        }
+    }
+}
+
+/// Positions of the shared children of all nodes in previously written segments, by address
+#[derive(Debug, Default)]
+pub struct SnapshotRegistry(HashMap<usize, usize>);
+
+impl Fallible for SnapshotRegistry {
+    type Error = SnapshotError;
+}
+
+impl SharedSerializeRegistry for SnapshotRegistry {
+    fn get_shared_ptr(&mut self, value: *const u8) -> Option<usize> {
+        self.0.get(&(value as usize)).copied()
+    }
+
+    fn add_shared_ptr(&mut self, value: *const u8, pos: usize) -> Result<(), Self::Error> {
+        self.0.insert(value as usize, pos);
+        Ok(())
+    }
+}
+
+/// The deserializer used to load snapshot segments
+///
+/// In addition to sharing deserialized children, this keeps track of where in the archive they came from,
+/// so writing can be continued after loading.
+#[derive(Default)]
+pub struct SnapshotDeserializer(HashMap<usize, Box<dyn SharedPointer>>);
+
+impl Fallible for SnapshotDeserializer {
+    type Error = SnapshotError;
+}
+
+impl SharedDeserializeRegistry for SnapshotDeserializer {
+    fn get_shared_ptr(&mut self, ptr: *const u8) -> Option<&dyn SharedPointer> {
+        self.0.get(&(ptr as usize)).map(|p| p.as_ref())
+    }
+
+    fn add_shared_ptr(
+        &mut self,
+        ptr: *const u8,
+        shared: Box<dyn SharedPointer>,
+    ) -> Result<(), Self::Error> {
+        self.0.insert(ptr as usize, shared);
+        Ok(())
+    }
+}
+
+/// Writes snapshots of an [ArcRadixTree] as segments containing only the nodes that changed
+///
+/// The writer keeps all nodes it has written alive, so that their addresses can not be reused for different
+/// nodes. To compact the storage and release these nodes, write the current tree with a new writer.
+pub struct SnapshotWriter<K: TArchivedKey, V: TValue + Archive> {
+    registry: SnapshotRegistry,
+    arcs: BTreeMap<usize, Arc<Vec<ArcRadixTree<K, V>>>>,
+    pos: usize,
+}
+
+impl<K: TArchivedKey, V: TValue + Archive> Default for SnapshotWriter<K, V> {
+    fn default() -> Self {
+        Self {
+            registry: SnapshotRegistry::default(),
+            arcs: BTreeMap::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<K: TArchivedKey, V: TValue + Archive> SnapshotWriter<K, V> {
+    /// A writer for a new, empty sequence of segments
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The total size of all segments written so far, including the ones this writer was loaded from
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Write a snapshot of the tree as a new segment, to be appended to all previous segments
+    ///
+    /// Subtrees that are shared with previously written snapshots are not written again.
+    pub fn write(&mut self, tree: &ArcRadixTree<K, V>) -> Result<AlignedVec, SnapshotError>
+    where
+        K: Serialize<SnapshotSerializer>,
+        V: Serialize<SnapshotSerializer>,
+    {
+        let mut serializer = CompositeSerializer::new(
+            WriteSerializer::with_pos(AlignedVec::new(), self.pos),
+            FallbackScratch::default(),
+            std::mem::take(&mut self.registry),
+        );
+        let res = serializer.serialize_value(tree);
+        let (segment, _, mut registry) = serializer.into_components();
+        if let Err(cause) = res {
+            // forget the nodes of the failed segment, which will not be stored
+            registry.0.retain(|_, pos| *pos < self.pos);
+            self.registry = registry;
+            return Err(SnapshotError::Serialize(cause.to_string()));
+        }
+        self.registry = registry;
+        tree.all_arcs(&mut self.arcs);
+        let segment = segment.into_inner();
+        self.pos += segment.len();
+        Ok(segment)
+    }
+
+    /// Load the latest snapshot from a sequence of segments, and a writer to append further segments
+    ///
+    /// An empty sequence of segments is an empty tree.
+    ///
+    /// # Safety
+    ///
+    /// The segments must have been written by a [SnapshotWriter], in order.
+    pub unsafe fn load_unchecked<S: AsRef<[u8]>>(
+        segments: impl IntoIterator<Item = S>,
+    ) -> Result<(ArcRadixTree<K, V>, Self), SnapshotError>
+    where
+        V: Archive<Archived = V>,
+        Archived<K>: Deserialize<K, SnapshotDeserializer>,
+        Archived<V>: Deserialize<V, SnapshotDeserializer>,
+    {
+        let data = concat(segments);
+        if data.is_empty() {
+            return Ok(Default::default());
+        }
+        let archived = archived_root::<ArcRadixTree<K, V>>(&data);
+        Self::from_archived(archived, &data)
+    }
+
+    fn from_archived(
+        archived: &Archived<ArcRadixTree<K, V>>,
+        data: &[u8],
+    ) -> Result<(ArcRadixTree<K, V>, Self), SnapshotError>
+    where
+        V: Archive<Archived = V>,
+        Archived<K>: Deserialize<K, SnapshotDeserializer>,
+        Archived<V>: Deserialize<V, SnapshotDeserializer>,
+    {
+        let mut deserializer = SnapshotDeserializer::default();
+        let tree: ArcRadixTree<K, V> = archived.deserialize(&mut deserializer)?;
+        // the deserialized children are at the same positions as when they were serialized
+        let base = data.as_ptr() as usize;
+        let registry = deserializer
+            .0
+            .iter()
+            .map(|(ptr, shared)| (shared.data_address() as usize, ptr - base))
+            .collect();
+        let mut arcs = BTreeMap::new();
+        tree.all_arcs(&mut arcs);
+        let writer = Self {
+            registry: SnapshotRegistry(registry),
+            arcs,
+            pos: data.len(),
+        };
+        Ok((tree, writer))
+    }
+}
+
+/// concatenate the segments into an aligned buffer, since segments are aligned relative to the start of the first
+fn concat<S: AsRef<[u8]>>(segments: impl IntoIterator<Item = S>) -> AlignedVec {
+    let mut data = AlignedVec::new();
+    for segment in segments {
+        data.extend_from_slice(segment.as_ref());
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::radix_tree::{AbstractRadixTree, AbstractRadixTreeMut};
+    use quickcheck::quickcheck;
+    use std::collections::BTreeSet;
+
+    type Test = ArcRadixTree<u8, u8>;
+
+    fn entries(tree: &Test) -> Vec<(Vec<u8>, u8)> {
+        tree.iter().map(|(k, v)| (k.to_vec(), *v)).collect()
+    }
+
+    quickcheck! {
+        fn write_and_load(batches: Vec<BTreeSet<Vec<u8>>>) -> bool {
+            let mut tree = Test::default();
+            let mut writer = SnapshotWriter::new();
+            let mut segments = Vec::new();
+            for batch in batches.iter().take(10) {
+                for key in batch {
+                    tree.insert(key, key.len() as u8);
+                }
+                segments.push(writer.write(&tree).unwrap());
+            }
+            let (mut loaded, mut loaded_writer) =
+                unsafe { SnapshotWriter::<u8, u8>::load_unchecked(&segments) }.unwrap();
+            let loaded_ok = entries(&loaded) == entries(&tree);
+            // continue writing from the loaded writer
+            for key in batches.first().into_iter().flatten() {
+                tree.insert(key, 0);
+                loaded.insert(key, 0);
+            }
+            segments.push(loaded_writer.write(&loaded).unwrap());
+            let (reloaded, _) =
+                unsafe { SnapshotWriter::<u8, u8>::load_unchecked(&segments) }.unwrap();
+            loaded_ok && entries(&reloaded) == entries(&tree)
+        }
+    }
+
+    #[test]
+    fn unchanged_subtrees_are_not_written_again() {
+        let mut tree = Test::default();
+        for i in 0..100u8 {
+            for j in 0..100u8 {
+                tree.insert(&[i, j], j);
+            }
+        }
+        let mut writer = SnapshotWriter::new();
+        let base = writer.write(&tree).unwrap();
+        tree.insert(&[7, 7, 7], 7);
+        let delta = writer.write(&tree).unwrap();
+        assert!(delta.len() * 10 < base.len());
+        assert_eq!(writer.position(), base.len() + delta.len());
+        let (loaded, _) =
+            unsafe { SnapshotWriter::<u8, u8>::load_unchecked(&[base, delta]) }.unwrap();
+        assert_eq!(loaded.get(&[7, 7, 7]), Some(&7));
+        assert_eq!(entries(&loaded), entries(&tree));
+    }
+}
@@ -0,0 +1,152 @@
+//! Block based intersection for sets of primitive integers
+//!
+//! Instead of comparing one pair of elements at a time and branching on the result, [intersection] compares whole
+//! blocks of elements without branches, which the compiler turns into SIMD instructions on targets that have them.
+//! No target specific intrinsics are used, so this works on all targets. To get the widest instructions, enable the
+//! target features of your CPU, e.g. with `-C target-cpu=native`.
+//!
+//! It works on the elements of a set as a slice, so it can be used with a [VecSet], an archived set or any other
+//! strictly sorted slice.
+//!
+//! This is opt-in: `&a & &b` and [intersection_with] still use the generic merge, even for sets of `u32` or `u64`
+//! with the `simd` feature enabled. Choosing the implementation by element type would need specialization, which is
+//! not available on stable rust. So call [intersection] directly where the intersection is hot.
+//!
+//! There is no block based membership test. [VecSet::contains] scans small sets without branches and uses the
+//! branchless binary search of std above that. Finding the block with a binary search over the last element of each
+//! block and comparing the whole block with the value was slower for all sizes in benchmarks.
+//!
+//! # Example
+//! ```
+//! use vec_collections::{simd, VecSet};
+//! let a: VecSet<[u64; 4]> = (0..1000).map(|x| x * 3).collect();
+//! let b: VecSet<[u64; 4]> = (0..1000).map(|x| x * 5).collect();
+//! let c: VecSet<[u64; 4]> = simd::intersection(&a, &b);
+//! assert_eq!(c, &a & &b);
+//! ```
+//!
+//! [VecSet]: ../struct.VecSet.html
+//! [VecSet::contains]: ../struct.VecSet.html#method.contains
+//! [intersection_with]: ../struct.VecSet.html#method.intersection_with
+use crate::VecSet;
+use smallvec::{Array, SmallVec};
+
+/// A primitive element type for the block based set operations
+pub trait SimdElement: Ord + Copy {
+    /// The number of elements that are compared at once
+    const BLOCK: usize;
+}
+
+impl SimdElement for u32 {
+    const BLOCK: usize = 8;
+}
+
+impl SimdElement for u64 {
+    const BLOCK: usize = 4;
+}
+
+/// If one set is this many times larger than the other, search for the elements of the smaller set
+const GALLOP_RATIO: usize = 32;
+
+/// The intersection of two sets, given as strictly sorted elements
+///
+/// For sets of similar size, this compares blocks of elements of both sets with each other. If one set is much
+/// smaller, its elements are searched for in the larger set instead.
+///
+/// The result is the same as `&a & &b`, which does not use this.
+pub fn intersection<T: SimdElement, A: Array<Item = T>>(a: &[T], b: &[T]) -> VecSet<A> {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let mut res = SmallVec::new();
+    if small.len().saturating_mul(GALLOP_RATIO) < large.len() {
+        gallop(small, large, &mut res);
+    } else {
+        blocks(small, large, &mut res);
+    }
+    VecSet::new_unsafe(res)
+}
+
+/// Compare all elements with the value, without branches
+#[inline]
+fn block_contains<T: SimdElement>(block: &[T], value: T) -> bool {
+    block.iter().fold(false, |found, x| found | (*x == value))
+}
+
+fn blocks<T: SimdElement, A: Array<Item = T>>(mut a: &[T], mut b: &[T], res: &mut SmallVec<A>) {
+    let n = T::BLOCK;
+    while a.len() >= n && b.len() >= n {
+        let (block_a, block_b) = (&a[..n], &b[..n]);
+        for x in block_a {
+            if block_contains(block_b, *x) {
+                res.push(*x);
+            }
+        }
+        // elements are unique, so elements of the block with the smaller last element can not match anything later
+        let (last_a, last_b) = (block_a[n - 1], block_b[n - 1]);
+        if last_a <= last_b {
+            a = &a[n..];
+        }
+        if last_b <= last_a {
+            b = &b[n..];
+        }
+    }
+    merge(a, b, res);
+}
+
+/// Scalar merge for the remaining elements
+fn merge<T: SimdElement, A: Array<Item = T>>(a: &[T], b: &[T], res: &mut SmallVec<A>) {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let (x, y) = (a[i], b[j]);
+        if x == y {
+            res.push(x);
+        }
+        i += (x <= y) as usize;
+        j += (y <= x) as usize;
+    }
+}
+
+/// Search for each element of the small set in the rest of the large set
+fn gallop<T: SimdElement, A: Array<Item = T>>(small: &[T], mut large: &[T], res: &mut SmallVec<A>) {
+    for &x in small {
+        // exponential search for a range of the large set that contains the first element >= x
+        let mut end = 1;
+        while end < large.len() && large[end - 1] < x {
+            end *= 2;
+        }
+        let start = end / 2;
+        let end = end.min(large.len());
+        // everything before x can be skipped, since the elements of the small set are increasing
+        large = &large[start + large[start..end].partition_point(|y| *y < x)..];
+        match large.first() {
+            Some(y) if *y == x => res.push(x),
+            Some(_) => {}
+            None => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+    use std::collections::BTreeSet;
+
+    quickcheck! {
+        fn intersection_u64(a: BTreeSet<u16>, b: BTreeSet<u16>, skewed: bool) -> bool {
+            let a: Vec<u64> = a.iter().map(|x| (x % 512) as u64).collect::<BTreeSet<_>>().into_iter().collect();
+            let b: Vec<u64> = if skewed {
+                // much larger than a, so the elements of a are searched for
+                (0..16384).filter(|x| x % 3 != 0).collect()
+            } else {
+                b.iter().map(|x| (x % 512) as u64).collect::<BTreeSet<_>>().into_iter().collect()
+            };
+            let expected: Vec<u64> = a.iter().filter(|x| b.binary_search(x).is_ok()).cloned().collect();
+            let actual: VecSet<[u64; 4]> = intersection(&a, &b);
+            let reversed: VecSet<[u64; 4]> = intersection(&b, &a);
+            // the same for u32, which has larger blocks
+            let narrow = |x: &[u64]| x.iter().map(|x| *x as u32).collect::<Vec<_>>();
+            let actual32: VecSet<[u32; 4]> = intersection(&narrow(&a), &narrow(&b));
+            actual.as_slice() == &expected[..] && reversed == actual && actual32.as_slice() == &narrow(&expected)[..]
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{VecMap, VecSet};
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+use smallvec::Array;
+
+/// Sizes up to this are counted individually, larger sizes are counted in a single bucket
+const MAX_TRACKED: usize = 64;
+
+/// Size statistics for a group of sets or maps, to choose the inline capacity, i.e. the [Array] parameter.
+///
+/// Record the sizes of the collections at representative points, e.g. after they have been built, then use
+/// [suggest_inline_capacity](SizeStats::suggest_inline_capacity) to pick an inline capacity. Existing collections
+/// can be converted with [VecSet::cast_storage] and [VecMap::cast_storage].
+///
+/// Recording is a few relaxed atomic operations, so a `static` instance can be shared between threads.
+///
+/// # Example
+/// ```
+/// use vec_collections::{SizeStats, VecSet};
+/// static STATS: SizeStats = SizeStats::new();
+/// for n in [1, 2, 2, 3, 10] {
+///     let set: VecSet<[u32; 2]> = (0..n).collect();
+///     STATS.record_set(&set);
+/// }
+/// assert_eq!(STATS.max(), 10);
+/// assert_eq!(STATS.spilled(2), 2);
+/// // 80% of the sets fit into an inline capacity of 3
+/// assert_eq!(STATS.suggest_inline_capacity(0.8), Some(3));
+/// ```
+pub struct SizeStats {
+    /// number of recorded sizes, by size. The last bucket counts all sizes above MAX_TRACKED.
+    counts: [AtomicU64; MAX_TRACKED + 2],
+    /// the largest recorded size
+    max: AtomicUsize,
+}
+
+impl SizeStats {
+    /// Empty statistics
+    pub const fn new() -> Self {
+        // only used to initialize the array, which gets a fresh copy for each element
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            counts: [ZERO; MAX_TRACKED + 2],
+            max: AtomicUsize::new(0),
+        }
+    }
+
+    /// Record the size of a collection
+    pub fn record(&self, len: usize) {
+        self.counts[len.min(MAX_TRACKED + 1)].fetch_add(1, Ordering::Relaxed);
+        self.max.fetch_max(len, Ordering::Relaxed);
+    }
+
+    /// Record the size of a set
+    pub fn record_set<A: Array>(&self, set: &VecSet<A>) {
+        self.record(set.len())
+    }
+
+    /// Record the size of a map
+    pub fn record_map<A: Array>(&self, map: &VecMap<A>) {
+        self.record(map.len())
+    }
+
+    /// Number of recorded sizes
+    pub fn count(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    /// The largest recorded size, the high water mark
+    pub fn max(&self) -> usize {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Number of recorded sizes that would not fit into the given inline capacity
+    ///
+    /// Sizes above 64 are not tracked individually, so for larger capacities they all count as spilled.
+    pub fn spilled(&self, inline_capacity: usize) -> u64 {
+        self.counts[inline_capacity.min(MAX_TRACKED) + 1..]
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// The smallest inline capacity so that at least the given fraction of the recorded sizes fit inline.
+    ///
+    /// Returns None if the capacity would be larger than 64, in which case an inline capacity is probably
+    /// not useful anyway.
+    pub fn suggest_inline_capacity(&self, fraction: f64) -> Option<usize> {
+        let total = self.count();
+        let mut fitting = 0;
+        for (size, count) in self.counts[..=MAX_TRACKED].iter().enumerate() {
+            fitting += count.load(Ordering::Relaxed);
+            if fitting as f64 >= fraction * total as f64 {
+                return Some(size);
+            }
+        }
+        None
+    }
+
+    /// Forget all recorded sizes
+    pub fn reset(&self) {
+        for count in self.counts.iter() {
+            count.store(0, Ordering::Relaxed);
+        }
+        self.max.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for SizeStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for SizeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SizeStats")
+            .field("count", &self.count())
+            .field("max", &self.max())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    quickcheck! {
+        fn size_stats(sizes: Vec<u8>, capacity: u8, percent: u8) -> bool {
+            let stats = SizeStats::new();
+            for size in sizes.iter() {
+                stats.record(usize::from(*size));
+            }
+            let capacity = usize::from(capacity) % (MAX_TRACKED + 1);
+            let fraction = f64::from(percent % 101) / 100.0;
+            let fitting = |c: usize| sizes.iter().filter(|s| usize::from(**s) <= c).count();
+            let expected = (0..=MAX_TRACKED).find(|c| fitting(*c) as f64 >= fraction * sizes.len() as f64);
+            stats.count() == sizes.len() as u64
+                && stats.max() == sizes.iter().map(|s| usize::from(*s)).max().unwrap_or_default()
+                && stats.spilled(capacity) == (sizes.len() - fitting(capacity)) as u64
+                && stats.suggest_inline_capacity(fraction) == expected
+        }
+    }
+}
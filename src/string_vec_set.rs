@@ -0,0 +1,359 @@
+use crate::{
+    compact::{read_len, read_varint, write_varint, CompactEncoding, CompactError},
+    VecSet,
+};
+use core::{fmt, iter::FromIterator};
+use smallvec::Array;
+
+/// number of strings per block. The first string of each block is stored in full.
+///
+/// Like in the compact sets, block boundaries are found with `%` instead of `is_multiple_of`, which needs rust 1.87.
+const BLOCK_SIZE: usize = 16;
+
+/// A read-only set of strings with prefix compression.
+///
+/// Strings are stored sorted in a single byte buffer using front coding: the first string of each block of 16 is
+/// stored in full, every following string as the length of the prefix shared with its predecessor plus the remaining
+/// suffix. For typical string sets with long shared prefixes, like paths or urls, this is much smaller than a
+/// `VecSet<[Box<str>; N]>`.
+///
+/// Lookups do a binary search over the first strings of the blocks, and then a scan of a single block. The scan
+/// uses the shared prefix lengths, so the shared prefixes are never compared more than once.
+///
+/// The compact encoding of a StringVecSet is just the byte buffer, so it can be stored and loaded cheaply.
+///
+/// # Example
+/// ```
+/// use vec_collections::StringVecSet;
+/// let set: StringVecSet = ["/usr/bin", "/usr/local/bin", "/usr/local/lib"].iter().collect();
+/// assert!(set.contains("/usr/local/bin"));
+/// assert!(!set.contains("/usr/local"));
+/// assert_eq!(set.iter().collect::<Vec<_>>(), vec!["/usr/bin", "/usr/local/bin", "/usr/local/lib"]);
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct StringVecSet {
+    /// the front coded strings
+    data: Vec<u8>,
+    /// offsets of the blocks in data
+    blocks: Vec<usize>,
+    /// the number of strings
+    len: usize,
+}
+
+/// Incrementally builds the front coded data from sorted, unique strings
+#[derive(Default)]
+struct Builder {
+    res: StringVecSet,
+    prev: Vec<u8>,
+}
+
+impl Builder {
+    #[allow(clippy::manual_is_multiple_of)]
+    fn push(&mut self, value: &[u8]) -> Result<(), CompactError> {
+        let res = &mut self.res;
+        if res.len > 0 && value <= self.prev.as_slice() {
+            return Err(CompactError::InvalidValue);
+        }
+        if res.len % BLOCK_SIZE == 0 {
+            res.blocks.push(res.data.len());
+            write_varint(value.len() as u64, &mut res.data);
+            res.data.extend_from_slice(value);
+        } else {
+            let shared = common_prefix(&self.prev, value);
+            write_varint(shared as u64, &mut res.data);
+            write_varint((value.len() - shared) as u64, &mut res.data);
+            res.data.extend_from_slice(&value[shared..]);
+        }
+        res.len += 1;
+        self.prev.clear();
+        self.prev.extend_from_slice(value);
+        Ok(())
+    }
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Read a varint from data that is valid by construction
+fn varint(data: &mut &[u8]) -> usize {
+    read_varint(data).expect("invalid front coded data") as usize
+}
+
+/// Read a length prefixed byte string from data that is valid by construction
+fn bytes<'a>(data: &mut &'a [u8], len: usize) -> &'a [u8] {
+    let (res, rest) = data.split_at(len);
+    *data = rest;
+    res
+}
+
+impl StringVecSet {
+    /// The empty set
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// The number of strings in the set
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The size of the compressed data in bytes
+    pub fn data_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Build a set from sorted, unique strings
+    ///
+    /// Returns None if the strings are not strictly sorted.
+    pub fn from_sorted<I: IntoIterator<Item = S>, S: AsRef<str>>(iter: I) -> Option<Self> {
+        let mut builder = Builder::default();
+        for s in iter {
+            builder.push(s.as_ref().as_bytes()).ok()?;
+        }
+        Some(builder.res)
+    }
+
+    /// The first string of the block at the given offset
+    fn block_head(&self, offset: usize) -> &[u8] {
+        let mut data = &self.data[offset..];
+        let len = varint(&mut data);
+        bytes(&mut data, len)
+    }
+
+    /// true if the set contains the string
+    pub fn contains(&self, value: &str) -> bool {
+        let value = value.as_bytes();
+        // find the last block whose head is <= value
+        let block = match self
+            .blocks
+            .partition_point(|offset| self.block_head(*offset) <= value)
+        {
+            0 => return false,
+            index => index - 1,
+        };
+        let end = (block + 1) * BLOCK_SIZE;
+        let mut data = &self.data[self.blocks[block]..];
+        let len = varint(&mut data);
+        let head = bytes(&mut data, len);
+        if head == value {
+            return true;
+        }
+        // length of the common prefix of value and the current string, which is < value
+        let mut lcp = common_prefix(head, value);
+        for _ in (block * BLOCK_SIZE + 1)..end.min(self.len) {
+            let shared = varint(&mut data);
+            let suffix_len = varint(&mut data);
+            let suffix = bytes(&mut data, suffix_len);
+            if shared < lcp {
+                // the current string differs from the previous one at a position where the previous one
+                // is equal to value, so it is larger than value, and so are all following strings
+                return false;
+            } else if shared == lcp {
+                let rest = &value[lcp..];
+                if suffix == rest {
+                    return true;
+                } else if suffix > rest {
+                    return false;
+                }
+                lcp += common_prefix(suffix, rest);
+            }
+            // if shared > lcp, the current string compares to value like the previous one, so it is smaller
+        }
+        false
+    }
+
+    /// An iterator over the strings in sorted order
+    ///
+    /// Since the strings are stored compressed, each string has to be reconstructed.
+    pub fn iter(&self) -> StringVecSetIter<'_> {
+        StringVecSetIter {
+            data: &self.data,
+            index: 0,
+            len: self.len,
+            current: Vec::new(),
+        }
+    }
+
+    /// Convert to a [VecSet] of owned strings, e.g. `VecSet<[Box<str>; 1]>` or `VecSet<[Arc<str>; 1]>`
+    pub fn to_vec_set<A: Array>(&self) -> VecSet<A>
+    where
+        A::Item: for<'a> From<&'a str>,
+    {
+        VecSet::new_unsafe(self.iter().map(|x| A::Item::from(x.as_str())).collect())
+    }
+}
+
+/// An iterator over the strings of a [StringVecSet]
+pub struct StringVecSetIter<'a> {
+    data: &'a [u8],
+    index: usize,
+    len: usize,
+    current: Vec<u8>,
+}
+
+impl<'a> Iterator for StringVecSetIter<'a> {
+    type Item = String;
+
+    #[allow(clippy::manual_is_multiple_of)]
+    fn next(&mut self) -> Option<String> {
+        if self.index >= self.len {
+            return None;
+        }
+        let data = &mut self.data;
+        if self.index % BLOCK_SIZE == 0 {
+            let len = varint(data);
+            self.current.clear();
+            self.current.extend_from_slice(bytes(data, len));
+        } else {
+            let shared = varint(data);
+            let suffix_len = varint(data);
+            self.current.truncate(shared);
+            self.current.extend_from_slice(bytes(data, suffix_len));
+        }
+        self.index += 1;
+        // the reconstructed string is one of the strings that were inserted, so it is valid utf8
+        Some(String::from_utf8(self.current.clone()).expect("invalid utf8"))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len - self.index;
+        (n, Some(n))
+    }
+}
+
+impl<'a> ExactSizeIterator for StringVecSetIter<'a> {}
+
+impl<S: AsRef<str>> FromIterator<S> for StringVecSet {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut strings: Vec<S> = iter.into_iter().collect();
+        strings.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        strings.dedup_by(|a, b| a.as_ref() == b.as_ref());
+        Self::from_sorted(strings).expect("strings are sorted")
+    }
+}
+
+impl<A: Array> From<&VecSet<A>> for StringVecSet
+where
+    A::Item: AsRef<str>,
+{
+    fn from(value: &VecSet<A>) -> Self {
+        // the order of a VecSet of strings is the same as the byte order
+        Self::from_sorted(value.iter()).expect("VecSet is sorted")
+    }
+}
+
+impl fmt::Debug for StringVecSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl CompactEncoding for StringVecSet {
+    fn write_compact(&self, target: &mut Vec<u8>) {
+        write_varint(self.len as u64, target);
+        write_varint(self.data.len() as u64, target);
+        target.extend_from_slice(&self.data);
+    }
+
+    /// Read a set, validating that all strings are valid utf8 and strictly sorted
+    #[allow(clippy::manual_is_multiple_of)]
+    fn read_compact(source: &mut &[u8]) -> Result<Self, CompactError> {
+        let len = read_len(source)?;
+        let size = read_len(source)?;
+        if size > source.len() {
+            return Err(CompactError::UnexpectedEof);
+        }
+        let (mut data, rest) = source.split_at(size);
+        *source = rest;
+        let mut builder = Builder::default();
+        let mut current = Vec::new();
+        let read_bytes = |data: &mut &[u8]| -> Result<Vec<u8>, CompactError> {
+            let len = read_len(data)?;
+            if len > data.len() {
+                return Err(CompactError::UnexpectedEof);
+            }
+            Ok(bytes(data, len).to_vec())
+        };
+        for index in 0..len {
+            if index % BLOCK_SIZE == 0 {
+                current = read_bytes(&mut data)?;
+            } else {
+                let shared = read_len(&mut data)?;
+                if shared > current.len() {
+                    return Err(CompactError::InvalidValue);
+                }
+                current.truncate(shared);
+                current.extend(read_bytes(&mut data)?);
+            }
+            core::str::from_utf8(&current).map_err(|_| CompactError::InvalidValue)?;
+            builder.push(&current)?;
+        }
+        if !data.is_empty() {
+            return Err(CompactError::TrailingBytes);
+        }
+        Ok(builder.res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+    use std::{collections::BTreeSet, sync::Arc};
+
+    fn with_prefixes(strings: Vec<String>) -> BTreeSet<String> {
+        // make sure there are long shared prefixes
+        strings
+            .into_iter()
+            .flat_map(|s| vec![format!("/common/prefix/{}", s), s])
+            .collect()
+    }
+
+    quickcheck! {
+        fn contains(strings: Vec<String>, probes: Vec<String>) -> bool {
+            let reference = with_prefixes(strings);
+            let set: StringVecSet = reference.iter().collect();
+            set.len() == reference.len()
+                && reference.iter().all(|x| set.contains(x))
+                && with_prefixes(probes).iter().all(|x| set.contains(x) == reference.contains(x))
+        }
+
+        fn iter(strings: Vec<String>) -> bool {
+            let reference = with_prefixes(strings);
+            let set: StringVecSet = reference.iter().collect();
+            set.iter().collect::<Vec<_>>() == reference.into_iter().collect::<Vec<_>>()
+        }
+
+        fn compact_roundtrip(strings: Vec<String>) -> bool {
+            let set: StringVecSet = with_prefixes(strings).iter().collect();
+            let bytes = set.to_compact_bytes();
+            StringVecSet::from_compact_bytes(&bytes) == Ok(set)
+        }
+
+        fn vec_set_roundtrip(strings: Vec<String>) -> bool {
+            let set: VecSet<[Arc<str>; 1]> = strings.iter().map(|x| Arc::from(x.as_str())).collect();
+            let compressed = StringVecSet::from(&set);
+            compressed.to_vec_set::<[Arc<str>; 1]>() == set
+        }
+    }
+
+    #[test]
+    fn unsorted_rejected() {
+        assert!(StringVecSet::from_sorted(["b", "a"]).is_none());
+        assert!(StringVecSet::from_sorted(["a", "a"]).is_none());
+        let set = StringVecSet::from_sorted(["a", "b"]).unwrap();
+        let mut bytes = set.to_compact_bytes();
+        // swap the two strings
+        let n = bytes.len();
+        bytes.swap(n - 1, n - 4);
+        assert_eq!(
+            StringVecSet::from_compact_bytes(&bytes),
+            Err(CompactError::InvalidValue)
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use crate::vec_map::{AbstractVecMap, VecMap};
+use crate::{
+    iterators::VecMapIter,
+    vec_map::{AbstractVecMap, VecMap},
+};
 use core::{
     borrow::Borrow,
     cmp,
@@ -58,13 +61,14 @@ impl<K: Clone, V: Clone, A: Array<Item = (K, V)>> Clone for TotalVecMap<V, A> {
 
 impl<K: Hash, V: Hash, A: Array<Item = (K, V)>> Hash for TotalVecMap<V, A> {
     fn hash<H: hash::Hasher>(&self, state: &mut H) {
-        self.0.hash(state)
+        self.0.hash(state);
+        self.1.hash(state);
     }
 }
 
 impl<K: PartialEq, V: PartialEq, A: Array<Item = (K, V)>> PartialEq for TotalVecMap<V, A> {
     fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+        self.0 == other.0 && self.1 == other.1
     }
 }
 
@@ -111,6 +115,23 @@ impl<K, V, A: Array<Item = (K, V)>> TotalVecMap<V, A> {
     pub fn non_default_mappings(&self) -> &VecMap<A> {
         &self.0
     }
+
+    /// The value for all keys that do not have an explicit mapping.
+    pub fn default_value(&self) -> &V {
+        &self.1
+    }
+
+    /// An iterator over the non-default mappings, sorted by key
+    pub fn iter_non_default(&self) -> VecMapIter<core::slice::Iter<'_, (K, V)>> {
+        self.0.iter()
+    }
+
+    /// Splits the map into the non-default mappings and the default value.
+    ///
+    /// This is the inverse of [new](TotalVecMap::new), so it can be used to persist a total map compactly.
+    pub fn into_parts(self) -> (VecMap<A>, V) {
+        (self.0, self.1)
+    }
 }
 
 impl<K: Debug, V: Debug, A: Array<Item = (K, V)>> Debug for TotalVecMap<V, A> {
@@ -228,12 +249,57 @@ impl<K: Ord + Clone, V: Eq, A: Array<Item = (K, V)>> TotalVecMap<V, A> {
 }
 
 impl<K: Ord + Clone, V: Ord + Clone, A: Array<Item = (K, V)>> TotalVecMap<V, A> {
+    /// Pointwise maximum of two total maps, the join of the pointwise order.
+    #[doc(alias = "join")]
     pub fn supremum(&self, that: &Self) -> Self {
         self.combine_ref(that, |a, b| cmp::max(a, b).clone())
     }
+    /// Pointwise minimum of two total maps, the meet of the pointwise order.
+    #[doc(alias = "meet")]
     pub fn infimum(&self, that: &Self) -> Self {
         self.combine_ref(that, |a, b| cmp::min(a, b).clone())
     }
+    /// Union keeping the larger value for each key, like [VecMap::union_max]. Same as
+    /// [supremum](TotalVecMap::supremum).
+    ///
+    /// Unlike the [VecMap] version, this returns a new map, like the other combinators of a total map.
+    pub fn union_max(&self, that: &Self) -> Self {
+        self.supremum(that)
+    }
+    /// Union keeping the smaller value for each key, like [VecMap::union_min]. Same as
+    /// [infimum](TotalVecMap::infimum).
+    ///
+    /// Unlike the [VecMap] version, this returns a new map, like the other combinators of a total map.
+    pub fn union_min(&self, that: &Self) -> Self {
+        self.infimum(that)
+    }
+}
+
+impl<K: Ord, V: Ord, A: Array<Item = (K, V)>> TotalVecMap<V, A> {
+    /// Compare two total maps pointwise, including the default values.
+    ///
+    /// Returns None if some keys map to a smaller and some keys map to a larger value, e.g. for concurrent
+    /// version vectors. Unlike [Ord], this is a partial order. Time complexity is O(N + M), without allocations.
+    pub fn pointwise_cmp(&self, that: &Self) -> Option<Ordering> {
+        pointwise_cmp(self.0.as_slice(), &self.1, that.0.as_slice(), &that.1)
+    }
+
+    /// true if every key maps to a value that is less than or equal to the value in `that`
+    ///
+    /// This is the pointwise order, unlike `<=`, which uses the lexicographic [Ord].
+    pub fn pointwise_le(&self, that: &Self) -> bool {
+        matches!(
+            self.pointwise_cmp(that),
+            Some(Ordering::Less | Ordering::Equal)
+        )
+    }
+
+    /// true if every key maps to a value that is greater than or equal to the value in `that`
+    ///
+    /// This is the pointwise order, unlike `>=`, which uses the lexicographic [Ord].
+    pub fn pointwise_ge(&self, that: &Self) -> bool {
+        that.pointwise_le(self)
+    }
 }
 
 impl<K: Clone, V: Eq, A: Array<Item = (K, V)>> TotalVecMap<V, A> {
@@ -256,6 +322,69 @@ impl<K: Clone, V: Eq, A: Array<Item = (K, V)>> TotalVecMap<V, A> {
             .collect();
         TotalVecMap(VecMap::new(elements), default)
     }
+
+    /// Keep only the non-default mappings for which the predicate returns true.
+    ///
+    /// All other keys will map to the default value.
+    pub fn filter<F: Fn(&K, &V) -> bool>(&self, f: F) -> Self
+    where
+        V: Clone,
+    {
+        let elements: smallvec::SmallVec<A> = self
+            .0
+            .slice_iter()
+            .filter(|(k, v)| f(k, v))
+            .cloned()
+            .collect();
+        TotalVecMap(VecMap::new(elements), self.1.clone())
+    }
+}
+
+/// Pointwise comparison of two total maps given as sorted non-default mappings and default values
+pub(crate) fn pointwise_cmp<K: Ord, V: Ord>(
+    mut a: &[(K, V)],
+    a_default: &V,
+    mut b: &[(K, V)],
+    b_default: &V,
+) -> Option<Ordering> {
+    let mut le = a_default <= b_default;
+    let mut ge = a_default >= b_default;
+    while le || ge {
+        let (v, w) = match (a.first(), b.first()) {
+            (Some((ka, va)), Some((kb, vb))) => match ka.cmp(kb) {
+                Ordering::Less => {
+                    a = &a[1..];
+                    (va, b_default)
+                }
+                Ordering::Greater => {
+                    b = &b[1..];
+                    (a_default, vb)
+                }
+                Ordering::Equal => {
+                    a = &a[1..];
+                    b = &b[1..];
+                    (va, vb)
+                }
+            },
+            (Some((_, va)), None) => {
+                a = &a[1..];
+                (va, b_default)
+            }
+            (None, Some((_, vb))) => {
+                b = &b[1..];
+                (a_default, vb)
+            }
+            (None, None) => break,
+        };
+        le &= v <= w;
+        ge &= v >= w;
+    }
+    match (le, ge) {
+        (true, true) => Some(Ordering::Equal),
+        (true, false) => Some(Ordering::Less),
+        (false, true) => Some(Ordering::Greater),
+        (false, false) => None,
+    }
 }
 
 impl<K: Ord + 'static, Q: ?Sized, V, A: Array<Item = (K, V)>> Index<&Q> for TotalVecMap<V, A>
@@ -324,12 +453,46 @@ mod tests {
             expected == actual
         }
 
+        fn parts_roundtrip(a: Ref) -> bool {
+            let x = from_ref(a.clone());
+            let (elements, default) = a;
+            let explicit = x.iter_non_default().all(|(k, v)| elements.get(k) == Some(v) && *v != default);
+            let (map, d) = x.clone().into_parts();
+            explicit && d == default && Test::new(map, d) == x
+        }
+
+        fn filter(a: Ref, key: i32) -> bool {
+            let x = from_ref(a);
+            let y = x.filter(|k, _| k % 2 == 0);
+            let expected = if key % 2 == 0 { x[&key] } else { *x.default_value() };
+            y[&key] == expected && y.default_value() == x.default_value()
+        }
+
+        fn map_values(a: Ref, key: i32) -> bool {
+            let x = from_ref(a);
+            let y: Test = x.map_values(|v| v % 3);
+            y[&key] == x[&key] % 3 && y.iter_non_default().all(|(_, v)| v != y.default_value())
+        }
+
         fn supremum(a: Ref, b: Ref) -> bool {
             let expected = from_ref(combine_reference(&a, &b, cmp::max));
             let a1 = from_ref(a);
             let b1 = from_ref(b);
             let actual = a1.supremum(&b1);
-            expected == actual
+            expected == actual && a1.union_max(&b1) == actual
+        }
+
+        fn pointwise_cmp(a: Ref, b: Ref) -> bool {
+            let pairs = combine_reference(&a, &b, |a, b| a.cmp(&b) as i32);
+            let values = || pairs.0.values().chain(Some(&pairs.1));
+            let le = values().all(|c| *c <= 0);
+            let ge = values().all(|c| *c >= 0);
+            let a1 = from_ref(a);
+            let b1 = from_ref(b);
+            a1.pointwise_le(&b1) == le
+                && a1.pointwise_ge(&b1) == ge
+                && a1.pointwise_le(&b1) == (a1.supremum(&b1) == b1)
+                && a1.pointwise_ge(&b1) == (a1.infimum(&b1) == b1)
         }
 
         fn infimum(a: Ref, b: Ref) -> bool {
@@ -337,7 +500,7 @@ mod tests {
             let a1 = from_ref(a);
             let b1 = from_ref(b);
             let actual = a1.infimum(&b1);
-            expected == actual
+            expected == actual && a1.union_min(&b1) == actual
         }
     }
 }
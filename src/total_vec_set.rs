@@ -1,4 +1,14 @@
-use crate::{AbstractVecSet, VecSet};
+#[cfg(feature = "rkyv")]
+use crate::ArchivedVecSet;
+#[cfg(feature = "rkyv_validated")]
+use crate::ArchivedVecSetError;
+use crate::{
+    merge_state::{CloneConverter, SmallVecMergeState},
+    vec_set::{relation_flags, SetCombineOp},
+    AbstractVecSet, SetRelation, VecSet,
+};
+#[cfg(feature = "rkyv_validated")]
+use bytecheck::CheckBytes;
 use core::{
     fmt,
     fmt::{Debug, Write},
@@ -6,6 +16,8 @@ use core::{
     mem,
     ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Sub, SubAssign},
 };
+#[cfg(feature = "rkyv_validated")]
+use rkyv::validation::ArchiveContext;
 #[cfg(feature = "serde")]
 use serde::{
     de::{Deserialize, Deserializer},
@@ -26,6 +38,17 @@ pub struct TotalVecSet<A: Array> {
 /// Type alias for a [TotalVecSet](struct.TotalVecSet) with up to 2 elements with inline storage.
 pub type TotalVecSet2<T> = TotalVecSet<[T; 2]>;
 
+/// The number of elements in a [TotalVecSet](struct.TotalVecSet)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Count {
+    /// A finite number of elements
+    Finite(usize),
+    /// The complement of a finite set
+    ///
+    /// The size of the universe of the element type is not known, so this is also used for e.g. negated sets of u8.
+    Infinite,
+}
+
 #[cfg(feature = "serde")]
 impl<A: Array> Serialize for TotalVecSet<A>
 where
@@ -47,6 +70,103 @@ where
     }
 }
 
+/// The archived form of a [TotalVecSet], that can be used directly from an rkyv archive
+#[cfg(feature = "rkyv")]
+#[repr(C)]
+pub struct ArchivedTotalVecSet<T> {
+    elements: ArchivedVecSet<T>,
+    negated: bool,
+}
+
+#[cfg(feature = "rkyv")]
+impl<T: Ord> ArchivedTotalVecSet<T> {
+    pub fn contains(&self, value: &T) -> bool {
+        self.negated ^ self.elements.contains(value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.negated && self.elements.is_empty()
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.negated && self.elements.is_empty()
+    }
+
+    /// The number of elements in the set, or [Count::Infinite] if the set is negated.
+    pub fn count(&self) -> Count {
+        if self.negated {
+            Count::Infinite
+        } else {
+            Count::Finite(self.elements.as_slice().len())
+        }
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<A> rkyv::Archive for TotalVecSet<A>
+where
+    A: Array,
+    A::Item: rkyv::Archive,
+{
+    type Archived = ArchivedTotalVecSet<<A::Item as rkyv::Archive>::Archived>;
+
+    type Resolver = <VecSet<A> as rkyv::Archive>::Resolver;
+
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = rkyv::out_field!(out.elements);
+        self.elements.resolve(pos + fp, resolver, fo);
+        let (fp, fo) = rkyv::out_field!(out.negated);
+        self.negated.resolve(pos + fp, (), fo);
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<S, T, A> rkyv::Serialize<S> for TotalVecSet<A>
+where
+    A: Array<Item = T>,
+    T: rkyv::Archive + rkyv::Serialize<S>,
+    S: rkyv::ser::ScratchSpace + rkyv::ser::Serializer,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        self.elements.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<D, T, A> rkyv::Deserialize<TotalVecSet<A>, D> for ArchivedTotalVecSet<T::Archived>
+where
+    A: Array<Item = T>,
+    T: rkyv::Archive,
+    D: rkyv::Fallible + ?Sized,
+    [<<A as Array>::Item as rkyv::Archive>::Archived]:
+        rkyv::DeserializeUnsized<[<A as Array>::Item], D>,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<TotalVecSet<A>, D::Error> {
+        let elements: VecSet<A> = self.elements.deserialize(deserializer)?;
+        Ok(TotalVecSet::new(elements, self.negated))
+    }
+}
+
+#[cfg(feature = "rkyv_validated")]
+impl<C: ?Sized, T> bytecheck::CheckBytes<C> for ArchivedTotalVecSet<T>
+where
+    C: ArchiveContext,
+    C::Error: std::error::Error,
+    T: Ord + CheckBytes<C>,
+    bool: bytecheck::CheckBytes<C>,
+{
+    type Error = ArchivedVecSetError;
+    unsafe fn check_bytes<'a>(
+        value: *const Self,
+        context: &mut C,
+    ) -> Result<&'a Self, Self::Error> {
+        ArchivedVecSet::check_bytes(core::ptr::addr_of!((*value).elements), context)?;
+        bool::check_bytes(core::ptr::addr_of!((*value).negated), context)
+            .map_err(|e| ArchivedVecSetError::ValueCheckError(e.to_string()))?;
+        Ok(&*value)
+    }
+}
+
 impl<T: Clone, A: Array<Item = T>> Clone for TotalVecSet<A> {
     fn clone(&self) -> Self {
         Self {
@@ -105,9 +225,30 @@ impl<T, A: Array<Item = T>> TotalVecSet<A> {
         true.into()
     }
 
+    /// Creates a set from the explicit elements and a flag whether the set is negated.
+    ///
+    /// If negated is true, the set contains everything except the elements.
+    pub fn from_parts(elements: VecSet<A>, negated: bool) -> Self {
+        Self::new(elements, negated)
+    }
+
+    /// Splits the set into the explicit elements and a flag whether the set is negated.
+    pub fn into_parts(self) -> (VecSet<A>, bool) {
+        (self.elements, self.negated)
+    }
+
     pub fn shrink_to_fit(&mut self) {
         self.elements.shrink_to_fit()
     }
+
+    /// The number of elements in the set, or [Count::Infinite] if the set is negated.
+    pub fn count(&self) -> Count {
+        if self.negated {
+            Count::Infinite
+        } else {
+            Count::Finite(self.elements.len())
+        }
+    }
 }
 
 impl<T, A: Array<Item = T>> From<bool> for TotalVecSet<A> {
@@ -148,6 +289,26 @@ impl<T: Ord, A: Array<Item = T>> TotalVecSet<A> {
         }
     }
 
+    /// The relation of this set to another set, computed in a single pass.
+    ///
+    /// Negated sets are considered to be infinite, like for [count](TotalVecSet::count). If more than one
+    /// relation applies, the first one in the order of the variants of [SetRelation] is returned.
+    pub fn relation(&self, that: &Self) -> SetRelation {
+        let (a_only, b_only, both) =
+            relation_flags(self.elements.as_slice(), that.elements.as_slice());
+        match (self.negated, that.negated) {
+            (false, false) => SetRelation::new(a_only, b_only, both),
+            // a is a subset of !b if a and b have no common elements, and disjoint from !b if a is a subset of b
+            (false, true) if !both => SetRelation::Subset,
+            (false, true) if !a_only => SetRelation::Disjoint,
+            (true, false) if !both => SetRelation::Superset,
+            (true, false) if !b_only => SetRelation::Disjoint,
+            // complements have the inverse subset relation, and are never disjoint
+            (true, true) => SetRelation::new(b_only, a_only, true),
+            _ => SetRelation::Overlapping,
+        }
+    }
+
     pub fn is_disjoint(&self, that: &Self) -> bool {
         match (self.negated, that.negated) {
             (false, false) => self.elements.is_disjoint(&that.elements),
@@ -156,6 +317,28 @@ impl<T: Ord, A: Array<Item = T>> TotalVecSet<A> {
             (true, true) => false,
         }
     }
+
+    /// true if this set contains all of the given values.
+    ///
+    /// The values must be strictly sorted. This is done in a single merge with the explicit elements.
+    pub fn contains_all(&self, sorted: &[T]) -> bool {
+        if self.negated {
+            !self.elements.contains_any(sorted)
+        } else {
+            self.elements.contains_all(sorted)
+        }
+    }
+
+    /// true if this set contains any of the given values.
+    ///
+    /// The values must be strictly sorted. This is done in a single merge with the explicit elements.
+    pub fn contains_any(&self, sorted: &[T]) -> bool {
+        if self.negated {
+            !self.elements.contains_all(sorted)
+        } else {
+            self.elements.contains_any(sorted)
+        }
+    }
 }
 
 impl<T: Ord + Clone, A: Array<Item = T>> TotalVecSet<A> {
@@ -166,6 +349,70 @@ impl<T: Ord + Clone, A: Array<Item = T>> TotalVecSet<A> {
             self.elements.remove(that);
         }
     }
+
+    /// Combine this set with another set using an arbitrary pointwise boolean function, in a single merge pass.
+    ///
+    /// An element is in the result if `f` applied to its membership in both sets is true. This covers the
+    /// operators, but also e.g. implication or nand.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::{total_vec_set::TotalVecSet, VecSet};
+    /// let a: TotalVecSet<[u32; 4]> = vec![1, 2].into_iter().collect::<VecSet<_>>().into();
+    /// let b: TotalVecSet<[u32; 4]> = vec![2, 3].into_iter().collect::<VecSet<_>>().into();
+    /// // a implies b
+    /// let implication = a.combine(&b, |a, b| !a | b);
+    /// assert!(!implication.contains(&1));
+    /// assert!(implication.contains(&2) && implication.contains(&3) && implication.contains(&4));
+    /// ```
+    pub fn combine(&self, that: &Self, f: impl Fn(bool, bool) -> bool) -> Self {
+        // membership of elements that are in neither set of explicit elements
+        let negated = f(self.negated, that.negated);
+        let op = SetCombineOp {
+            a_only: f(!self.negated, that.negated) != negated,
+            b_only: f(self.negated, !that.negated) != negated,
+            both: f(!self.negated, !that.negated) != negated,
+        };
+        let elements = SmallVecMergeState::merge(
+            self.elements.as_slice(),
+            that.elements.as_slice(),
+            op,
+            CloneConverter,
+        );
+        Self::new(VecSet::new_unsafe(elements), negated)
+    }
+
+    /// The intersection of any number of sets.
+    ///
+    /// The non-negated sets are intersected in order of increasing size using [VecSet::intersect_all], then the
+    /// elements of the negated sets are removed. This stops as soon as the intersection is empty.
+    ///
+    /// The intersection of zero sets is the set of all elements.
+    pub fn intersect_all<'a, I>(sets: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Self>,
+        A: 'a,
+    {
+        let (negated, positive): (Vec<&Self>, Vec<&Self>) =
+            sets.into_iter().partition(|set| set.negated);
+        if positive.is_empty() {
+            // the complement of the union of the negated elements
+            let mut elements = VecSet::empty();
+            for set in negated {
+                elements.union_with(&set.elements);
+            }
+            return Self::new(elements, true);
+        }
+        let mut elements: VecSet<A> =
+            VecSet::intersect_all(positive.iter().map(|set| &set.elements));
+        for set in negated {
+            if elements.is_empty() {
+                break;
+            }
+            elements.difference_with(&set.elements);
+        }
+        Self::new(elements, false)
+    }
 }
 
 impl<T: Ord + Clone, A: Array<Item = T>> BitAnd for &TotalVecSet<A> {
@@ -431,6 +678,67 @@ mod tests {
         fn diff_sample(a: Test, b: Test) -> bool {
             binary_op(&a, &b, &(&a - &b), |a, b| a & !b)
         }
+
+        fn combine_sample(a: Test, b: Test, table: u8) -> bool {
+            let f = |a: bool, b: bool| table & (1 << (a as u8 * 2 + b as u8)) != 0;
+            binary_op(&a, &b, &a.combine(&b, f), f)
+        }
+
+        fn intersect_all_sample(sets: Vec<Test>) -> bool {
+            let expected = sets.iter().fold(Test::all(), |a, b| &a & b);
+            TotalVecSet::intersect_all(&sets) == expected
+        }
+
+        fn relation_sample(a: Test, b: Test) -> bool {
+            let expected = if a == b {
+                SetRelation::Equal
+            } else if a.is_subset(&b) {
+                SetRelation::Subset
+            } else if b.is_subset(&a) {
+                SetRelation::Superset
+            } else if a.is_disjoint(&b) {
+                SetRelation::Disjoint
+            } else {
+                SetRelation::Overlapping
+            };
+            a.relation(&b) == expected
+        }
+
+        fn parts_roundtrip(a: Test) -> bool {
+            let (elements, negated) = a.clone().into_parts();
+            Test::from_parts(elements, negated) == a
+        }
+
+        fn contains_all_any_sample(a: Test, b: Test) -> bool {
+            let probes = b.elements.as_slice();
+            a.contains_all(probes) == probes.iter().all(|x| a.contains(x))
+                && a.contains_any(probes) == probes.iter().any(|x| a.contains(x))
+        }
+
+        fn count_sample(a: Test) -> bool {
+            let expected = if a.negated {
+                Count::Infinite
+            } else {
+                Count::Finite(a.elements.iter().count())
+            };
+            let count = a.count();
+            count == expected && (!a).count() != count
+        }
+
+        #[cfg(feature = "rkyv_validated")]
+        fn rkyv_roundtrip_validated(a: Test, probes: Vec<i64>) -> bool {
+            use rkyv::*;
+            use ser::Serializer;
+            let mut serializer = ser::serializers::AllocSerializer::<256>::default();
+            serializer.serialize_value(&a).unwrap();
+            let bytes = serializer.into_serializer().into_inner();
+            let archived = rkyv::check_archived_root::<Test>(&bytes).unwrap();
+            let deserialized: Test = archived.deserialize(&mut Infallible).unwrap();
+            a == deserialized
+                && archived.count() == a.count()
+                && archived.is_empty() == a.is_empty()
+                && probes.iter().all(|x| archived.contains(x) == a.contains(x))
+        }
     }
 
     bitop_assign_consistent!(Test);
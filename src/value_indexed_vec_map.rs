@@ -0,0 +1,239 @@
+use crate::{VecMap, VecSet};
+use core::{
+    fmt,
+    iter::FromIterator,
+    ops::{Bound, RangeBounds},
+};
+use smallvec::Array;
+
+/// A [VecMap] together with a secondary index that is sorted by value.
+///
+/// The index is a [VecSet] of `(value, key)` pairs, so mappings can be queried by a range of values.
+/// All modifications go through this wrapper, which keeps the map and the index in sync.
+///
+/// Keys and values are stored twice, so this is best suited for small, cheaply cloneable keys and values.
+///
+/// # Example
+/// ```
+/// use vec_collections::ValueIndexedVecMap;
+/// let mut scores: ValueIndexedVecMap<[(&str, u32); 4], [(u32, &str); 4]> =
+///     vec![("a", 10), ("b", 30), ("c", 20)].into_iter().collect();
+/// scores.update(&"a", |score| *score += 30);
+/// let top: Vec<&str> = scores.value_range(25..).iter().map(|(_, name)| *name).collect();
+/// assert_eq!(top, vec!["b", "a"]);
+/// ```
+pub struct ValueIndexedVecMap<A: Array, B: Array> {
+    map: VecMap<A>,
+    /// the same mappings as `map`, as (value, key) pairs
+    index: VecSet<B>,
+}
+
+impl<A: Array, B: Array> ValueIndexedVecMap<A, B> {
+    /// An empty map
+    pub fn empty() -> Self {
+        Self {
+            map: VecMap::empty(),
+            index: VecSet::empty(),
+        }
+    }
+
+    /// number of mappings
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// true if there are no mappings
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The mappings, sorted by key
+    pub fn as_map(&self) -> &VecMap<A> {
+        &self.map
+    }
+
+    /// The mappings as (value, key) pairs, sorted by value
+    pub fn as_index(&self) -> &VecSet<B> {
+        &self.index
+    }
+
+    /// Returns the map and the value index
+    pub fn into_inner(self) -> (VecMap<A>, VecSet<B>) {
+        (self.map, self.index)
+    }
+}
+
+impl<K, V, A, B> ValueIndexedVecMap<A, B>
+where
+    K: Ord + Clone + 'static,
+    V: Ord + Clone,
+    A: Array<Item = (K, V)>,
+    B: Array<Item = (V, K)>,
+{
+    /// lookup of a mapping by key. Time complexity is O(log N).
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.map.get(key)
+    }
+
+    /// Insert a mapping, returning the previous value for the key, if any.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self
+            .map
+            .insert(key.clone(), value.clone())
+            .map(|old| self.unindex(old, key.clone()));
+        self.index.insert((value, key));
+        old
+    }
+
+    /// Remove the mapping for a key, returning the value if it was present.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let old = self.map.remove(key)?;
+        Some(self.unindex(old, key.clone()))
+    }
+
+    /// Modify the value for a key in place, and move the entry of the value index accordingly.
+    ///
+    /// Returns false if there is no mapping for the key.
+    pub fn update(&mut self, key: &K, f: impl FnOnce(&mut V)) -> bool {
+        let value = match self.map.get_mut(key) {
+            Some(value) => value,
+            None => return false,
+        };
+        let old = value.clone();
+        f(value);
+        if *value != old {
+            let value = value.clone();
+            self.unindex(old, key.clone());
+            self.index.insert((value, key.clone()));
+        }
+        true
+    }
+
+    /// All mappings with a value in the given range, as (value, key) pairs sorted by value.
+    ///
+    /// This is a binary search in the value index, so the time complexity is O(log N).
+    pub fn value_range(&self, range: impl RangeBounds<V>) -> &[(V, K)] {
+        let elements = self.index.as_slice();
+        let start = match range.start_bound() {
+            Bound::Included(v) => elements.partition_point(|(x, _)| x < v),
+            Bound::Excluded(v) => elements.partition_point(|(x, _)| x <= v),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(v) => elements.partition_point(|(x, _)| x <= v),
+            Bound::Excluded(v) => elements.partition_point(|(x, _)| x < v),
+            Bound::Unbounded => elements.len(),
+        };
+        &elements[start..end.max(start)]
+    }
+
+    /// Remove a mapping from the value index, returning the value
+    fn unindex(&mut self, value: V, key: K) -> V {
+        let entry = (value, key);
+        self.index.remove(&entry);
+        entry.0
+    }
+}
+
+impl<K, V, A, B> FromIterator<(K, V)> for ValueIndexedVecMap<A, B>
+where
+    K: Ord + Clone + 'static,
+    V: Ord + Clone,
+    A: Array<Item = (K, V)>,
+    B: Array<Item = (V, K)>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        VecMap::from_iter(iter).into()
+    }
+}
+
+impl<K, V, A, B> From<VecMap<A>> for ValueIndexedVecMap<A, B>
+where
+    K: Ord + Clone + 'static,
+    V: Ord + Clone,
+    A: Array<Item = (K, V)>,
+    B: Array<Item = (V, K)>,
+{
+    fn from(map: VecMap<A>) -> Self {
+        let index = map.iter().map(|(k, v)| (v.clone(), k.clone())).collect();
+        Self { map, index }
+    }
+}
+
+impl<A: Array, B: Array> Default for ValueIndexedVecMap<A, B> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<A: Array, B: Array> Clone for ValueIndexedVecMap<A, B>
+where
+    A::Item: Clone,
+    B::Item: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            index: self.index.clone(),
+        }
+    }
+}
+
+impl<A: Array, B: Array> fmt::Debug for ValueIndexedVecMap<A, B>
+where
+    A::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+    use std::collections::BTreeMap;
+
+    type Test = ValueIndexedVecMap<[(u8, i8); 2], [(i8, u8); 2]>;
+
+    fn in_sync(map: &Test) -> bool {
+        let expected: VecSet<[(i8, u8); 2]> = map.as_map().iter().map(|(k, v)| (*v, *k)).collect();
+        map.as_index() == &expected
+    }
+
+    quickcheck! {
+        fn insert_remove_update(ops: Vec<(u8, u8, i8)>) -> bool {
+            let mut map = Test::empty();
+            let mut reference = BTreeMap::new();
+            for (op, key, value) in ops {
+                let (actual, expected) = match op % 3 {
+                    0 => (map.insert(key, value), reference.insert(key, value)),
+                    1 => (map.remove(&key), reference.remove(&key)),
+                    _ => {
+                        let updated = map.update(&key, |v| *v = v.wrapping_add(value));
+                        let expected = reference.get_mut(&key).map(|v| {
+                            *v = v.wrapping_add(value);
+                            *v
+                        });
+                        (updated.then(|| *map.get(&key).unwrap()), expected)
+                    }
+                };
+                if actual != expected || !in_sync(&map) {
+                    return false;
+                }
+            }
+            map.as_map().iter().map(|(k, v)| (*k, *v)).eq(reference.into_iter())
+        }
+
+        fn value_range(entries: BTreeMap<u8, i8>, a: i8, b: i8) -> bool {
+            let map: Test = entries.clone().into_iter().collect();
+            let mut expected: Vec<(i8, u8)> = entries
+                .into_iter()
+                .filter(|(_, v)| a <= *v && *v < b)
+                .map(|(k, v)| (v, k))
+                .collect();
+            expected.sort();
+            in_sync(&map) && map.value_range(a..b) == &expected[..]
+        }
+    }
+}
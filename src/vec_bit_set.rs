@@ -0,0 +1,542 @@
+use crate::{iterators::VecSetIter, VecSet};
+use core::{
+    fmt,
+    hash::Hash,
+    iter::FromIterator,
+    marker::PhantomData,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Sub, SubAssign},
+};
+use smallvec::{Array, SmallVec};
+
+/// An element type for a [VecBitSet], with a small universe that can be mapped to bit indices.
+///
+/// This is implemented for u8 and u16. For larger types, a bitmap would be too large.
+pub trait BitSetElement: Ord + Copy {
+    /// The bit index of the element. Must be order preserving.
+    fn to_index(self) -> usize;
+    /// Inverse of [to_index](BitSetElement::to_index).
+    fn from_index(index: usize) -> Self;
+}
+
+impl BitSetElement for u8 {
+    fn to_index(self) -> usize {
+        self as usize
+    }
+    fn from_index(index: usize) -> Self {
+        index as u8
+    }
+}
+
+impl BitSetElement for u16 {
+    fn to_index(self) -> usize {
+        self as usize
+    }
+    fn from_index(index: usize) -> Self {
+        index as u16
+    }
+}
+
+/// A set of small integers backed by a bitmap.
+///
+/// For dense sets of u8 or u16 elements, this is more compact and faster than a [VecSet], and can be converted from
+/// and to a [VecSet].
+///
+/// These methods mirror the [VecSet] methods of the same name: `empty`, `single`, `len`, `is_empty`, `contains`,
+/// `insert`, `remove`, `retain`, `iter`, `is_disjoint`, `is_subset`, `is_superset`, `union`, `intersection`,
+/// `difference`, `symmetric_difference` and the corresponding `*_with` methods. So do the set operators and
+/// [FromIterator] and [Extend]. Code that only uses these on a concrete type can switch the representation by
+/// changing the type, except that `iter` yields elements by value. There is no common trait, since
+/// [AbstractVecSet] requires the elements as a sorted slice, so generic code written against [AbstractVecSet]
+/// can not use this.
+///
+/// The bitmap is stored in a [SmallVec] of u64 words, without trailing zero words. So a set of u8 never allocates.
+///
+/// # Example
+/// ```
+/// use vec_collections::{VecBitSet, VecSet};
+/// let a: VecBitSet<u8> = (0..100).collect();
+/// let b: VecBitSet<u8> = (50..150).collect();
+/// let c = &a & &b;
+/// assert_eq!(c.len(), 50);
+/// let d: VecSet<[u8; 4]> = c.into();
+/// assert_eq!(d, (50..100).collect::<Vec<_>>());
+/// ```
+///
+/// [VecSet]: struct.VecSet.html
+/// [AbstractVecSet]: trait.AbstractVecSet.html
+/// [SmallVec]: https://docs.rs/smallvec/1.4.1/smallvec/struct.SmallVec.html
+pub struct VecBitSet<T> {
+    words: SmallVec<[u64; 4]>,
+    _t: PhantomData<T>,
+}
+
+impl<T> VecBitSet<T> {
+    fn new(words: SmallVec<[u64; 4]>) -> Self {
+        let mut res = Self {
+            words,
+            _t: PhantomData,
+        };
+        res.trim();
+        res
+    }
+
+    /// remove trailing zero words, to keep the representation canonical
+    fn trim(&mut self) {
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+    }
+
+    /// The empty set.
+    pub fn empty() -> Self {
+        Self::new(SmallVec::new())
+    }
+
+    /// The number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// true if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The underlying bitmap as a slice of words, without trailing zero words.
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Shrink the underlying SmallVec to fit.
+    pub fn shrink_to_fit(&mut self) {
+        self.words.shrink_to_fit()
+    }
+
+    fn combine(&self, that: &Self, f: impl Fn(u64, u64) -> u64) -> Self {
+        let n = self.words.len().max(that.words.len());
+        let word = |w: &[u64], i: usize| w.get(i).cloned().unwrap_or_default();
+        Self::new(
+            (0..n)
+                .map(|i| f(word(&self.words, i), word(&that.words, i)))
+                .collect(),
+        )
+    }
+
+    fn combine_with(&mut self, that: &Self, f: impl Fn(u64, u64) -> u64) {
+        if self.words.len() < that.words.len() {
+            self.words.resize(that.words.len(), 0);
+        }
+        for (i, a) in self.words.iter_mut().enumerate() {
+            *a = f(*a, that.words.get(i).cloned().unwrap_or_default());
+        }
+        self.trim();
+    }
+
+    /// true if this set has no common elements with another set.
+    pub fn is_disjoint(&self, that: &Self) -> bool {
+        self.words
+            .iter()
+            .zip(that.words.iter())
+            .all(|(a, b)| a & b == 0)
+    }
+
+    /// true if this set is a subset of another set.
+    ///
+    /// A set is considered to be a subset of itself.
+    pub fn is_subset(&self, that: &Self) -> bool {
+        self.words.len() <= that.words.len()
+            && self
+                .words
+                .iter()
+                .zip(that.words.iter())
+                .all(|(a, b)| a & !b == 0)
+    }
+
+    /// true if this set is a superset of another set.
+    ///
+    /// A set is considered to be a superset of itself.
+    pub fn is_superset(&self, that: &Self) -> bool {
+        that.is_subset(self)
+    }
+
+    pub fn union(&self, that: &Self) -> Self {
+        self.combine(that, |a, b| a | b)
+    }
+
+    pub fn intersection(&self, that: &Self) -> Self {
+        self.combine(that, |a, b| a & b)
+    }
+
+    pub fn symmetric_difference(&self, that: &Self) -> Self {
+        self.combine(that, |a, b| a ^ b)
+    }
+
+    pub fn difference(&self, that: &Self) -> Self {
+        self.combine(that, |a, b| a & !b)
+    }
+
+    pub fn union_with(&mut self, that: &Self) {
+        self.combine_with(that, |a, b| a | b)
+    }
+
+    pub fn intersection_with(&mut self, that: &Self) {
+        self.combine_with(that, |a, b| a & b)
+    }
+
+    pub fn xor_with(&mut self, that: &Self) {
+        self.combine_with(that, |a, b| a ^ b)
+    }
+
+    /// In place symmetric difference with another set. Same as [xor_with](VecBitSet::xor_with).
+    pub fn symmetric_difference_with(&mut self, that: &Self) {
+        self.xor_with(that)
+    }
+
+    pub fn difference_with(&mut self, that: &Self) {
+        self.combine_with(that, |a, b| a & !b)
+    }
+}
+
+impl<T: BitSetElement> VecBitSet<T> {
+    /// A set with a single element.
+    pub fn single(value: T) -> Self {
+        let mut res = Self::empty();
+        res.insert(value);
+        res
+    }
+
+    /// true if the set contains the element. Time complexity is O(1).
+    pub fn contains(&self, value: &T) -> bool {
+        let index = value.to_index();
+        self.words
+            .get(index / 64)
+            .map(|w| w & (1 << (index % 64)) != 0)
+            .unwrap_or_default()
+    }
+
+    /// insert an element. Returns true if the element was not present before.
+    pub fn insert(&mut self, value: T) -> bool {
+        let index = value.to_index();
+        let (word, mask) = (index / 64, 1 << (index % 64));
+        if self.words.len() <= word {
+            self.words.resize(word + 1, 0);
+        }
+        let res = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        res
+    }
+
+    /// remove an element. Returns true if the element was present before.
+    pub fn remove(&mut self, value: &T) -> bool {
+        let index = value.to_index();
+        let (word, mask) = (index / 64, 1 << (index % 64));
+        let res = match self.words.get_mut(word) {
+            Some(w) if *w & mask != 0 => {
+                *w &= !mask;
+                true
+            }
+            _ => false,
+        };
+        self.trim();
+        res
+    }
+
+    /// Retain all elements matching a predicate.
+    ///
+    /// Returns the number of removed elements.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> usize {
+        let mut removed = 0;
+        for (i, word) in self.words.iter_mut().enumerate() {
+            let mut bits = *word;
+            while bits != 0 {
+                let bit = bits.trailing_zeros() as usize;
+                // clear the lowest set bit
+                bits &= bits - 1;
+                if !f(&T::from_index(i * 64 + bit)) {
+                    *word &= !(1 << bit);
+                    removed += 1;
+                }
+            }
+        }
+        self.trim();
+        removed
+    }
+
+    /// An iterator that returns the elements of this set in sorted order
+    pub fn iter(&self) -> VecSetIter<VecBitSetIter<'_, T>> {
+        VecSetIter::new(VecBitSetIter {
+            words: &self.words,
+            offset: 0,
+            current: self.words.first().cloned().unwrap_or_default(),
+            _t: PhantomData,
+        })
+    }
+
+    /// Convert into a [VecSet](struct.VecSet.html).
+    pub fn to_vec_set<A: Array<Item = T>>(&self) -> VecSet<A> {
+        let mut res: SmallVec<A> = SmallVec::with_capacity(self.len());
+        res.extend(self.iter());
+        VecSet::new_unsafe(res)
+    }
+}
+
+/// Iterator over the elements of a [VecBitSet]
+pub struct VecBitSetIter<'a, T> {
+    words: &'a [u64],
+    offset: usize,
+    current: u64,
+    _t: PhantomData<T>,
+}
+
+impl<'a, T: BitSetElement> Iterator for VecBitSetIter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        while self.current == 0 {
+            self.offset += 1;
+            self.current = *self.words.get(self.offset)?;
+        }
+        let bit = self.current.trailing_zeros() as usize;
+        // clear the lowest set bit
+        self.current &= self.current - 1;
+        Some(T::from_index(self.offset * 64 + bit))
+    }
+}
+
+impl<T> Default for VecBitSet<T> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T> Clone for VecBitSet<T> {
+    fn clone(&self) -> Self {
+        Self {
+            words: self.words.clone(),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for VecBitSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.words == other.words
+    }
+}
+
+impl<T> Eq for VecBitSet<T> {}
+
+impl<T> Hash for VecBitSet<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.words.hash(state)
+    }
+}
+
+impl<T: BitSetElement + fmt::Debug> fmt::Debug for VecBitSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: BitSetElement> FromIterator<T> for VecBitSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut res = Self::empty();
+        res.extend(iter);
+        res
+    }
+}
+
+impl<T: BitSetElement> Extend<T> for VecBitSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for x in iter {
+            self.insert(x);
+        }
+    }
+}
+
+impl<T: BitSetElement, A: Array<Item = T>> From<&VecSet<A>> for VecBitSet<T> {
+    fn from(value: &VecSet<A>) -> Self {
+        value.iter().cloned().collect()
+    }
+}
+
+impl<T: BitSetElement, A: Array<Item = T>> From<VecSet<A>> for VecBitSet<T> {
+    fn from(value: VecSet<A>) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl<T: BitSetElement, A: Array<Item = T>> From<VecBitSet<T>> for VecSet<A> {
+    fn from(value: VecBitSet<T>) -> Self {
+        value.to_vec_set()
+    }
+}
+
+impl<T> BitAnd for &VecBitSet<T> {
+    type Output = VecBitSet<T>;
+    fn bitand(self, that: Self) -> Self::Output {
+        self.intersection(that)
+    }
+}
+
+impl<T> BitOr for &VecBitSet<T> {
+    type Output = VecBitSet<T>;
+    fn bitor(self, that: Self) -> Self::Output {
+        self.union(that)
+    }
+}
+
+impl<T> BitXor for &VecBitSet<T> {
+    type Output = VecBitSet<T>;
+    fn bitxor(self, that: Self) -> Self::Output {
+        self.symmetric_difference(that)
+    }
+}
+
+impl<T> Sub for &VecBitSet<T> {
+    type Output = VecBitSet<T>;
+    fn sub(self, that: Self) -> Self::Output {
+        self.difference(that)
+    }
+}
+
+impl<T> BitAndAssign for VecBitSet<T> {
+    fn bitand_assign(&mut self, that: Self) {
+        self.intersection_with(&that)
+    }
+}
+
+impl<T> BitOrAssign for VecBitSet<T> {
+    fn bitor_assign(&mut self, that: Self) {
+        self.union_with(&that)
+    }
+}
+
+impl<T> BitXorAssign for VecBitSet<T> {
+    fn bitxor_assign(&mut self, that: Self) {
+        self.xor_with(&that)
+    }
+}
+
+impl<T> SubAssign for VecBitSet<T> {
+    fn sub_assign(&mut self, that: Self) {
+        self.difference_with(&that)
+    }
+}
+
+impl<T> BitAndAssign<&VecBitSet<T>> for VecBitSet<T> {
+    fn bitand_assign(&mut self, that: &Self) {
+        self.intersection_with(that)
+    }
+}
+
+impl<T> BitOrAssign<&VecBitSet<T>> for VecBitSet<T> {
+    fn bitor_assign(&mut self, that: &Self) {
+        self.union_with(that)
+    }
+}
+
+impl<T> BitXorAssign<&VecBitSet<T>> for VecBitSet<T> {
+    fn bitxor_assign(&mut self, that: &Self) {
+        self.xor_with(that)
+    }
+}
+
+impl<T> SubAssign<&VecBitSet<T>> for VecBitSet<T> {
+    fn sub_assign(&mut self, that: &Self) {
+        self.difference_with(that)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use obey::*;
+    use quickcheck::*;
+    use std::collections::BTreeSet;
+
+    impl<T: BitSetElement + Arbitrary> Arbitrary for VecBitSet<T> {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let elements: Vec<T> = Arbitrary::arbitrary(g);
+            elements.into_iter().collect()
+        }
+    }
+
+    impl TestSamples<u16, bool> for VecBitSet<u16> {
+        fn samples(&self, res: &mut BTreeSet<u16>) {
+            res.insert(u16::MIN);
+            for x in self.iter() {
+                res.insert(x.saturating_sub(1));
+                res.insert(x);
+                res.insert(x.saturating_add(1));
+            }
+            res.insert(u16::MAX);
+        }
+
+        fn at(&self, elem: u16) -> bool {
+            self.contains(&elem)
+        }
+    }
+
+    type Test = VecBitSet<u16>;
+    type Reference = BTreeSet<u16>;
+
+    quickcheck! {
+        fn is_disjoint_sample(a: Test, b: Test) -> bool {
+            binary_property_test(&a, &b, a.is_disjoint(&b), |a, b| !(a & b))
+        }
+
+        fn is_subset_sample(a: Test, b: Test) -> bool {
+            binary_property_test(&a, &b, a.is_subset(&b), |a, b| !a | b)
+        }
+
+        fn union_sample(a: Test, b: Test) -> bool {
+            binary_element_test(&a, &b, &a | &b, |a, b| a | b)
+        }
+
+        fn intersection_sample(a: Test, b: Test) -> bool {
+            binary_element_test(&a, &b, &a & &b, |a, b| a & b)
+        }
+
+        fn xor_sample(a: Test, b: Test) -> bool {
+            binary_element_test(&a, &b, &a ^ &b, |a, b| a ^ b)
+        }
+
+        fn diff_sample(a: Test, b: Test) -> bool {
+            binary_element_test(&a, &b, &a - &b, |a, b| a & !b)
+        }
+
+        fn iter_sorted(a: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            a1.len() == a.len() && a1.iter().eq(a.iter().cloned())
+        }
+
+        fn vec_set_roundtrip(a: Reference) -> bool {
+            let a1: VecSet<[u16; 2]> = a.iter().cloned().collect();
+            let b: Test = a1.clone().into();
+            let a2: VecSet<[u16; 2]> = b.into();
+            a1 == a2
+        }
+
+        fn remove(a: Reference, b: Reference) -> bool {
+            let mut a1: Test = a.iter().cloned().collect();
+            for x in b.iter() {
+                a1.remove(x);
+            }
+            let expected: Test = a.difference(&b).cloned().collect();
+            a1 == expected
+        }
+
+        fn retain(a: Reference, b: Reference) -> bool {
+            let mut a1: Test = a.iter().cloned().collect();
+            let removed = a1.retain(|x| !b.contains(x));
+            let expected: Test = a.difference(&b).cloned().collect();
+            a1 == expected && a1.as_words().last() != Some(&0) && removed == a.intersection(&b).count()
+        }
+    }
+
+    bitop_assign_consistent!(Test);
+    set_predicate_consistent!(Test);
+    bitop_symmetry!(Test);
+    bitop_empty!(Test);
+}
@@ -3,20 +3,35 @@
 use crate::iterators::SliceIterator;
 use crate::{
     dedup::{sort_dedup_by_key, Keep},
-    merge_state::{InPlaceSmallVecMergeStateRef, MergeStateMut, NoConverter, SmallVecMergeState},
-    VecSet,
+    in_place::cast_storage,
+    merge_state::{InPlaceSmallVecMergeStateRef, NoConverter, SmallVecMergeState},
+    vec_set::{check_sorted, first_unsorted, search_by_key, NotSortedError},
+    AbstractVecSet, CapacityPolicy, TransformInPlace, VecSet,
 };
 use crate::{iterators::VecMapIter, merge_state::InPlaceMergeState};
 use binary_merge::MergeOperation;
 #[cfg(feature = "rkyv_validated")]
 use bytecheck::CheckBytes;
-use core::{borrow::Borrow, cmp::Ordering, fmt, fmt::Debug, hash, hash::Hash, iter::FromIterator};
-#[cfg(feature = "rkyv")]
-use rkyv::{validation::ArchiveContext, Archive};
-use smallvec::{Array, SmallVec};
-use std::collections::BTreeMap;
+use core::{
+    borrow::Borrow,
+    cmp::{self, Ordering},
+    fmt,
+    fmt::Debug,
+    hash,
+    hash::Hash,
+    iter::FromIterator,
+    ops::{BitAnd, BitAndAssign, Deref, Sub, SubAssign},
+};
+#[cfg(feature = "rkyv_validated")]
+use rkyv::validation::ArchiveContext;
+use smallvec::{Array, CollectionAllocErr, SmallVec};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+};
 #[cfg(feature = "serde")]
 use {
+    crate::vec_set::RawElements,
     core::marker::PhantomData,
     serde::{
         de::{Deserialize, Deserializer, MapAccess, Visitor},
@@ -24,9 +39,16 @@ use {
     },
 };
 
+/// The entries, after checking the invariants if the `debug_invariants` feature is enabled in a debug build
+#[inline]
+fn checked<K: Ord, V>(entries: &[(K, V)]) -> &[(K, V)] {
+    check_sorted(entries, |(k, _)| k);
+    entries
+}
+
 /// An abstract vec map
 ///
-/// this is implemented by VecMap and ArchivedVecMap, so they are interoperable.
+/// this is implemented by VecMap, ArchivedVecMap and slices sorted by key, so they are interoperable.
 pub trait AbstractVecMap<K, V> {
     fn as_slice(&self) -> &[(K, V)];
 
@@ -34,19 +56,46 @@ pub trait AbstractVecMap<K, V> {
         self.as_slice().is_empty()
     }
 
-    fn iter(&self) -> VecMapIter<core::slice::Iter<(K, V)>> {
+    fn iter(&self) -> VecMapIter<core::slice::Iter<'_, (K, V)>> {
         VecMapIter::new(self.as_slice().iter())
     }
 
-    /// lookup of a mapping. Time complexity is O(log N). Binary search.
+    /// iterate over all mappings with keys strictly greater than the given key
+    ///
+    /// This is a binary search, so it can be used to resume iteration at the last returned key, e.g. for paging
+    /// over a large archived map, without skipping all previous mappings.
+    fn iter_after<Q>(&self, key: &Q) -> VecMapIter<core::slice::Iter<'_, (K, V)>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let elements = self.as_slice();
+        let start = elements.partition_point(|p| p.0.borrow() <= key);
+        VecMapIter::new(elements[start..].iter())
+    }
+
+    /// The index of the first mapping for which the predicate is false, see [slice::partition_point].
+    ///
+    /// The predicate must be true for a prefix of the mappings, e.g. `|(k, _)| k < key` for the first mapping
+    /// with a key that is greater than or equal to `key`.
+    fn partition_point(&self, pred: impl FnMut(&(K, V)) -> bool) -> usize {
+        self.as_slice().partition_point(pred)
+    }
+
+    /// Binary search with a comparator function, see [slice::binary_search_by].
+    fn binary_search_by(&self, f: impl FnMut(&(K, V)) -> Ordering) -> Result<usize, usize> {
+        self.as_slice().binary_search_by(f)
+    }
+
+    /// lookup of a mapping. Time complexity is O(log N). Binary search, or a branchless scan for maps with less
+    /// than 16 mappings.
     fn get<Q>(&self, key: &Q) -> Option<&V>
     where
         K: Borrow<Q> + 'static,
         Q: Ord + ?Sized,
     {
         let elements = self.as_slice();
-        elements
-            .binary_search_by(|p| p.0.borrow().cmp(key))
+        search_by_key(elements, key, |p| p.0.borrow())
             .map(|index| &elements[index].1)
             .ok()
     }
@@ -61,8 +110,8 @@ pub trait AbstractVecMap<K, V> {
         F: Fn(OuterJoinArg<&K, &V, &W>) -> Option<R>,
     {
         VecMap::<A>::new(SmallVecMergeState::merge(
-            self.as_slice(),
-            that.as_slice(),
+            checked(self.as_slice()),
+            checked(that.as_slice()),
             OuterJoinOp(f),
             NoConverter,
         ))
@@ -75,8 +124,8 @@ pub trait AbstractVecMap<K, V> {
         A: Array<Item = (K, R)>,
     {
         VecMap::new(SmallVecMergeState::merge(
-            self.as_slice(),
-            that.as_slice(),
+            checked(self.as_slice()),
+            checked(that.as_slice()),
             LeftJoinOp(f),
             NoConverter,
         ))
@@ -89,8 +138,8 @@ pub trait AbstractVecMap<K, V> {
         A: Array<Item = (K, R)>,
     {
         VecMap::new(SmallVecMergeState::merge(
-            self.as_slice(),
-            that.as_slice(),
+            checked(self.as_slice()),
+            checked(that.as_slice()),
             RightJoinOp(f),
             NoConverter,
         ))
@@ -103,8 +152,8 @@ pub trait AbstractVecMap<K, V> {
         A: Array<Item = (K, R)>,
     {
         VecMap::new(SmallVecMergeState::merge(
-            self.as_slice(),
-            that.as_slice(),
+            checked(self.as_slice()),
+            checked(that.as_slice()),
             InnerJoinOp(f),
             NoConverter,
         ))
@@ -117,6 +166,18 @@ impl<K, V, A: Array<Item = (K, V)>> AbstractVecMap<K, V> for VecMap<A> {
     }
 }
 
+/// A slice of entries that is strictly sorted by key can be used as a map operand without copying it into a
+/// [VecMap] first.
+///
+/// The slice is not checked in release builds, so using an unsorted slice gives wrong results. If the
+/// `debug_invariants` feature is enabled in a debug build, the operations check that it is sorted by key and panic
+/// otherwise.
+impl<K, V> AbstractVecMap<K, V> for &[(K, V)] {
+    fn as_slice(&self) -> &[(K, V)] {
+        self
+    }
+}
+
 #[cfg(feature = "rkyv")]
 impl<K, V> AbstractVecMap<K, V> for ArchivedVecMap<K, V> {
     fn as_slice(&self) -> &[(K, V)] {
@@ -126,6 +187,9 @@ impl<K, V> AbstractVecMap<K, V> for ArchivedVecMap<K, V> {
 
 /// A map backed by a [SmallVec] of key value pairs.
 ///
+/// Like [VecSet], this does not implement `FromIterator` and `Extend` for pairs of references, since they would
+/// conflict with the impls for `A::Item`. Map the pairs to owned values before collecting instead.
+///
 /// [SmallVec]: https://docs.rs/smallvec/1.4.1/smallvec/struct.SmallVec.html
 pub struct VecMap<A: Array>(SmallVec<A>);
 
@@ -134,6 +198,62 @@ pub struct VecMap<A: Array>(SmallVec<A>);
 /// This is a good default, since for usize sized keys and values, 1 mapping is the max you can fit in without making the struct larger.
 pub type VecMap1<K, V> = VecMap<[(K, V); 1]>;
 
+/// Error when creating a map from separate keys and values.
+///
+/// Contains the rejected keys and values, so they can be reused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FromPartsError<T> {
+    /// the number of keys and values differs
+    LengthMismatch(T),
+    /// the keys are not strictly sorted
+    NotSorted(NotSortedError<T>),
+}
+
+impl<T> FromPartsError<T> {
+    /// The rejected keys and values
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::LengthMismatch(parts) => parts,
+            Self::NotSorted(e) => e.into_inner(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for FromPartsError<T> {}
+
+impl<T> fmt::Display for FromPartsError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LengthMismatch(_) => write!(f, "number of keys and values differs"),
+            Self::NotSorted(e) => write!(f, "keys {}", e),
+        }
+    }
+}
+
+/// Error when a key transformation maps two different keys to the same key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateKeyError<K>(K);
+
+impl<K> DuplicateKeyError<K> {
+    /// The key that more than one key was mapped to
+    pub fn key(&self) -> &K {
+        &self.0
+    }
+
+    /// The key that more than one key was mapped to
+    pub fn into_key(self) -> K {
+        self.0
+    }
+}
+
+impl<K: fmt::Debug> std::error::Error for DuplicateKeyError<K> {}
+
+impl<K: fmt::Debug> fmt::Display for DuplicateKeyError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "duplicate key {:?}", self.0)
+    }
+}
+
 impl<T: Debug, A: Array<Item = T>> Debug for VecMap<A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_set().entries(self.as_slice().iter()).finish()
@@ -144,6 +264,11 @@ impl<T: Clone, A: Array<Item = T>> Clone for VecMap<A> {
     fn clone(&self) -> Self {
         Self(self.0.clone())
     }
+
+    /// Reuses the allocation of `self`, and the resources of the existing elements, where possible.
+    fn clone_from(&mut self, source: &Self) {
+        self.0.clone_from(&source.0)
+    }
 }
 
 impl<T: Hash, A: Array<Item = T>> Hash for VecMap<A> {
@@ -201,29 +326,6 @@ impl<A: Array> From<VecMap<A>> for VecSet<A> {
     }
 }
 
-struct CombineOp<F, K>(F, std::marker::PhantomData<K>);
-
-impl<'a, K: Ord, V, A: Array<Item = (K, V)>, B: Array<Item = (K, V)>, F: Fn(V, V) -> V>
-    MergeOperation<InPlaceMergeState<'a, A, B>> for CombineOp<F, K>
-{
-    fn cmp(&self, a: &(K, V), b: &(K, V)) -> Ordering {
-        a.0.cmp(&b.0)
-    }
-    fn from_a(&self, m: &mut InPlaceMergeState<A, B>, n: usize) -> bool {
-        m.advance_a(n, true)
-    }
-    fn from_b(&self, m: &mut InPlaceMergeState<A, B>, n: usize) -> bool {
-        m.advance_b(n, true)
-    }
-    fn collision(&self, m: &mut InPlaceMergeState<A, B>) -> bool {
-        if let (Some((ak, av)), Some((_, bv))) = (m.a.pop_front(), m.b.next()) {
-            let r = (self.0)(av, bv);
-            m.a.push((ak, r));
-        }
-        true
-    }
-}
-
 pub enum OuterJoinArg<K, A, B> {
     Left(K, A),
     Right(K, B),
@@ -234,6 +336,8 @@ struct OuterJoinOp<F>(F);
 struct LeftJoinOp<F>(F);
 struct RightJoinOp<F>(F);
 struct InnerJoinOp<F>(F);
+/// Keep (true) or remove (false) the entries whose keys are in a set
+struct KeysOp(bool);
 
 impl<K: Ord, V, A: Array<Item = (K, V)>> FromIterator<(K, V)> for VecMap<A> {
     fn from_iter<I: IntoIterator<Item = A::Item>>(iter: I) -> Self {
@@ -247,6 +351,43 @@ impl<K, V, A: Array<Item = (K, V)>> From<BTreeMap<K, V>> for VecMap<A> {
     }
 }
 
+impl<K: Ord, V, S, A: Array<Item = (K, V)>> From<HashMap<K, V, S>> for VecMap<A> {
+    fn from(value: HashMap<K, V, S>) -> Self {
+        // keys of a hash map are unique, so a single sort is sufficient
+        let mut entries: SmallVec<A> = value.into_iter().collect();
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        Self::new(entries)
+    }
+}
+
+impl<K: Hash + Eq, V, S: hash::BuildHasher + Default, A: Array<Item = (K, V)>> From<VecMap<A>>
+    for HashMap<K, V, S>
+{
+    fn from(value: VecMap<A>) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: Ord, V, S, A: Array<Item = (K, V)>> From<indexmap::IndexMap<K, V, S>> for VecMap<A> {
+    fn from(value: indexmap::IndexMap<K, V, S>) -> Self {
+        // keys of an index map are unique, so a single sort is sufficient
+        let mut entries: SmallVec<A> = value.into_iter().collect();
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        Self::new(entries)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: Hash + Eq, V, S: hash::BuildHasher + Default, A: Array<Item = (K, V)>> From<VecMap<A>>
+    for indexmap::IndexMap<K, V, S>
+{
+    /// The insertion order of the resulting index map is the key order of the vec map
+    fn from(value: VecMap<A>) -> Self {
+        value.into_iter().collect()
+    }
+}
+
 impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> Extend<A::Item> for VecMap<A> {
     fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
         self.merge_with::<A>(iter.into_iter().collect());
@@ -259,12 +400,82 @@ impl<A: Array> AsRef<[A::Item]> for VecMap<A> {
     }
 }
 
+impl<A: Array> Deref for VecMap<A> {
+    type Target = [A::Item];
+
+    fn deref(&self) -> &[A::Item] {
+        self.as_slice()
+    }
+}
+
+impl<A: Array> Borrow<[A::Item]> for VecMap<A> {
+    fn borrow(&self) -> &[A::Item] {
+        self.as_slice()
+    }
+}
+
+impl<T: PartialEq, A: Array<Item = T>> PartialEq<[T]> for VecMap<A> {
+    fn eq(&self, other: &[T]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+impl<T: PartialEq, A: Array<Item = T>> PartialEq<&[T]> for VecMap<A> {
+    fn eq(&self, other: &&[T]) -> bool {
+        self.as_slice() == *other
+    }
+}
+
+impl<T: PartialEq, A: Array<Item = T>> PartialEq<Vec<T>> for VecMap<A> {
+    fn eq(&self, other: &Vec<T>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
 impl<A: Array> From<VecMap<A>> for SmallVec<A> {
     fn from(value: VecMap<A>) -> Self {
         value.0
     }
 }
 
+/// The entries whose keys are in the set, see [select](VecMap::select)
+impl<K: Ord + Clone + 'static, V: Clone, A: Array<Item = (K, V)>, B: Array<Item = K>>
+    BitAnd<&VecSet<B>> for &VecMap<A>
+{
+    type Output = VecMap<A>;
+    fn bitand(self, that: &VecSet<B>) -> Self::Output {
+        self.select(that)
+    }
+}
+
+/// The entries whose keys are not in the set, see [without](VecMap::without)
+impl<K: Ord + Clone + 'static, V: Clone, A: Array<Item = (K, V)>, B: Array<Item = K>>
+    Sub<&VecSet<B>> for &VecMap<A>
+{
+    type Output = VecMap<A>;
+    fn sub(self, that: &VecSet<B>) -> Self::Output {
+        self.without(that)
+    }
+}
+
+/// Retain the entries whose keys are in the set, see [retain_keys](VecMap::retain_keys)
+impl<K: Ord + 'static, V, A: Array<Item = (K, V)>, B: Array<Item = K>> BitAndAssign<&VecSet<B>>
+    for VecMap<A>
+{
+    fn bitand_assign(&mut self, that: &VecSet<B>) {
+        self.retain_keys(that)
+    }
+}
+
+/// Remove the entries whose keys are in the set, see [remove_keys](VecMap::remove_keys)
+impl<K: Ord + 'static, V, A: Array<Item = (K, V)>, B: Array<Item = K>> SubAssign<&VecSet<B>>
+    for VecMap<A>
+{
+    fn sub_assign(&mut self, that: &VecSet<B>) {
+        self.remove_keys(that)
+    }
+}
+
 impl<'a, K, V, W, R, A, F> MergeOperation<SmallVecMergeState<'a, (K, V), (K, W), A>>
     for OuterJoinOp<F>
 where
@@ -434,7 +645,7 @@ impl<'a, K, V, W, F, A> MergeOperation<InPlaceSmallVecMergeStateRef<'a, A, (K, W
     for LeftJoinOp<F>
 where
     A: Array<Item = (K, V)>,
-    K: Ord + Clone,
+    K: Ord,
     F: Fn(&K, V, Option<&W>) -> Option<V>,
 {
     fn cmp(&self, a: &(K, V), b: &(K, W)) -> Ordering {
@@ -572,7 +783,7 @@ impl<'a, K, V, W, F, A> MergeOperation<InPlaceSmallVecMergeStateRef<'a, A, (K, W
     for InnerJoinOp<F>
 where
     A: Array<Item = (K, V)>,
-    K: Ord + Clone,
+    K: Ord,
     F: Fn(&K, V, &W) -> Option<V>,
 {
     fn cmp(&self, a: &(K, V), b: &(K, W)) -> Ordering {
@@ -598,7 +809,83 @@ where
     }
 }
 
+impl<'a, K: Ord, V, A> MergeOperation<InPlaceSmallVecMergeStateRef<'a, A, K>> for KeysOp
+where
+    A: Array<Item = (K, V)>,
+{
+    fn cmp(&self, a: &(K, V), b: &K) -> Ordering {
+        a.0.cmp(b)
+    }
+    fn from_a(&self, m: &mut InPlaceSmallVecMergeStateRef<'a, A, K>, n: usize) -> bool {
+        m.a.consume(n, !self.0);
+        true
+    }
+    fn from_b(&self, m: &mut InPlaceSmallVecMergeStateRef<'a, A, K>, n: usize) -> bool {
+        m.b.drop_front(n);
+        true
+    }
+    fn collision(&self, m: &mut InPlaceSmallVecMergeStateRef<'a, A, K>) -> bool {
+        m.a.consume(1, self.0);
+        m.b.drop_front(1);
+        true
+    }
+}
+
+impl<'a, K, V, A> MergeOperation<SmallVecMergeState<'a, (K, V), K, A>> for KeysOp
+where
+    K: Ord + Clone,
+    V: Clone,
+    A: Array<Item = (K, V)>,
+{
+    fn cmp(&self, a: &(K, V), b: &K) -> Ordering {
+        a.0.cmp(b)
+    }
+    fn from_a(&self, m: &mut SmallVecMergeState<'a, (K, V), K, A>, n: usize) -> bool {
+        let entries = m.a.take_front(n);
+        if !self.0 {
+            m.r.extend(entries.iter().cloned());
+        }
+        true
+    }
+    fn from_b(&self, m: &mut SmallVecMergeState<'a, (K, V), K, A>, n: usize) -> bool {
+        m.b.drop_front(n);
+        // when keeping, nothing after the last key can be kept
+        !self.0 || !m.b.as_slice().is_empty()
+    }
+    fn collision(&self, m: &mut SmallVecMergeState<'a, (K, V), K, A>) -> bool {
+        let entries = m.a.take_front(1);
+        if self.0 {
+            m.r.extend(entries.iter().cloned());
+        }
+        m.b.drop_front(1);
+        true
+    }
+}
+
 impl<K, V, A: Array<Item = (K, V)>> VecMap<A> {
+    /// retain all pairs matching a predicate, with mutable access to the values
+    ///
+    /// Returns the number of removed pairs.
+    ///
+    /// # Example
+    ///
+    /// Decrement counters and drop the ones hitting zero in a single pass:
+    /// ```
+    /// use vec_collections::VecMap;
+    /// let mut counters: VecMap<[(&str, u32); 4]> = vec![("a", 1), ("b", 2), ("c", 1)].into_iter().collect();
+    /// let removed = counters.retain_mut(|_, count| {
+    ///     *count -= 1;
+    ///     *count > 0
+    /// });
+    /// assert_eq!(removed, 2);
+    /// assert_eq!(counters.get(&"b"), Some(&1));
+    /// ```
+    pub fn retain_mut<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) -> usize {
+        let n = self.0.len();
+        self.0.retain(|(k, v)| f(k, v));
+        n - self.0.len()
+    }
+
     /// map values while keeping keys
     pub fn map_values<R, B: Array<Item = (K, R)>, F: FnMut(V) -> R>(self, mut f: F) -> VecMap<B> {
         VecMap::new(
@@ -608,6 +895,120 @@ impl<K, V, A: Array<Item = (K, V)>> VecMap<A> {
                 .collect(),
         )
     }
+
+    /// modify all values in place, keeping keys
+    ///
+    /// Unlike [map_values](Self::map_values), this reuses the existing storage.
+    pub fn map_values_in_place<F: FnMut(&mut V)>(&mut self, mut f: F) {
+        for (_, v) in self.0.iter_mut() {
+            f(v)
+        }
+    }
+
+    /// replace all values with the result of a function of the old value, keeping keys
+    ///
+    /// Like [map_values](Self::map_values), but for a function from `V` to `V`, so the existing storage is reused.
+    /// If `f` panics, the map contains the entries processed so far.
+    pub fn transform_values<F: FnMut(V) -> V>(&mut self, mut f: F) {
+        self.0.flat_map_in_place(|(k, v)| Some((k, f(v))))
+    }
+
+    /// Maps the keys of the map, keeping the values.
+    ///
+    /// While mapping, this checks whether `f` preserved the order of the keys, and only sorts again if it did not.
+    /// So a monotone transformation, e.g. widening integer ids, is O(N). If `f` maps several keys to the same key,
+    /// the entry with the largest original key is kept, like when collecting.
+    pub fn map_keys<L: Ord, B: Array<Item = (L, V)>, F: FnMut(K) -> L>(self, f: F) -> VecMap<B> {
+        let (entries, sorted) = Self::map_keys0(self.0, f);
+        VecMap::new(if sorted {
+            entries
+        } else {
+            sort_dedup_by_key(entries.into_iter(), Keep::Last, |(k, _)| k)
+        })
+    }
+
+    /// Maps the keys of the map, keeping the values, or fails if `f` maps several keys to the same key.
+    ///
+    /// Like [map_keys](VecMap::map_keys), this only sorts again if `f` did not preserve the order of the keys.
+    pub fn try_map_keys<L: Ord, B: Array<Item = (L, V)>, F: FnMut(K) -> L>(
+        self,
+        f: F,
+    ) -> Result<VecMap<B>, DuplicateKeyError<L>> {
+        let (mut entries, sorted): (SmallVec<B>, _) = Self::map_keys0(self.0, f);
+        if !sorted {
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            if let Some(index) = entries.windows(2).position(|w| w[0].0 == w[1].0) {
+                return Err(DuplicateKeyError(entries.swap_remove(index).0));
+            }
+        }
+        Ok(VecMap::new(entries))
+    }
+
+    /// map the keys, and check if they are still strictly sorted on the way
+    // `is_none_or` needs rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    fn map_keys0<L: Ord, B: Array<Item = (L, V)>, F: FnMut(K) -> L>(
+        entries: SmallVec<A>,
+        mut f: F,
+    ) -> (SmallVec<B>, bool) {
+        let mut res: SmallVec<B> = SmallVec::with_capacity(entries.len());
+        let mut sorted = true;
+        for (k, v) in entries {
+            let k = f(k);
+            if sorted {
+                sorted = res.last().map_or(true, |(prev, _)| *prev < k);
+            }
+            res.push((k, v));
+        }
+        (res, sorted)
+    }
+
+    /// Splits the map into separate keys and values, in key order.
+    ///
+    /// This is useful for interop with columnar formats. Each part is allocated once, with the exact size.
+    pub fn into_parts<KA: Array<Item = K>, VA: Array<Item = V>>(
+        self,
+    ) -> (SmallVec<KA>, SmallVec<VA>) {
+        let mut keys = SmallVec::with_capacity(self.len());
+        let mut values = SmallVec::with_capacity(self.len());
+        for (k, v) in self.0 {
+            keys.push(k);
+            values.push(v);
+        }
+        (keys, values)
+    }
+
+    /// Intern the values, so that equal values share a single [Arc]
+    ///
+    /// This is useful for maps where many keys have equal values, e.g. status strings.
+    pub fn compact_values<B: Array<Item = (K, Arc<V>)>>(self) -> VecMap<B>
+    where
+        V: Ord,
+    {
+        let mut interned: BTreeSet<Arc<V>> = BTreeSet::new();
+        self.map_values(|value| match interned.get(&value) {
+            Some(shared) => shared.clone(),
+            None => {
+                let shared = Arc::new(value);
+                interned.insert(shared.clone());
+                shared
+            }
+        })
+    }
+
+    /// Remove all entries that have the same value as the previous entry.
+    ///
+    /// This turns a map into a run-length encoding of itself, where an entry marks the start of a run of keys with
+    /// the same value. The value for a key is then the value of the last entry with a key less than or equal to it.
+    /// Returns the number of removed entries.
+    pub fn dedup_adjacent_values(&mut self) -> usize
+    where
+        V: PartialEq,
+    {
+        let n = self.0.len();
+        self.0.dedup_by(|(_, b), (_, a)| a == b);
+        n - self.0.len()
+    }
 }
 
 impl<A: Array> VecMap<A> {
@@ -629,14 +1030,110 @@ impl<A: Array> VecMap<A> {
         self.0.len()
     }
 
+    /// The capacity of the underlying SmallVec.
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// true if the entries are stored on the heap instead of inline
+    pub fn spilled(&self) -> bool {
+        self.0.spilled()
+    }
+
+    /// The empty map with space for at least `capacity` mappings.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(SmallVec::with_capacity(capacity))
+    }
+
+    /// Reserve space for at least `additional` more mappings.
+    ///
+    /// In place operations like [combine_with](VecMap::combine_with) use the spare capacity as the gap between the
+    /// result and the remaining entries, so reserving the expected growth once avoids reallocations when merging many
+    /// batches.
+    pub fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional)
+    }
+
+    /// Reserve space for at least `additional` more mappings, returning an error if the allocation fails.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), CollectionAllocErr> {
+        self.0.try_reserve(additional)
+    }
+
+    /// Shrink the underlying SmallVec to fit.
+    pub fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit()
+    }
+
+    /// Release excess capacity of the underlying SmallVec according to a [CapacityPolicy].
+    pub fn apply_capacity_policy(&mut self, policy: CapacityPolicy) {
+        policy.apply(&mut self.0)
+    }
+
+    /// Perform one or more operations on the map, then apply a [CapacityPolicy].
+    pub fn with_capacity_policy<R>(
+        &mut self,
+        policy: CapacityPolicy,
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let res = f(self);
+        self.apply_capacity_policy(policy);
+        res
+    }
+
+    /// An iterator over the key value pairs, sorted by key
+    ///
+    /// This is the same as [AbstractVecMap::iter], but does not require the trait to be in scope.
+    pub fn iter(&self) -> VecMapIter<core::slice::Iter<'_, A::Item>> {
+        VecMapIter::new(self.0.iter())
+    }
+
     /// the underlying memory as a slice of key value pairs
-    fn as_slice(&self) -> &[A::Item] {
+    pub fn as_slice(&self) -> &[A::Item] {
         self.0.as_ref()
     }
 
     /// retain all pairs matching a predicate
-    pub fn retain<F: FnMut(&A::Item) -> bool>(&mut self, mut f: F) {
-        self.0.retain(|entry| f(entry))
+    ///
+    /// Returns the number of removed pairs.
+    pub fn retain<F: FnMut(&A::Item) -> bool>(&mut self, mut f: F) -> usize {
+        let n = self.0.len();
+        self.0.retain(|entry| f(entry));
+        n - self.0.len()
+    }
+
+    /// remove all pairs matching a predicate, and return them as a new map
+    ///
+    /// This is the opposite of [retain](VecMap::retain): pairs for which the predicate returns true are removed.
+    ///
+    /// The retained pairs stay in place, so only the removed pairs are moved to new storage.
+    pub fn extract_if<F: FnMut(&A::Item) -> bool>(&mut self, mut f: F) -> Self {
+        let mut removed = SmallVec::new();
+        self.0.flat_map_in_place(|x| {
+            if f(&x) {
+                removed.push(x);
+                None
+            } else {
+                Some(x)
+            }
+        });
+        Self(removed)
+    }
+
+    /// Split the map into the pairs matching a predicate and the pairs not matching it, in a single pass.
+    ///
+    /// Like [Iterator::partition], the first map contains the pairs for which the predicate returns true. It reuses the
+    /// allocation of `self`.
+    pub fn partition<F: FnMut(&A::Item) -> bool>(mut self, mut f: F) -> (Self, Self) {
+        let mut rejected = SmallVec::new();
+        self.0.flat_map_in_place(|x| {
+            if f(&x) {
+                Some(x)
+            } else {
+                rejected.push(x);
+                None
+            }
+        });
+        (self, Self(rejected))
     }
 
     #[cfg(feature = "total")]
@@ -648,14 +1145,101 @@ impl<A: Array> VecMap<A> {
         self.0
     }
 
+    /// Converts to a map with a different inline capacity.
+    ///
+    /// If the entries are on the heap, the heap buffer is reused instead of copying the entries, unless they fit
+    /// into the inline storage of the target type.
+    pub fn cast_storage<B: Array<Item = A::Item>>(self) -> VecMap<B> {
+        VecMap(cast_storage(self.0))
+    }
+
     /// Creates a vecmap with a single item
     pub fn single(item: A::Item) -> Self {
         Self(smallvec::smallvec![item])
     }
+
+    /// Removes and returns the mapping with the smallest key, if any.
+    ///
+    /// This is O(N), since the remaining mappings have to be moved.
+    pub fn pop_first(&mut self) -> Option<A::Item> {
+        if self.0.is_empty() {
+            None
+        } else {
+            Some(self.0.remove(0))
+        }
+    }
+
+    /// Removes and returns the mapping with the largest key, if any. This is O(1).
+    pub fn pop_last(&mut self) -> Option<A::Item> {
+        self.0.pop()
+    }
 }
 
 impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> VecMap<A> {
+    /// Creates a map from key value pairs that are strictly sorted by key, without sorting them again.
+    ///
+    /// This is O(1). If the keys are not strictly sorted, the map will not work correctly. This is checked
+    /// in debug builds.
+    pub fn from_sorted_unchecked(entries: SmallVec<A>) -> Self {
+        debug_assert!(first_unsorted(&entries, |(k, _)| k).is_none());
+        Self::new(entries)
+    }
+
+    /// Re-establish the invariants of the map, by sorting by key and removing duplicate keys.
+    ///
+    /// This is only needed to recover from known bad input, e.g. via [from_sorted_unchecked](VecMap::from_sorted_unchecked)
+    /// or keys whose order changed. On duplicate keys, the last entry is kept, like when collecting. Returns true
+    /// if the map had to be repaired.
+    pub fn repair(&mut self) -> bool {
+        if first_unsorted(&self.0, |(k, _)| k).is_none() {
+            return false;
+        }
+        let entries = std::mem::take(&mut self.0);
+        self.0 = sort_dedup_by_key(entries.into_iter(), Keep::Last, |(k, _)| k);
+        true
+    }
+
+    /// Check the invariants, if the `debug_invariants` feature is enabled in a debug build
+    #[inline]
+    fn check_invariants(&self) {
+        check_sorted(&self.0, |(k, _)| k)
+    }
+
+    /// Creates a map from key value pairs that are strictly sorted by key, or returns them if they are not.
+    ///
+    /// This is O(N), so it is cheaper than collecting if the keys are known to be sorted, e.g. when
+    /// deserializing.
+    pub fn try_from_sorted(entries: SmallVec<A>) -> Result<Self, NotSortedError<SmallVec<A>>> {
+        match first_unsorted(&entries, |(k, _)| k) {
+            Some(index) => Err(NotSortedError::new(index, entries)),
+            None => Ok(Self::new(entries)),
+        }
+    }
+
+    /// Creates a map from separate keys and values, the inverse of [into_parts](VecMap::into_parts).
+    ///
+    /// The keys must be strictly sorted, and there must be as many values as keys. This is O(N).
+    #[allow(clippy::type_complexity)]
+    pub fn from_parts<KA: Array<Item = K>, VA: Array<Item = V>>(
+        keys: SmallVec<KA>,
+        values: SmallVec<VA>,
+    ) -> Result<Self, FromPartsError<(SmallVec<KA>, SmallVec<VA>)>> {
+        if keys.len() != values.len() {
+            return Err(FromPartsError::LengthMismatch((keys, values)));
+        }
+        if let Some(index) = first_unsorted(&keys, |k| k) {
+            return Err(FromPartsError::NotSorted(NotSortedError::new(
+                index,
+                (keys, values),
+            )));
+        }
+        let mut entries = SmallVec::with_capacity(keys.len());
+        entries.extend(keys.into_iter().zip(values));
+        Ok(Self::new(entries))
+    }
+
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.check_invariants();
         match self.0.binary_search_by(|(k, _)| k.cmp(&key)) {
             Ok(index) => {
                 let mut elem = (key, value);
@@ -669,27 +1253,104 @@ impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> VecMap<A> {
         }
     }
 
-    pub fn inner_join_with<W, F>(&mut self, that: &impl AbstractVecMap<K, W>, f: F)
+    /// Remove the mapping for a key, returning the value if it was present. Time complexity is O(N).
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
     where
-        K: Ord + Clone,
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.check_invariants();
+        match self.0.binary_search_by(|p| p.0.borrow().cmp(key)) {
+            Ok(index) => Some(self.0.remove(index).1),
+            Err(_) => None,
+        }
+    }
+
+    pub fn inner_join_with<W, F>(&mut self, that: &impl AbstractVecMap<K, W>, f: F)
+    where
+        K: Ord,
         F: Fn(&K, V, &W) -> Option<V>,
     {
+        self.check_invariants();
         InPlaceSmallVecMergeStateRef::merge(
             &mut self.0,
-            &that.as_slice(),
+            &checked(that.as_slice()),
             InnerJoinOp(f),
             NoConverter,
         )
     }
 
-    pub fn left_join_with<W, F>(&mut self, that: &impl AbstractVecMap<K, W>, f: F)
+    /// Remove all entries whose keys are in the given set, in a single merge pass.
+    pub fn remove_keys(&mut self, keys: &impl AbstractVecSet<K>)
+    where
+        K: Ord,
+    {
+        self.check_invariants();
+        InPlaceSmallVecMergeStateRef::merge(
+            &mut self.0,
+            &keys.as_slice(),
+            KeysOp(false),
+            NoConverter,
+        )
+    }
+
+    /// Retain only the entries whose keys are in the given set, in a single merge pass.
+    pub fn retain_keys(&mut self, keys: &impl AbstractVecSet<K>)
+    where
+        K: Ord,
+    {
+        self.check_invariants();
+        InPlaceSmallVecMergeStateRef::merge(
+            &mut self.0,
+            &keys.as_slice(),
+            KeysOp(true),
+            NoConverter,
+        )
+    }
+
+    /// A copy of the entries whose keys are in the given set, in a single merge pass.
+    ///
+    /// Unlike [retain_keys](VecMap::retain_keys), this leaves this map untouched and only clones the selected entries.
+    pub fn select(&self, keys: &impl AbstractVecSet<K>) -> Self
     where
         K: Ord + Clone,
+        V: Clone,
+    {
+        self.check_invariants();
+        Self::new(SmallVecMergeState::merge(
+            self.as_slice(),
+            keys.as_slice(),
+            KeysOp(true),
+            NoConverter,
+        ))
+    }
+
+    /// A copy of the entries whose keys are not in the given set, in a single merge pass.
+    ///
+    /// Unlike [remove_keys](VecMap::remove_keys), this leaves this map untouched and only clones the remaining entries.
+    pub fn without(&self, keys: &impl AbstractVecSet<K>) -> Self
+    where
+        K: Ord + Clone,
+        V: Clone,
+    {
+        self.check_invariants();
+        Self::new(SmallVecMergeState::merge(
+            self.as_slice(),
+            keys.as_slice(),
+            KeysOp(false),
+            NoConverter,
+        ))
+    }
+
+    pub fn left_join_with<W, F>(&mut self, that: &impl AbstractVecMap<K, W>, f: F)
+    where
+        K: Ord,
         F: Fn(&K, V, Option<&W>) -> Option<V>,
     {
+        self.check_invariants();
         InPlaceSmallVecMergeStateRef::merge(
             &mut self.0,
-            &that.as_slice(),
+            &checked(that.as_slice()),
             LeftJoinOp(f),
             NoConverter,
         )
@@ -700,9 +1361,10 @@ impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> VecMap<A> {
         K: Ord + Clone,
         F: Fn(&K, Option<V>, &W) -> Option<V>,
     {
+        self.check_invariants();
         InPlaceSmallVecMergeStateRef::merge(
             &mut self.0,
-            &that.as_slice(),
+            &checked(that.as_slice()),
             RightJoinOp(f),
             NoConverter,
         )
@@ -713,9 +1375,10 @@ impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> VecMap<A> {
         K: Ord + Clone,
         F: Fn(OuterJoinArg<&K, V, &W>) -> Option<V>,
     {
+        self.check_invariants();
         InPlaceSmallVecMergeStateRef::merge(
             &mut self.0,
-            &that.as_slice(),
+            &checked(that.as_slice()),
             OuterJoinOp(f),
             NoConverter,
         )
@@ -734,6 +1397,8 @@ impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> VecMap<A> {
         that: VecMap<B>,
         f: F,
     ) {
+        self.check_invariants();
+        that.check_invariants();
         InPlaceMergeState::merge(
             &mut self.0,
             that.0,
@@ -747,20 +1412,412 @@ impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> VecMap<A> {
             NoConverter,
         );
     }
+
+    /// in-place union with another map of the same type, keeping the larger value in case of collisions.
+    pub fn union_max<B: Array<Item = A::Item>>(&mut self, that: VecMap<B>)
+    where
+        V: Ord,
+    {
+        self.combine_with(that, cmp::max)
+    }
+
+    /// in-place union with another map of the same type, keeping the smaller value in case of collisions.
+    pub fn union_min<B: Array<Item = A::Item>>(&mut self, that: VecMap<B>)
+    where
+        V: Ord,
+    {
+        self.combine_with(that, cmp::min)
+    }
 }
 
 impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> VecMap<A> {
+    /// lookup of a mapping. Time complexity is O(log N). Binary search.
+    ///
+    /// This is the same as [AbstractVecMap::get], but does not require the trait to be in scope.
+    /// Otherwise [slice::get] would be used via deref.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.check_invariants();
+        AbstractVecMap::get(self, key)
+    }
+
     pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
     where
         K: Borrow<Q>,
         Q: Ord + ?Sized,
     {
+        self.check_invariants();
         let elements = self.0.as_mut_slice();
         match elements.binary_search_by(|p| p.0.borrow().cmp(key)) {
             Ok(index) => Some(&mut elements[index].1),
             Err(_) => None,
         }
     }
+
+    /// Get mutable references to the values for several distinct keys at once.
+    ///
+    /// Returns None if any of the keys is not present, or if any two keys are equal.
+    /// Time complexity is O(N log M) for N keys in a map with M mappings.
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.check_invariants();
+        let mut indices = [0usize; N];
+        for (index, key) in indices.iter_mut().zip(keys.iter()) {
+            *index = self.0.binary_search_by(|p| p.0.borrow().cmp(key)).ok()?;
+        }
+        // the entries are sorted by key, so distinct keys have distinct indices
+        let mut sorted = indices;
+        sorted.sort_unstable();
+        if sorted.windows(2).any(|w| w[0] == w[1]) {
+            return None;
+        }
+        let elements = self.0.as_mut_ptr();
+        // safety: all indices are in bounds and pairwise distinct, so the references do not alias
+        Some(indices.map(|index| unsafe { &mut (*elements.add(index)).1 }))
+    }
+}
+
+impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> VecMap<A> {
+    /// Creates an [Accumulator] to efficiently combine a large number of individual updates into this map.
+    ///
+    /// `f` is used to combine values for the same key, like in [combine_with](VecMap::combine_with).
+    pub fn accumulator<F: Fn(V, V) -> V>(self, f: F) -> Accumulator<A, F> {
+        Accumulator {
+            map: self,
+            buffer: Vec::new(),
+            f,
+        }
+    }
+
+    /// Creates a [StagedVecMap] that buffers up to `threshold` inserts before merging them into this map.
+    pub fn staged(self, threshold: usize) -> StagedVecMap<A> {
+        StagedVecMap {
+            base: self,
+            staging: SmallVec::new(),
+            threshold: threshold.max(1),
+        }
+    }
+}
+
+impl<K1: Ord, K2, V, A: Array<Item = ((K1, K2), V)>> VecMap<A> {
+    /// All entries whose key has the given first component, for maps with composite keys.
+    ///
+    /// The entries are contiguous, so this is just two binary searches. This allows a map with tuple keys to be
+    /// used like a nested map, without allocating an inner map per first component.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::VecMap;
+    /// let map: VecMap<[((u32, u32), &str); 4]> =
+    ///     vec![((1, 1), "a"), ((2, 1), "b"), ((2, 7), "c"), ((3, 0), "d")].into_iter().collect();
+    /// assert_eq!(map.range_prefix(&2), &[((2, 1), "b"), ((2, 7), "c")]);
+    /// assert!(map.range_prefix(&4).is_empty());
+    /// ```
+    pub fn range_prefix(&self, prefix: &K1) -> &[((K1, K2), V)] {
+        let elements = self.as_slice();
+        let start = elements.partition_point(|((k1, _), _)| k1 < prefix);
+        let end = start + elements[start..].partition_point(|((k1, _), _)| k1 == prefix);
+        &elements[start..end]
+    }
+}
+
+impl<K: Ord + Clone, R, A: Array<Item = (K, R)>> VecMap<A> {
+    /// Join any number of maps by key in a single merge pass, producing a new result
+    ///
+    /// For each key that is present in at least one of the maps, `f` is called with the key and an array
+    /// containing the value of each map for that key, or None if the key is not present in that map.
+    /// Keys for which `f` returns None are omitted from the result.
+    ///
+    /// Time complexity is O(N * M) for N maps with a total of M mappings.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::VecMap;
+    /// let a: VecMap<[(u32, u32); 4]> = vec![(1, 1), (2, 2)].into_iter().collect();
+    /// let b: VecMap<[(u32, u32); 4]> = vec![(2, 20), (3, 30)].into_iter().collect();
+    /// let c: VecMap<[(u32, u32); 4]> = vec![(1, 100), (3, 300)].into_iter().collect();
+    /// let sum: VecMap<[(u32, u32); 4]> =
+    ///     VecMap::multi_join([&a, &b, &c], |_, values| Some(values.iter().flatten().copied().sum()));
+    /// assert_eq!(sum, vec![(1, 101), (2, 22), (3, 330)]);
+    /// ```
+    pub fn multi_join<V, M, F, const N: usize>(maps: [&M; N], f: F) -> Self
+    where
+        M: AbstractVecMap<K, V>,
+        F: Fn(&K, [Option<&V>; N]) -> Option<R>,
+    {
+        let slices = maps.map(|map| map.as_slice());
+        let mut positions = [0usize; N];
+        let mut result = SmallVec::new();
+        // the smallest key that has not been processed yet
+        while let Some(key) = slices
+            .iter()
+            .zip(positions.iter())
+            .filter_map(|(&slice, index)| slice.get(*index))
+            .map(|(k, _)| k)
+            .min()
+        {
+            let values = core::array::from_fn(|i| match slices[i].get(positions[i]) {
+                Some((k, v)) if k == key => Some(v),
+                _ => None,
+            });
+            for (index, value) in positions.iter_mut().zip(values.iter()) {
+                if value.is_some() {
+                    *index += 1;
+                }
+            }
+            if let Some(r) = f(key, values) {
+                result.push((key.clone(), r));
+            }
+        }
+        Self::new(result)
+    }
+}
+
+impl<K: Ord, V: Ord, B: Array<Item = V>, A: Array<Item = (K, VecSet<B>)>> VecMap<A> {
+    /// Group key value pairs by key, producing a map from each key to the set of its values
+    ///
+    /// This sorts the pairs once and then groups them in a single pass, so it is O(N log N) for N pairs,
+    /// whereas inserting into the set for each key individually is quadratic in the worst case.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::{VecMap, VecSet};
+    /// let pairs = vec![("b", 2), ("a", 1), ("b", 1), ("a", 1)];
+    /// let groups: VecMap<[(&str, VecSet<[u32; 2]>); 2]> = VecMap::group_by(pairs);
+    /// assert_eq!(groups.get(&"a").unwrap().as_ref(), &[1]);
+    /// assert_eq!(groups.get(&"b").unwrap().as_ref(), &[1, 2]);
+    /// ```
+    pub fn group_by<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut pairs: Vec<(K, V)> = iter.into_iter().collect();
+        pairs.sort_unstable();
+        let mut pairs = pairs.into_iter().peekable();
+        let mut result = SmallVec::new();
+        while let Some((key, value)) = pairs.next() {
+            let mut values: SmallVec<B> = smallvec::smallvec![value];
+            while let Some((_, value)) = pairs.next_if(|(k, _)| *k == key) {
+                // the pairs are sorted, so duplicate values are adjacent
+                if values.last() != Some(&value) {
+                    values.push(value);
+                }
+            }
+            result.push((key, VecSet::from_sorted_unchecked(values)));
+        }
+        Self::new(result)
+    }
+}
+
+impl<K: Ord + Clone, V: Ord + Clone, A: Array<Item = (K, V)>> VecMap<A> {
+    /// Invert the map, producing a map from each value to the set of keys that map to it
+    ///
+    /// This is done with a single sort, using [group_by](VecMap::group_by).
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::{VecMap, VecSet};
+    /// let a: VecMap<[(u32, char); 4]> = vec![(1, 'a'), (2, 'b'), (3, 'a')].into_iter().collect();
+    /// let inverted: VecMap<[(char, VecSet<[u32; 2]>); 2]> = a.invert();
+    /// assert_eq!(inverted.get(&'a').unwrap().as_ref(), &[1, 3]);
+    /// assert_eq!(inverted.get(&'b').unwrap().as_ref(), &[2]);
+    /// ```
+    pub fn invert<B: Array<Item = K>, C: Array<Item = (V, VecSet<B>)>>(&self) -> VecMap<C> {
+        VecMap::group_by(self.0.iter().map(|(k, v)| (v.clone(), k.clone())))
+    }
+}
+
+/// A builder that accumulates individual updates into a [VecMap].
+///
+/// Inserting into a VecMap is O(N), so accumulating e.g. counts one by one is slow for large maps. The
+/// accumulator buffers updates and merges them into the map in sorted batches, which makes the cost
+/// of an update amortized O(log N).
+///
+/// # Example
+/// ```
+/// use vec_collections::{VecMap, VecMap1};
+/// let mut counts = VecMap1::<u32, u32>::default().accumulator(|a, b| a + b);
+/// for x in (0..1000).map(|x| x % 10) {
+///     counts.add(x, 1);
+/// }
+/// assert_eq!(counts.get(&3), Some(&100));
+/// let counts: VecMap1<u32, u32> = counts.into_inner();
+/// assert_eq!(counts.len(), 10);
+/// ```
+pub struct Accumulator<A: Array, F> {
+    /// the merged state
+    map: VecMap<A>,
+    /// unsorted updates that have not been merged yet
+    buffer: Vec<A::Item>,
+    /// function to combine values for the same key
+    f: F,
+}
+
+impl<K: Ord + 'static, V, A: Array<Item = (K, V)>, F: Fn(V, V) -> V> Accumulator<A, F> {
+    /// Add a value for a key. If there is already a value, the two will be combined.
+    pub fn add(&mut self, key: K, value: V) {
+        self.buffer.push((key, value));
+        // merging is O(N), so only do it when the buffer has about the size of the map
+        if self.buffer.len() >= self.map.len().max(16) {
+            self.flush();
+        }
+    }
+
+    /// Merge all buffered updates into the map.
+    pub fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        // this must be a stable sort, so values for the same key are combined in insertion order
+        self.buffer.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut batch: Vec<(K, V)> = Vec::with_capacity(self.buffer.len());
+        for (k, v) in self.buffer.drain(..) {
+            match batch.pop() {
+                Some((lk, lv)) if lk == k => batch.push((lk, (self.f)(lv, v))),
+                Some(last) => {
+                    batch.push(last);
+                    batch.push((k, v));
+                }
+                None => batch.push((k, v)),
+            }
+        }
+        let batch: VecMap<[(K, V); 0]> = VecMap::new(SmallVec::from_vec(batch));
+        self.map.combine_with(batch, &self.f);
+    }
+
+    /// The merged state, after merging all buffered updates.
+    pub fn merged(&mut self) -> &VecMap<A> {
+        self.flush();
+        &self.map
+    }
+
+    /// Lookup of the merged value for a key.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.merged().get(key)
+    }
+
+    /// Returns the merged map.
+    pub fn into_inner(mut self) -> VecMap<A> {
+        self.flush();
+        self.map
+    }
+}
+
+/// A [VecMap] with a small unsorted staging buffer for inserts, created with [VecMap::staged].
+///
+/// Inserting into a VecMap is O(N). Here, inserts go to the staging buffer, which is merged into the map in a
+/// single pass once it reaches the threshold. Lookups consult the staging buffer first, so they are O(T + log N)
+/// for a threshold of T. Bulk reads go through [compacted](StagedVecMap::compacted), which merges the staging
+/// buffer first.
+///
+/// Unlike an [Accumulator], this does not need to merge on lookups, so it is useful for interleaved inserts
+/// and lookups.
+///
+/// # Example
+/// ```
+/// use vec_collections::VecMap1;
+/// let mut map = VecMap1::<u32, &str>::default().staged(8);
+/// map.insert(2, "b");
+/// map.insert(1, "a");
+/// assert_eq!(map.get(&1), Some(&"a"));
+/// assert_eq!(map.staged_len(), 2);
+/// assert_eq!(map.compacted().as_slice(), &[(1, "a"), (2, "b")]);
+/// ```
+pub struct StagedVecMap<A: Array> {
+    /// the sorted base map
+    base: VecMap<A>,
+    /// unsorted entries with unique keys, that take precedence over the entries of the base map
+    staging: SmallVec<[A::Item; 16]>,
+    /// number of staged entries at which they are merged into the base map
+    threshold: usize,
+}
+
+impl<K: Ord + 'static, V, A: Array<Item = (K, V)>> StagedVecMap<A> {
+    /// Insert a mapping. The previous value for the key, if any, is replaced.
+    ///
+    /// This is O(T) for a threshold of T, except for every T-th insert, which merges in O(N + T log T).
+    pub fn insert(&mut self, key: K, value: V) {
+        match self.staging.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = value,
+            None => {
+                self.staging.push((key, value));
+                if self.staging.len() >= self.threshold {
+                    self.compact();
+                }
+            }
+        }
+    }
+
+    /// Lookup of a mapping, consulting the staging buffer first.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.staging.iter().find(|(k, _)| k.borrow() == key) {
+            Some((_, v)) => Some(v),
+            None => self.base.get(key),
+        }
+    }
+
+    /// true if there is a mapping for the key
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Remove the mapping for a key, returning the value if it was present. Time complexity is O(N).
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let staged = self
+            .staging
+            .iter()
+            .position(|(k, _)| k.borrow() == key)
+            .map(|index| self.staging.swap_remove(index).1);
+        let base = self.base.remove(key);
+        staged.or(base)
+    }
+
+    /// Merge the staging buffer into the base map in a single pass.
+    pub fn compact(&mut self) {
+        if self.staging.is_empty() {
+            return;
+        }
+        // keys in the staging buffer are unique, so the order of equal keys does not matter
+        self.staging.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let staged: VecMap<[(K, V); 16]> = VecMap::new(core::mem::take(&mut self.staging));
+        self.base.merge_with(staged);
+    }
+
+    /// The map, after merging the staging buffer.
+    pub fn compacted(&mut self) -> &VecMap<A> {
+        self.compact();
+        &self.base
+    }
+
+    /// Number of entries in the staging buffer
+    pub fn staged_len(&self) -> usize {
+        self.staging.len()
+    }
+
+    /// Returns the map, after merging the staging buffer.
+    pub fn into_inner(mut self) -> VecMap<A> {
+        self.compact();
+        self.base
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -769,12 +1826,18 @@ where
     K: Serialize,
     V: Serialize,
 {
+    /// Human readable formats get a map. Binary formats get a tuple of a flag that the keys are sorted and
+    /// the key value pairs, so deserialization can skip sorting.
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut state = serializer.serialize_map(Some(self.len()))?;
-        for (k, v) in self.0.iter() {
-            state.serialize_entry(&k, &v)?;
+        if serializer.is_human_readable() {
+            let mut state = serializer.serialize_map(Some(self.len()))?;
+            for (k, v) in self.0.iter() {
+                state.serialize_entry(&k, &v)?;
+            }
+            state.end()
+        } else {
+            (true, self.0.as_slice()).serialize(serializer)
         }
-        state.end()
     }
 }
 
@@ -785,9 +1848,20 @@ where
     V: Deserialize<'de>,
 {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        deserializer.deserialize_map(VecMapVisitor {
-            phantom: PhantomData,
-        })
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_map(VecMapVisitor {
+                phantom: PhantomData,
+            })
+        } else {
+            let (sorted, RawElements(mut values)) =
+                <(bool, RawElements<A>)>::deserialize(deserializer)?;
+            // do not trust the flag, but checking is much cheaper than sorting
+            if !sorted || first_unsorted(&values, |(k, _)| k).is_some() {
+                values.sort_by_key(|x: &(K, V)| x.0.clone());
+                values.dedup_by_key(|x: &mut (K, V)| x.0.clone());
+            }
+            Ok(VecMap(values))
+        }
     }
 }
 
@@ -826,6 +1900,48 @@ where
 #[repr(transparent)]
 pub struct ArchivedVecMap<K, V>(rkyv::vec::ArchivedVec<(K, V)>);
 
+/// Hashes like a [VecMap] with the same entries, so archived and live maps can be used interchangeably as keys
+#[cfg(feature = "rkyv")]
+impl<K: Hash, V: Hash> Hash for ArchivedVecMap<K, V> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.0.as_slice().hash(state)
+    }
+}
+
+/// Compares like a [VecMap] with the same entries, so archived maps can be nested in archived sets and maps
+#[cfg(feature = "rkyv")]
+impl<K: PartialEq, V: PartialEq> PartialEq for ArchivedVecMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_slice() == other.0.as_slice()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<K: Eq, V: Eq> Eq for ArchivedVecMap<K, V> {}
+
+#[cfg(feature = "rkyv")]
+impl<K: PartialOrd, V: PartialOrd> PartialOrd for ArchivedVecMap<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.0.as_slice().partial_cmp(other.0.as_slice())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<K: Ord, V: Ord> Ord for ArchivedVecMap<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.as_slice().cmp(other.0.as_slice())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for ArchivedVecMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
 #[cfg(feature = "rkyv")]
 impl<K, V, A> rkyv::Archive for VecMap<A>
 where
@@ -876,10 +1992,13 @@ where
 #[cfg(feature = "rkyv_validated")]
 #[derive(Debug)]
 pub enum ArchivedVecMapError {
-    /// error with the individual elements of the VecSet
-    ValueCheckError,
-    /// elements were not properly ordered
-    OrderCheckError,
+    /// error with the individual entries of the VecMap, with the message of the underlying error
+    ValueCheckError(String),
+    /// entries were not properly ordered by key
+    OrderCheckError {
+        /// index of the first entry whose key is not greater than the key of the entry before it
+        index: usize,
+    },
 }
 
 #[cfg(feature = "rkyv_validated")]
@@ -888,7 +2007,12 @@ impl std::error::Error for ArchivedVecMapError {}
 #[cfg(feature = "rkyv_validated")]
 impl std::fmt::Display for ArchivedVecMapError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Self::ValueCheckError(cause) => write!(f, "invalid entries: {}", cause),
+            Self::OrderCheckError { index } => {
+                write!(f, "keys are not strictly sorted at index {}", index)
+            }
+        }
     }
 }
 
@@ -897,8 +2021,8 @@ impl<C: ?Sized, K, V> bytecheck::CheckBytes<C> for ArchivedVecMap<K, V>
 where
     C: ArchiveContext,
     C::Error: std::error::Error,
-    K: Ord + Archive + CheckBytes<C>,
-    V: Archive + CheckBytes<C>,
+    K: Ord + CheckBytes<C>,
+    V: CheckBytes<C>,
     bool: bytecheck::CheckBytes<C>,
 {
     type Error = ArchivedVecMapError;
@@ -908,13 +2032,9 @@ where
     ) -> Result<&'a Self, Self::Error> {
         let values = &(*value).0;
         CheckBytes::check_bytes(values, context)
-            .map_err(|_| ArchivedVecMapError::ValueCheckError)?;
-        if !values
-            .iter()
-            .zip(values.iter().skip(1))
-            .all(|((ak, _), (bk, _))| ak < bk)
-        {
-            return Err(ArchivedVecMapError::OrderCheckError);
+            .map_err(|e| ArchivedVecMapError::ValueCheckError(e.to_string()))?;
+        if let Some(index) = first_unsorted(values, |(k, _)| k) {
+            return Err(ArchivedVecMapError::OrderCheckError { index });
         };
         Ok(&*value)
     }
@@ -925,7 +2045,7 @@ mod tests {
     use super::*;
     use maplit::btreemap;
     use quickcheck::*;
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, BTreeSet};
     use OuterJoinArg::*;
 
     type Test = VecMap1<i32, i32>;
@@ -961,20 +2081,37 @@ mod tests {
         #[cfg(feature = "serde")]
         fn serde_roundtrip(reference: Test) -> bool {
             let bytes = serde_json::to_vec(&reference).unwrap();
-            let deser = serde_json::from_slice(&bytes).unwrap();
+            let deser: Test = serde_json::from_slice(&bytes).unwrap();
             reference == deser
         }
 
+        #[cfg(feature = "serde")]
+        fn serde_binary_roundtrip(a: Ref) -> bool {
+            let reference: Test = a.clone().into();
+            let bytes = serde_cbor::to_vec(&reference).unwrap();
+            let deser: Test = serde_cbor::from_slice(&bytes).unwrap();
+            // unsorted pairs are sorted on deserialization
+            let unsorted: Vec<(i32, i32)> = a.into_iter().rev().collect();
+            let bytes = serde_cbor::to_vec(&(false, unsorted)).unwrap();
+            let deser_unsorted: Test = serde_cbor::from_slice(&bytes).unwrap();
+            reference == deser && reference == deser_unsorted
+        }
+
         #[cfg(feature = "rkyv")]
         fn rkyv_roundtrip_unvalidated(a: Test) -> bool {
             use rkyv::*;
             use ser::Serializer;
+            fn hash_of(x: &impl Hash) -> u64 {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                x.hash(&mut hasher);
+                hash::Hasher::finish(&hasher)
+            }
             let mut serializer = ser::serializers::AllocSerializer::<256>::default();
             serializer.serialize_value(&a).unwrap();
             let bytes = serializer.into_serializer().into_inner();
             let archived = unsafe { rkyv::archived_root::<Test>(&bytes) };
             let deserialized: Test = archived.deserialize(&mut Infallible).unwrap();
-            a == deserialized
+            a == deserialized && hash_of(&a) == hash_of(archived)
         }
 
         #[cfg(feature = "rkyv_validated")]
@@ -990,6 +2127,71 @@ mod tests {
             a == deserialized
         }
 
+        #[cfg(feature = "rkyv_validated")]
+        #[quickcheck]
+        fn rkyv_validation_error(a: Vec<(i32, i32)>) -> bool {
+            use rkyv::{ser::Serializer, validation::CheckArchiveError};
+            // a vec has the same archived representation as a map, but does not have to be sorted
+            let mut serializer = rkyv::ser::serializers::AllocSerializer::<256>::default();
+            serializer.serialize_value(&a).unwrap();
+            let bytes = serializer.into_serializer().into_inner();
+            match (rkyv::check_archived_root::<Test>(&bytes), first_unsorted(&a, |(k, _)| k)) {
+                (Ok(archived), None) => archived.as_slice() == &a[..],
+                (Err(CheckArchiveError::CheckBytesError(ArchivedVecMapError::OrderCheckError { index })), Some(expected)) => {
+                    index == expected
+                }
+                _ => false,
+            }
+        }
+
+        #[cfg(feature = "rkyv_validated")]
+        #[quickcheck]
+        fn rkyv_nested_validated(entries: Vec<(u8, i64)>, key: u8, value: i64) -> bool {
+            use rkyv::*;
+            use ser::Serializer;
+            let mut a: BTreeMap<u8, BTreeSet<i64>> = BTreeMap::new();
+            for (k, v) in entries {
+                a.entry(k % 8).or_default().insert(v);
+            }
+            type Nested = VecMap<[(u8, VecSet<[i64; 2]>); 2]>;
+            let map: Nested = a.iter().map(|(k, v)| (*k, v.iter().cloned().collect())).collect();
+            let mut serializer = ser::serializers::AllocSerializer::<256>::default();
+            serializer.serialize_value(&map).unwrap();
+            let bytes = serializer.into_serializer().into_inner();
+            let archived = rkyv::check_archived_root::<Nested>(&bytes).unwrap();
+            let deserialized: Nested = archived.deserialize(&mut Infallible).unwrap();
+            let expected = a.get(&key).map(|set| set.contains(&value));
+            let actual = archived.get(&key).map(|set| set.contains(&value));
+            map == deserialized && expected == actual
+        }
+
+        #[cfg(feature = "rkyv_validated")]
+        #[quickcheck]
+        fn rkyv_nested_keys_validated(entries: Vec<(u8, u8, i64)>, key: u8) -> bool {
+            use rkyv::*;
+            use ser::Serializer;
+            // sets of up to 4 bits as keys, so that lookups find something
+            let bits = |x: u8| (0..4u8).filter(|i| x & (1 << i) != 0).collect::<BTreeSet<u8>>();
+            let mut a: BTreeMap<BTreeSet<u8>, BTreeMap<u8, i64>> = BTreeMap::new();
+            for (k, k1, v) in entries {
+                a.entry(bits(k)).or_default().insert(k1, v);
+            }
+            type Nested = VecMap<[(VecSet<[u8; 2]>, VecMap<[(u8, i64); 2]>); 2]>;
+            let map: Nested = a
+                .iter()
+                .map(|(k, v)| (k.iter().cloned().collect(), v.clone().into()))
+                .collect();
+            let mut serializer = ser::serializers::AllocSerializer::<256>::default();
+            serializer.serialize_value(&map).unwrap();
+            let bytes = serializer.into_serializer().into_inner();
+            let archived = rkyv::check_archived_root::<Nested>(&bytes).unwrap();
+            let deserialized: Nested = archived.deserialize(&mut Infallible).unwrap();
+            let key: Vec<u8> = bits(key).into_iter().collect();
+            let expected = a.iter().find(|(k, _)| k.iter().eq(key.iter())).map(|(_, v)| v.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>());
+            let actual = archived.get(&key[..]).map(|v| v.iter().cloned().collect::<Vec<_>>());
+            map == deserialized && expected == actual
+        }
+
         fn outer_join(a: Ref, b: Ref) -> bool {
             let expected: Test = outer_join_reference(&a, &b).into();
             let a: Test = a.into();
@@ -1035,4 +2237,376 @@ mod tests {
         assert_eq!(actual, expected);
         println!("{:?}", actual);
     }
+
+    quickcheck! {
+        fn retain_extract_if(a: Ref) -> bool {
+            let mut retained: Test = a.clone().into();
+            let mut extracted = retained.clone();
+            let removed = retained.retain(|(k, _)| k % 3 == 0);
+            let extracted_part = extracted.extract_if(|(k, _)| k % 3 != 0);
+            let mut all = retained.clone();
+            all.merge_with::<[(i32, i32); 1]>(extracted_part.clone());
+            retained == extracted && removed == extracted_part.len() && all == Test::from(a)
+        }
+
+        fn repair(a: Vec<(i32, i32)>) -> bool {
+            let mut map = Test::new(a.iter().cloned().collect());
+            let sorted = first_unsorted(&a, |(k, _)| k).is_none();
+            map.repair() != sorted && map == a.into_iter().collect::<Test>()
+        }
+
+        fn clone_from(a: Ref, b: Ref) -> bool {
+            let mut a: Test = a.into();
+            let b: Test = b.into();
+            a.clone_from(&b);
+            a == b
+        }
+
+        fn partition(a: Ref) -> bool {
+            let map: Test = a.clone().into();
+            let (even, odd) = map.partition(|(k, _)| k % 2 == 0);
+            let (expected_even, expected_odd): (Ref, Ref) = a.into_iter().partition(|(k, _)| k % 2 == 0);
+            even == Test::from(expected_even) && odd == Test::from(expected_odd)
+        }
+
+        fn retain_mut(a: Ref) -> bool {
+            let mut map: Test = a.clone().into();
+            let removed = map.retain_mut(|k, v| {
+                *v = v.wrapping_add(1);
+                k % 2 == 0
+            });
+            let expected: Test = a
+                .iter()
+                .filter(|(k, _)| *k % 2 == 0)
+                .map(|(k, v)| (*k, v.wrapping_add(1)))
+                .collect();
+            map == expected && removed == a.len() - expected.len()
+        }
+
+        fn accumulator(a: Ref, updates: Vec<(i32, i32)>) -> bool {
+            let mut expected = a.clone();
+            for (k, v) in updates.iter() {
+                *expected.entry(*k).or_default() ^= v;
+            }
+            let mut acc = Test::from(a).accumulator(|a, b| a ^ b);
+            for (k, v) in updates {
+                acc.add(k, v);
+            }
+            let expected: Test = expected.into();
+            acc.into_inner() == expected
+        }
+
+        fn staged(a: Ref, ops: Vec<(bool, i32, i32)>, threshold: u8) -> bool {
+            let mut expected = a.clone();
+            let mut staged = Test::from(a).staged(usize::from(threshold % 8));
+            for (insert, k, v) in ops {
+                let (k, v) = (k % 16, v);
+                if insert {
+                    expected.insert(k, v);
+                    staged.insert(k, v);
+                } else if expected.remove(&k) != staged.remove(&k) {
+                    return false;
+                }
+                if (0..16).any(|k| expected.get(&k) != staged.get(&k)) {
+                    return false;
+                }
+            }
+            let expected: Test = expected.into();
+            staged.into_inner() == expected
+        }
+
+        fn cast_storage(a: Ref) -> bool {
+            let map: Test = a.into();
+            let ptr = map.as_slice().as_ptr();
+            let spilled = map.len() > 1;
+            let cast: VecMap<[(i32, i32); 0]> = map.clone().cast_storage();
+            let back: Test = cast.clone().cast_storage();
+            cast.as_slice() == map.as_slice() && back == map && (!spilled || map.cast_storage::<[(i32, i32); 0]>().as_slice().as_ptr() == ptr)
+        }
+
+        fn try_from_sorted(a: Vec<(i32, i32)>) -> bool {
+            let entries: SmallVec<[(i32, i32); 1]> = a.iter().cloned().collect();
+            let sorted = a.windows(2).all(|w| w[0].0 < w[1].0);
+            match Test::try_from_sorted(entries) {
+                Ok(map) => sorted && map.as_slice() == a.as_slice(),
+                Err(e) => !sorted && a[e.index() - 1].0 >= a[e.index()].0 && e.into_inner().as_slice() == a.as_slice(),
+            }
+        }
+
+        fn into_parts_from_parts(a: Ref, keys: Vec<i32>, values: Vec<i32>) -> bool {
+            let map: Test = a.clone().into();
+            let (k, v): (SmallVec<[i32; 4]>, SmallVec<[i32; 4]>) = map.clone().into_parts();
+            let roundtrip = k.iter().copied().eq(a.keys().copied())
+                && v.iter().copied().eq(a.values().copied())
+                && Test::from_parts(k, v) == Ok(map);
+            let sorted = keys.windows(2).all(|w| w[0] < w[1]);
+            let parts = (SmallVec::<[i32; 4]>::from_vec(keys.clone()), SmallVec::<[i32; 4]>::from_vec(values.clone()));
+            let checked = match Test::from_parts(parts.0.clone(), parts.1.clone()) {
+                Ok(map) => sorted && keys.len() == values.len() && map.iter().map(|(k, v)| (*k, *v)).eq(keys.iter().copied().zip(values.iter().copied())),
+                Err(FromPartsError::LengthMismatch(p)) => keys.len() != values.len() && p == parts,
+                Err(FromPartsError::NotSorted(e)) => keys.len() == values.len() && !sorted && e.into_inner() == parts,
+            };
+            roundtrip && checked
+        }
+
+        fn map_keys(a: Ref, div: i8) -> bool {
+            let map: Test = a.clone().into();
+            let div = i32::from(div).abs().max(1);
+            // monotone, but not strictly, for div > 1
+            let f = |k: i32| i64::from(k.div_euclid(div));
+            let mut expected: BTreeMap<i64, i32> = BTreeMap::new();
+            for (k, v) in &a {
+                expected.insert(f(*k), *v);
+            }
+            let expected: VecMap1<i64, i32> = expected.into();
+            let distinct = expected.len() == a.len();
+            // not monotone
+            let neg: VecMap1<i32, i32> = map.clone().map_keys(|k: i32| k.wrapping_neg());
+            let expected_neg: VecMap1<i32, i32> = a.iter().map(|(k, v)| (k.wrapping_neg(), *v)).collect();
+            map.clone().map_keys(f) == expected
+                && neg == expected_neg
+                && match map.try_map_keys::<i64, [(i64, i32); 1], _>(f) {
+                    Ok(res) => distinct && res == expected,
+                    Err(e) => !distinct && a.keys().filter(|k| f(**k) == *e.key()).count() > 1,
+                }
+        }
+
+        fn union_max_min(a: Ref, b: Ref) -> bool {
+            let mut max: Test = a.clone().into();
+            let mut min: Test = a.clone().into();
+            max.union_max::<[(i32, i32); 1]>(b.clone().into());
+            min.union_min::<[(i32, i32); 1]>(b.clone().into());
+            let mut expected_max = a.clone();
+            let mut expected_min = a;
+            for (k, v) in b {
+                let x = expected_max.entry(k).or_insert(v);
+                *x = (*x).max(v);
+                let x = expected_min.entry(k).or_insert(v);
+                *x = (*x).min(v);
+            }
+            max == Test::from(expected_max) && min == Test::from(expected_min)
+        }
+
+        fn iter_double_ended(a: Ref, n: usize) -> bool {
+            let map: Test = a.clone().into();
+            let a: Vec<(i32, i32)> = a.into_iter().collect();
+            let n = n % (a.len() + 2);
+            map.iter().rev().eq(a.iter().rev())
+                && map.iter().nth(n) == a.get(n)
+                && map.iter().nth_back(n) == a.iter().rev().nth(n)
+                && map.iter().last() == a.last()
+        }
+
+        fn pop_first_last(a: Ref) -> bool {
+            let mut actual: Test = a.clone().into();
+            let mut expected = a;
+            let mut res = true;
+            while !expected.is_empty() {
+                res &= actual.pop_first() == expected.pop_first();
+                res &= actual.pop_last() == expected.pop_last();
+            }
+            res && actual.pop_first().is_none() && actual.pop_last().is_none()
+        }
+
+        fn group_by(pairs: Vec<(i32, i32)>) -> bool {
+            let mut expected: BTreeMap<i32, BTreeSet<i32>> = BTreeMap::new();
+            for (k, v) in pairs.iter() {
+                expected.entry(*k).or_default().insert(*v);
+            }
+            let actual: VecMap<[(i32, VecSet<[i32; 2]>); 2]> = VecMap::group_by(pairs);
+            actual.len() == expected.len()
+                && actual
+                    .iter()
+                    .zip(expected.iter())
+                    .all(|((k, vs), (ek, evs))| k == ek && vs.iter().eq(evs.iter()))
+        }
+
+        fn invert(a: Ref) -> bool {
+            let inverted: VecMap<[(i32, VecSet<[i32; 2]>); 2]> = Test::from(a.clone()).invert();
+            let keys = inverted.iter().map(|(_, keys)| keys.len()).sum::<usize>() == a.len();
+            keys && inverted
+                .iter()
+                .all(|(v, keys)| keys.iter().all(|k| a.get(k) == Some(v)))
+        }
+
+        fn multi_join(a: Ref, b: Ref, c: Ref) -> bool {
+            let mut expected: BTreeMap<i32, Vec<Option<i32>>> = BTreeMap::new();
+            for (i, map) in [&a, &b, &c].iter().enumerate() {
+                for (k, v) in map.iter() {
+                    expected.entry(*k).or_insert_with(|| vec![None; 3])[i] = Some(*v);
+                }
+            }
+            let a: Test = a.into();
+            let b: Test = b.into();
+            let c: Test = c.into();
+            let actual: VecMap<[(i32, Vec<Option<i32>>); 1]> =
+                VecMap::multi_join([&a, &b, &c], |_, values| Some(values.iter().map(|x| x.copied()).collect()));
+            let none: VecMap<[(i32, i32); 1]> = VecMap::multi_join([&a, &b, &c], |_, _| None);
+            actual == VecMap::from(expected) && none.is_empty()
+        }
+
+        fn remove_retain_keys(a: Ref, keys: Vec<i32>) -> bool {
+            let set: VecSet<[i32; 2]> = keys.iter().cloned().collect();
+            let mut removed: Test = a.clone().into();
+            removed.remove_keys(&set);
+            let mut retained: Test = a.clone().into();
+            retained.retain_keys(&set);
+            let expected_removed: Test = a.iter().filter(|(k, _)| !set.contains(k)).map(|(k, v)| (*k, *v)).collect();
+            let expected_retained: Test = a.iter().filter(|(k, _)| set.contains(k)).map(|(k, v)| (*k, *v)).collect();
+            removed == expected_removed && retained == expected_retained
+        }
+
+        fn slice_operand(a: Ref, b: Ref) -> bool {
+            let a: Test = a.into();
+            let b: Test = b.into();
+            let slice: &[(i32, i32)] = b.as_slice();
+            let f = |arg: OuterJoinArg<&i32, &i32, &i32>| match arg {
+                Left(_, a) => Some(*a),
+                Right(_, b) => Some(*b),
+                Both(_, a, b) => Some(a + b),
+            };
+            let expected: Test = a.outer_join(&b, f);
+            let actual: Test = a.outer_join(&slice, f);
+            let mut in_place = a.clone();
+            in_place.inner_join_with(&slice, |_, a, b| Some(a + b));
+            expected == actual && in_place == a.inner_join(&b, |_, a, b| Some(a + b))
+        }
+
+        fn map_values_in_place(a: Ref) -> bool {
+            let map: Test = a.into();
+            let expected: Test = map.clone().map_values(|v| v.wrapping_mul(3));
+            let mut in_place = map.clone();
+            in_place.map_values_in_place(|v| *v = v.wrapping_mul(3));
+            let mut transformed = map;
+            let ptr = transformed.as_slice().as_ptr();
+            transformed.transform_values(|v| v.wrapping_mul(3));
+            in_place == expected && transformed == expected && transformed.as_slice().as_ptr() == ptr
+        }
+
+        fn compact_values(a: Ref) -> bool {
+            let map: VecMap<[(i32, i32); 1]> = a.clone().into_iter().map(|(k, v)| (k, v % 4)).collect();
+            let compacted: VecMap<[(i32, Arc<i32>); 1]> = map.clone().compact_values();
+            let same_values = compacted.iter().map(|(k, v)| (*k, **v)).eq(map.iter().map(|(k, v)| (*k, *v)));
+            let shared = compacted.iter().all(|(_, x)| compacted.iter().all(|(_, y)| (x == y) == Arc::ptr_eq(x, y)));
+            same_values && shared
+        }
+
+        fn dedup_adjacent_values(a: Ref) -> bool {
+            let map: VecMap<[(i32, i32); 1]> = a.into_iter().map(|(k, v)| (k, v % 4)).collect();
+            let mut runs = map.clone();
+            let removed = runs.dedup_adjacent_values();
+            // every key can be found in the run length encoding
+            let found = map.iter().all(|(k, v)| {
+                let i = runs.as_slice().partition_point(|(x, _)| x <= k);
+                runs.as_slice()[i - 1].1 == *v
+            });
+            let no_runs = runs.as_slice().windows(2).all(|w| w[0].1 != w[1].1);
+            found && no_runs && removed + runs.len() == map.len()
+        }
+
+        fn partition_point(a: Ref, key: i32) -> bool {
+            let map: Test = a.clone().into();
+            let lower = AbstractVecMap::partition_point(&map, |(k, _)| *k < key);
+            lower == a.range(..key).count()
+                && AbstractVecMap::binary_search_by(&map.as_slice(), |(k, _)| k.cmp(&key)) == a.contains_key(&key).then_some(lower).ok_or(lower)
+        }
+
+        fn iter_after(a: Ref, key: i32) -> bool {
+            use std::ops::Bound;
+            let map: Test = a.clone().into();
+            let expected: Vec<_> = a.range((Bound::Excluded(key), Bound::Unbounded)).map(|(k, v)| (*k, *v)).collect();
+            let actual: Vec<_> = map.iter_after(&key).map(|(k, v)| (*k, *v)).collect();
+            expected == actual
+        }
+
+        fn range_prefix(a: BTreeMap<(i8, i8), i32>, prefix: i8) -> bool {
+            let map: VecMap<[((i8, i8), i32); 2]> = a.clone().into();
+            let expected: Vec<((i8, i8), i32)> = a.into_iter().filter(|((k1, _), _)| *k1 == prefix).collect();
+            map.range_prefix(&prefix) == &expected[..]
+        }
+
+        fn select_without(a: Ref, keys: Vec<i32>) -> bool {
+            let set: VecSet<[i32; 2]> = keys.iter().cloned().collect();
+            let map: Test = a.into();
+            let mut removed = map.clone();
+            removed.remove_keys(&set);
+            let mut retained = map.clone();
+            retained.retain_keys(&set);
+            let mut and_assign = map.clone();
+            and_assign &= &set;
+            let mut sub_assign = map.clone();
+            sub_assign -= &set;
+            map.select(&set) == retained
+                && map.without(&set) == removed
+                && &map & &set == retained
+                && &map - &set == removed
+                && and_assign == retained
+                && sub_assign == removed
+        }
+
+        fn hash_map_roundtrip(a: Ref) -> bool {
+            let hash_map: HashMap<i32, i32> = a.clone().into_iter().collect();
+            let map: Test = hash_map.clone().into();
+            map == Test::from(a) && HashMap::<i32, i32>::from(map) == hash_map
+        }
+
+        #[cfg(feature = "indexmap")]
+        fn index_map_roundtrip(a: Ref) -> bool {
+            let index_map: indexmap::IndexMap<i32, i32> = a.clone().into_iter().rev().collect();
+            let map: Test = index_map.into();
+            let sorted = indexmap::IndexMap::<i32, i32>::from(map.clone());
+            map == Test::from(a.clone()) && sorted.into_iter().eq(a.into_iter())
+        }
+    }
+
+    #[test]
+    fn capacity() {
+        let mut map: Test = VecMap::with_capacity(10);
+        assert!(map.capacity() >= 10);
+        map.reserve(100);
+        assert!(map.capacity() >= 100);
+        assert!(map.try_reserve(usize::MAX).is_err());
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn non_clone_keys() {
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct NoClone(String);
+        fn key(x: &str) -> NoClone {
+            NoClone(x.to_string())
+        }
+        let mut a: VecMap<[(NoClone, u32); 2]> = vec![(key("a"), 1), (key("b"), 2), (key("c"), 3)]
+            .into_iter()
+            .collect();
+        let b: VecMap<[(NoClone, u32); 2]> =
+            vec![(key("a"), 10), (key("b"), 20)].into_iter().collect();
+        a.left_join_with(&b, |_, v, w| Some(v + w.copied().unwrap_or_default()));
+        a.inner_join_with(&b, |_, v, w| if *w > 10 { Some(v) } else { None });
+        a.remove_keys(&vec![key("x")].into_iter().collect::<VecSet<[NoClone; 2]>>());
+        a.retain_keys(&vec![key("b")].into_iter().collect::<VecSet<[NoClone; 2]>>());
+        assert_eq!(a.get(&key("b")), Some(&22));
+        assert_eq!(a.len(), 1);
+    }
+
+    #[test]
+    fn slice_traits() {
+        let a: Test = btreemap! { 1 => 2, 3 => 4 }.into();
+        assert_eq!(a, vec![(1, 2), (3, 4)]);
+        assert_eq!(a, &[(1, 2), (3, 4)][..]);
+        assert_eq!(a.last(), Some(&(3, 4)));
+        assert_eq!(a.get(&3), Some(&4));
+    }
+
+    #[test]
+    fn get_many_mut() {
+        let mut map: Test = btreemap! { 1 => 1, 2 => 2, 3 => 3 }.into();
+        if let Some([a, b]) = map.get_many_mut([&3, &1]) {
+            std::mem::swap(a, b);
+        }
+        assert_eq!(map, Test::from(btreemap! { 1 => 3, 2 => 2, 3 => 1 }));
+        assert!(map.get_many_mut([&1, &1]).is_none());
+        assert!(map.get_many_mut([&1, &4]).is_none());
+        assert!(map.get_many_mut::<i32, 0>([]).is_some());
+    }
 }
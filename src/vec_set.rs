@@ -1,26 +1,34 @@
 use crate::dedup::Keep;
 pub use crate::iterators::VecSetIter;
 use crate::merge_state::{
-    CloneConverter, IdConverter, InPlaceMergeState, InPlaceSmallVecMergeStateRef, NoConverter,
+    CloneConverter, IdConverter, InPlaceIterMergeState, InPlaceMergeState,
+    InPlaceSmallVecMergeStateRef, NoConverter,
 };
 use crate::{
     dedup::sort_dedup,
-    merge_state::{BoolOpMergeState, MergeStateMut, SmallVecMergeState},
+    in_place::cast_storage,
+    merge_actions::merge_actions_by,
+    merge_state::{BoolOpMergeState, MergeStateMut, RelationMergeState, SmallVecMergeState},
+    CapacityPolicy, MergeAction, TransformInPlace,
 };
 use binary_merge::MergeOperation;
 #[cfg(feature = "rkyv_validated")]
 use bytecheck::CheckBytes;
 use core::{
+    borrow::Borrow,
     cmp::Ordering,
     fmt, hash,
     hash::Hash,
     iter::FromIterator,
-    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Sub, SubAssign},
+    ops::{
+        BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Bound, Deref, Range,
+        RangeBounds, Sub, SubAssign,
+    },
 };
-#[cfg(feature = "rkyv")]
-use rkyv::{validation::ArchiveContext, Archive};
-use smallvec::{Array, SmallVec};
-use std::collections::BTreeSet;
+#[cfg(feature = "rkyv_validated")]
+use rkyv::validation::ArchiveContext;
+use smallvec::{Array, CollectionAllocErr, SmallVec};
+use std::collections::{BTreeSet, HashSet};
 #[cfg(feature = "serde")]
 use {
     core::marker::PhantomData,
@@ -34,6 +42,14 @@ struct SetUnionOp;
 struct SetIntersectionOp;
 struct SetXorOp;
 struct SetDiffOpt;
+struct SetRelationOp;
+/// Keep the elements that are only in a, only in b, or in both, for arbitrary boolean functions of two sets
+#[cfg(feature = "total")]
+pub(crate) struct SetCombineOp {
+    pub a_only: bool,
+    pub b_only: bool,
+    pub both: bool,
+}
 
 /// A set backed by a [SmallVec] of elements.
 ///
@@ -68,6 +84,15 @@ struct SetDiffOpt;
 /// let a: VecSet<[u32; 4]> = (0..4).collect(); // does not allocate
 /// ```
 ///
+/// Unlike the std collections, VecSet does not implement `FromIterator<&T>` and `Extend<&T>` for `T: Copy`.
+/// The element type is an associated type of the array parameter, so such an impl would conflict with
+/// `FromIterator<A::Item>`. Use [copied](std::iter::Iterator::copied) instead.
+/// ```
+/// use vec_collections::VecSet;
+/// let elements = vec![3u64, 1, 2];
+/// let a: VecSet<[u64; 4]> = elements.iter().copied().collect();
+/// ```
+///
 /// # General usage
 /// ```
 /// use vec_collections::{VecSet, AbstractVecSet};
@@ -110,9 +135,135 @@ pub struct VecSet<A: Array>(SmallVec<A>);
 /// This is a good default, since for usize sized types, 2 is the max you can fit in without making the struct larger.
 pub type VecSet2<T> = VecSet<[T; 2]>;
 
+/// The relation between two sets, as computed by [relation](AbstractVecSet::relation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SetRelation {
+    /// Both sets contain the same elements
+    Equal,
+    /// All elements of the first set are contained in the second set, which has additional elements
+    Subset,
+    /// All elements of the second set are contained in the first set, which has additional elements
+    Superset,
+    /// The sets have no elements in common
+    Disjoint,
+    /// The sets have some elements in common, and each set has elements that the other set does not have
+    Overlapping,
+}
+
+impl SetRelation {
+    /// Classify a relation, given which kinds of elements exist
+    pub(crate) fn new(a_only: bool, b_only: bool, both: bool) -> Self {
+        match (a_only, b_only, both) {
+            (false, false, _) => Self::Equal,
+            (false, true, _) => Self::Subset,
+            (true, false, _) => Self::Superset,
+            (true, true, false) => Self::Disjoint,
+            (true, true, true) => Self::Overlapping,
+        }
+    }
+}
+
+/// Error when creating a collection from elements that are not strictly sorted.
+///
+/// Contains the rejected elements, so they can be reused, e.g. by sorting them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotSortedError<T> {
+    index: usize,
+    elements: T,
+}
+
+impl<T> NotSortedError<T> {
+    pub(crate) fn new(index: usize, elements: T) -> Self {
+        Self { index, elements }
+    }
+
+    /// The index of the first element that is not greater than its predecessor
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The rejected elements
+    pub fn into_inner(self) -> T {
+        self.elements
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for NotSortedError<T> {}
+
+impl<T> fmt::Display for NotSortedError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "elements are not strictly sorted at index {}",
+            self.index
+        )
+    }
+}
+
+/// Below this number of elements, lookups scan all elements instead of doing a binary search
+const LINEAR_SEARCH_LEN: usize = 16;
+
+/// True if a strictly sorted slice contains the value
+///
+/// Small slices are scanned without branches, comparing every element with the value. For primitive elements the
+/// scan is vectorized, and there are no mispredicted branches when lookups come in an unpredictable order.
+#[inline]
+pub(crate) fn sorted_contains<T: Ord>(elements: &[T], value: &T) -> bool {
+    if elements.len() < LINEAR_SEARCH_LEN {
+        elements.iter().fold(false, |found, x| found | (x == value))
+    } else {
+        elements.binary_search(value).is_ok()
+    }
+}
+
+/// Search a strictly sorted slice by key, with the same result as [slice::binary_search_by]
+///
+/// Small slices are scanned without branches, counting the elements that are smaller than the key. For primitive
+/// keys the scan is vectorized, and there are no mispredicted branches when lookups come in an unpredictable order.
+#[inline]
+pub(crate) fn search_by_key<T, K: Ord + ?Sized>(
+    elements: &[T],
+    key: &K,
+    f: impl Fn(&T) -> &K,
+) -> Result<usize, usize> {
+    if elements.len() < LINEAR_SEARCH_LEN {
+        let index = elements.iter().map(|x| (f(x) < key) as usize).sum();
+        match elements.get(index) {
+            Some(x) if f(x) == key => Ok(index),
+            _ => Err(index),
+        }
+    } else {
+        elements.binary_search_by(|x| f(x).cmp(key))
+    }
+}
+
+/// The index of the first element that is not strictly greater than its predecessor, if any
+pub(crate) fn first_unsorted<T, K: Ord>(elements: &[T], key: impl Fn(&T) -> &K) -> Option<usize> {
+    // use Ord, not PartialOrd, to be consistent with the merge operations
+    elements
+        .windows(2)
+        .position(|w| key(&w[0]).cmp(key(&w[1])) != Ordering::Less)
+        .map(|i| i + 1)
+}
+
+/// Check that the elements are strictly sorted, if the `debug_invariants` feature is enabled in a debug build.
+///
+/// This panics at the first operation that sees broken invariants, instead of giving wrong answers later.
+#[inline]
+pub(crate) fn check_sorted<T, K: Ord>(elements: &[T], key: impl Fn(&T) -> &K) {
+    if cfg!(all(debug_assertions, feature = "debug_invariants")) {
+        if let Some(index) = first_unsorted(elements, key) {
+            panic!(
+                "invariant violated: elements are not strictly sorted at index {}",
+                index
+            );
+        }
+    }
+}
+
 /// An abstract vec set
 ///
-/// this is implemented by VecSet and ArchivedVecSet, so they are interoperable.
+/// this is implemented by VecSet, ArchivedVecSet and strictly sorted slices, so they are interoperable.
 pub trait AbstractVecSet<T: Ord> {
     // the elements as a slice, must be strictly ordered
     fn as_slice(&self) -> &[T];
@@ -120,7 +271,38 @@ pub trait AbstractVecSet<T: Ord> {
         self.as_slice().is_empty()
     }
     fn contains(&self, value: &T) -> bool {
-        self.as_slice().binary_search(value).is_ok()
+        sorted_contains(self.as_slice(), value)
+    }
+
+    /// The index of the first element for which the predicate is false, see [slice::partition_point].
+    ///
+    /// The predicate must be true for a prefix of the elements, e.g. `|x| x < value` for the first element that
+    /// is greater than or equal to `value`.
+    fn partition_point(&self, pred: impl FnMut(&T) -> bool) -> usize {
+        self.as_slice().partition_point(pred)
+    }
+
+    /// Binary search with a comparator function, see [slice::binary_search_by].
+    ///
+    /// Since the elements are unique, the index of a match is unique as well.
+    fn binary_search_by(&self, f: impl FnMut(&T) -> Ordering) -> Result<usize, usize> {
+        self.as_slice().binary_search_by(f)
+    }
+
+    /// true if this set contains all of the given values.
+    ///
+    /// The values must be strictly sorted, like the elements of a set. This is done in a single merge instead of
+    /// a binary search per value, so it is much faster for a large number of values.
+    fn contains_all(&self, sorted: &[T]) -> bool {
+        !BoolOpMergeState::merge(sorted, self.as_slice(), SetDiffOpt)
+    }
+
+    /// true if this set contains any of the given values.
+    ///
+    /// The values must be strictly sorted, like the elements of a set. This is done in a single merge instead of
+    /// a binary search per value, so it is much faster for a large number of values.
+    fn contains_any(&self, sorted: &[T]) -> bool {
+        BoolOpMergeState::merge(sorted, self.as_slice(), SetIntersectionOp)
     }
 
     /// true if this set has no common elements with another set.
@@ -142,6 +324,17 @@ pub trait AbstractVecSet<T: Ord> {
         !BoolOpMergeState::merge(that.as_slice(), self.as_slice(), SetDiffOpt)
     }
 
+    /// The relation of this set to another set, computed in a single pass.
+    ///
+    /// This is cheaper than calling e.g. [is_subset](AbstractVecSet::is_subset) and
+    /// [is_superset](AbstractVecSet::is_superset) separately. If more than one relation applies, the first
+    /// one in the order of the variants of [SetRelation] is returned, so e.g. the empty set is a subset of
+    /// any non-empty set.
+    fn relation(&self, that: &impl AbstractVecSet<T>) -> SetRelation {
+        let (a_only, b_only, both) = relation_flags(self.as_slice(), that.as_slice());
+        SetRelation::new(a_only, b_only, both)
+    }
+
     fn union<A: Array<Item = T>>(&self, that: &impl AbstractVecSet<T>) -> VecSet<A>
     where
         T: Clone,
@@ -191,9 +384,19 @@ pub trait AbstractVecSet<T: Ord> {
     }
 
     /// An iterator that returns references to the items of this set in sorted order
-    fn iter(&self) -> VecSetIter<core::slice::Iter<T>> {
+    fn iter(&self) -> VecSetIter<core::slice::Iter<'_, T>> {
         VecSetIter::new(self.as_slice().iter())
     }
+
+    /// An iterator over the items of this set that are strictly greater than the given value
+    ///
+    /// This is a binary search, so it can be used to resume iteration at the last returned item, e.g. for paging
+    /// over a large archived set, without skipping all previous items.
+    fn iter_after(&self, value: &T) -> VecSetIter<core::slice::Iter<'_, T>> {
+        let elements = self.as_slice();
+        let start = elements.partition_point(|x| x <= value);
+        VecSetIter::new(elements[start..].iter())
+    }
 }
 
 impl<A: Array> AbstractVecSet<A::Item> for VecSet<A>
@@ -201,16 +404,29 @@ where
     A::Item: Ord,
 {
     fn as_slice(&self) -> &[A::Item] {
+        check_sorted(&self.0, |x| x);
         self.0.as_ref()
     }
 }
 
+/// A strictly sorted slice can be used as a set operand without copying it into a [VecSet] first.
+///
+/// The slice is not checked in release builds, so using an unsorted slice gives wrong results. If the `debug_invariants`
+/// feature is enabled in a debug build, [as_slice](AbstractVecSet::as_slice) checks it and panics if it is not sorted.
+impl<T: Ord> AbstractVecSet<T> for &[T] {
+    fn as_slice(&self) -> &[T] {
+        check_sorted(self, |x| x);
+        self
+    }
+}
+
 #[cfg(feature = "rkyv")]
 impl<T> AbstractVecSet<T> for ArchivedVecSet<T>
 where
     T: Ord,
 {
     fn as_slice(&self) -> &[T] {
+        check_sorted(&self.0, |x| x);
         self.0.as_ref()
     }
 }
@@ -225,6 +441,11 @@ impl<T: Clone, A: Array<Item = T>> Clone for VecSet<A> {
     fn clone(&self) -> Self {
         Self(self.0.clone())
     }
+
+    /// Reuses the allocation of `self`, and the resources of the existing elements, where possible.
+    fn clone_from(&mut self, source: &Self) {
+        self.0.clone_from(&source.0)
+    }
 }
 
 impl<T: Hash, A: Array<Item = T>> Hash for VecSet<A> {
@@ -247,6 +468,11 @@ impl<T: PartialOrd, A: Array<Item = T>> PartialOrd for VecSet<A> {
     }
 }
 
+/// Sets are ordered lexicographically by their elements in ascending order, like the corresponding sorted slices.
+///
+/// So the empty set is the smallest set, and `{1, 2}` < `{1, 3}` < `{2}`. This order does not depend on the inline
+/// capacity of the set and is guaranteed not to change, so it can be relied upon for persisted indexes. See
+/// [cmp_by_len_then_lex](VecSet::cmp_by_len_then_lex) for an order by size.
 impl<T: Ord, A: Array<Item = T>> Ord for VecSet<A> {
     fn cmp(&self, other: &Self) -> Ordering {
         self.0.cmp(&other.0)
@@ -269,17 +495,33 @@ impl<A: Array> VecSet<A> {
         Self::new_unsafe(SmallVec::new())
     }
     /// An iterator that returns references to the items of this set in sorted order
-    pub fn iter(&self) -> VecSetIter<core::slice::Iter<A::Item>> {
+    pub fn iter(&self) -> VecSetIter<core::slice::Iter<'_, A::Item>> {
         VecSetIter::new(self.0.iter())
     }
     /// The underlying memory as a slice.
-    fn as_slice(&self) -> &[A::Item] {
+    pub fn as_slice(&self) -> &[A::Item] {
         &self.0
     }
     /// The number of elements in the set.
     pub fn len(&self) -> usize {
         self.0.len()
     }
+    /// The empty set with space for at least `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::new_unsafe(SmallVec::with_capacity(capacity))
+    }
+    /// Reserve space for at least `additional` more elements.
+    ///
+    /// In place operations like [union_with](VecSet::union_with) use the spare capacity as the gap between the result
+    /// and the remaining elements, so reserving the expected growth once avoids reallocations when merging many
+    /// batches.
+    pub fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional)
+    }
+    /// Reserve space for at least `additional` more elements, returning an error if the allocation fails.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), CollectionAllocErr> {
+        self.0.try_reserve(additional)
+    }
     /// Shrink the underlying SmallVec<T> to fit.
     pub fn shrink_to_fit(&mut self) {
         self.0.shrink_to_fit()
@@ -288,21 +530,155 @@ impl<A: Array> VecSet<A> {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+    /// Release excess capacity of the underlying SmallVec according to a [CapacityPolicy].
+    pub fn apply_capacity_policy(&mut self, policy: CapacityPolicy) {
+        policy.apply(&mut self.0)
+    }
+    /// Perform one or more operations on the set, then apply a [CapacityPolicy].
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::{CapacityPolicy, VecSet};
+    /// let mut a: VecSet<[u32; 4]> = (0..1000).collect();
+    /// let b: VecSet<[u32; 4]> = (0..10).collect();
+    /// a.with_capacity_policy(CapacityPolicy::ShrinkAbove(2), |a| a.intersection_with(&b));
+    /// assert!(a.capacity() <= 2 * a.len());
+    /// ```
+    pub fn with_capacity_policy<R>(
+        &mut self,
+        policy: CapacityPolicy,
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let res = f(self);
+        self.apply_capacity_policy(policy);
+        res
+    }
+    /// The capacity of the underlying SmallVec.
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+    /// true if the elements are stored on the heap instead of inline
+    pub fn spilled(&self) -> bool {
+        self.0.spilled()
+    }
     /// Returns the wrapped SmallVec.
     pub fn into_inner(self) -> SmallVec<A> {
         self.0
     }
+    /// Converts to a set with a different inline capacity.
+    ///
+    /// If the elements are on the heap, the heap buffer is reused instead of copying the elements, unless they fit
+    /// into the inline storage of the target type.
+    pub fn cast_storage<B: Array<Item = A::Item>>(self) -> VecSet<B> {
+        VecSet(cast_storage(self.0))
+    }
+    /// Removes and returns the smallest element, if any.
+    ///
+    /// This is O(N), since the remaining elements have to be moved.
+    pub fn pop_first(&mut self) -> Option<A::Item> {
+        if self.0.is_empty() {
+            None
+        } else {
+            Some(self.0.remove(0))
+        }
+    }
+    /// Removes and returns the largest element, if any. This is O(1).
+    pub fn pop_last(&mut self) -> Option<A::Item> {
+        self.0.pop()
+    }
+    /// Keep only the `n` smallest elements
+    pub(crate) fn truncate(&mut self, n: usize) {
+        self.0.truncate(n)
+    }
+    /// Keep only the `n` largest elements
+    pub(crate) fn truncate_front(&mut self, n: usize) {
+        if self.0.len() > n {
+            let excess = self.0.len() - n;
+            self.0.drain(..excess);
+        }
+    }
 }
 
 impl<A: Array> VecSet<A>
 where
     A::Item: Ord,
 {
+    /// Creates a set from strictly sorted elements, without sorting them again.
+    ///
+    /// This is O(1). If the elements are not strictly sorted, the set will not work correctly. This is checked
+    /// in debug builds.
+    pub fn from_sorted_unchecked(elements: SmallVec<A>) -> Self {
+        debug_assert!(first_unsorted(&elements, |x| x).is_none());
+        Self::new_unsafe(elements)
+    }
+
+    /// Creates a set from strictly sorted elements, or returns them if they are not strictly sorted.
+    ///
+    /// This is O(N), so it is cheaper than collecting if the elements are known to be sorted, e.g. when
+    /// deserializing.
+    pub fn try_from_sorted(elements: SmallVec<A>) -> Result<Self, NotSortedError<SmallVec<A>>> {
+        match first_unsorted(&elements, |x| x) {
+            Some(index) => Err(NotSortedError::new(index, elements)),
+            None => Ok(Self::new_unsafe(elements)),
+        }
+    }
+
+    /// Re-establish the invariants of the set, by sorting and removing duplicates.
+    ///
+    /// This is only needed to recover from known bad input, e.g. via [from_sorted_unchecked](VecSet::from_sorted_unchecked)
+    /// or elements whose order changed. On duplicates, the first element is kept. Returns true if the set had to
+    /// be repaired.
+    pub fn repair(&mut self) -> bool {
+        if first_unsorted(&self.0, |x| x).is_none() {
+            return false;
+        }
+        let elements = std::mem::take(&mut self.0);
+        self.0 = sort_dedup(elements.into_iter(), Keep::First);
+        true
+    }
+
+    /// Compare sets by their number of elements first, and then lexicographically like [Ord].
+    ///
+    /// This is a total order, consistent with equality, and guaranteed not to change. To use it for the keys of a
+    /// map, wrap the sets in [OrderedBy](crate::OrderedBy) with the [LenThenLex](crate::LenThenLex) comparator.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::VecSet;
+    /// use std::cmp::Ordering;
+    /// let a: VecSet<[u32; 4]> = vec![2].into();
+    /// let b: VecSet<[u32; 4]> = vec![1, 3].into();
+    /// assert_eq!(a.cmp(&b), Ordering::Greater);
+    /// assert_eq!(a.cmp_by_len_then_lex(&b), Ordering::Less);
+    /// ```
+    pub fn cmp_by_len_then_lex<B: Array<Item = A::Item>>(&self, that: &VecSet<B>) -> Ordering {
+        self.len()
+            .cmp(&that.len())
+            .then_with(|| self.as_slice().cmp(that.as_slice()))
+    }
+
+    /// Check the invariants, if the `debug_invariants` feature is enabled in a debug build
+    #[inline]
+    pub(crate) fn check_invariants(&self) {
+        check_sorted(&self.0, |x| x)
+    }
+
+    /// true if the set contains the element. Time complexity is O(log N). Binary search, or a branchless scan for
+    /// sets with less than 16 elements.
+    ///
+    /// This is the same as [AbstractVecSet::contains], but does not require the trait to be in scope.
+    /// Otherwise the linear [slice::contains] would be used via deref.
+    pub fn contains(&self, value: &A::Item) -> bool {
+        self.check_invariants();
+        sorted_contains(&self.0, value)
+    }
+
     /// insert an element.
     ///
     /// The time complexity of this is O(N), so building a large set using single element inserts will be slow!
     /// Prefer using [from_iter](std::iter::FromIterator::from_iter) when building a large VecSet from elements.
     pub fn insert(&mut self, that: A::Item) -> bool {
+        self.check_invariants();
         match self.0.binary_search(&that) {
             Ok(index) => {
                 self.0[index] = that;
@@ -315,11 +691,41 @@ where
         }
     }
 
+    /// Insert an element, returning the equal element that was replaced, if any.
+    ///
+    /// This is useful when equality only considers part of an element, like for [BTreeSet::replace](std::collections::BTreeSet::replace).
+    pub fn replace(&mut self, that: A::Item) -> Option<A::Item> {
+        self.check_invariants();
+        match self.0.binary_search(&that) {
+            Ok(index) => Some(std::mem::replace(&mut self.0[index], that)),
+            Err(index) => {
+                self.0.insert(index, that);
+                None
+            }
+        }
+    }
+
+    /// Get a reference to the element equal to `that`, inserting it if it is not present.
+    ///
+    /// Unlike [insert](VecSet::insert), this never overwrites an existing element.
+    pub fn get_or_insert(&mut self, that: A::Item) -> &A::Item {
+        self.check_invariants();
+        let index = match self.0.binary_search(&that) {
+            Ok(index) => index,
+            Err(index) => {
+                self.0.insert(index, that);
+                index
+            }
+        };
+        &self.0[index]
+    }
+
     /// Remove an element.
     ///
     /// The time complexity of this is O(N), so removing many elements using single element removes inserts will be slow!
     /// Prefer using [retain](VecSet::retain) when removing a large number of elements.
     pub fn remove(&mut self, that: &A::Item) -> bool {
+        self.check_invariants();
         if let Ok(index) = self.0.binary_search(that) {
             self.0.remove(index);
             true
@@ -329,8 +735,117 @@ where
     }
 
     /// Retain all elements matching a predicate.
-    pub fn retain<F: FnMut(&A::Item) -> bool>(&mut self, mut f: F) {
-        self.0.retain(|entry| f(entry))
+    ///
+    /// Returns the number of removed elements.
+    pub fn retain<F: FnMut(&A::Item) -> bool>(&mut self, mut f: F) -> usize {
+        let n = self.0.len();
+        self.0.retain(|entry| f(entry));
+        n - self.0.len()
+    }
+
+    /// Remove all elements matching a predicate, and return them as a new set.
+    ///
+    /// This is the opposite of [retain](VecSet::retain): elements for which the predicate returns true are removed.
+    ///
+    /// The retained elements stay in place, so only the removed elements are moved to new storage.
+    pub fn extract_if<F: FnMut(&A::Item) -> bool>(&mut self, mut f: F) -> Self {
+        let mut removed = SmallVec::new();
+        self.0.flat_map_in_place(|x| {
+            if f(&x) {
+                removed.push(x);
+                None
+            } else {
+                Some(x)
+            }
+        });
+        Self::new_unsafe(removed)
+    }
+
+    /// Split the set into the elements matching a predicate and the elements not matching it, in a single pass.
+    ///
+    /// Like [Iterator::partition], the first set contains the elements for which the predicate returns true. It reuses the
+    /// allocation of `self`.
+    pub fn partition<F: FnMut(&A::Item) -> bool>(mut self, mut f: F) -> (Self, Self) {
+        let mut rejected = SmallVec::new();
+        self.0.flat_map_in_place(|x| {
+            if f(&x) {
+                Some(x)
+            } else {
+                rejected.push(x);
+                None
+            }
+        });
+        (self, Self::new_unsafe(rejected))
+    }
+
+    /// The [MergeAction]s to merge this set with another set.
+    ///
+    /// This describes how the elements of both sets interleave, e.g. to replicate a union on another copy of this set.
+    pub fn merge_actions<'a>(
+        &'a self,
+        that: &'a impl AbstractVecSet<A::Item>,
+    ) -> impl Iterator<Item = MergeAction> + 'a {
+        self.check_invariants();
+        merge_actions_by(self.as_slice(), that.as_slice(), |a, b| a.cmp(b))
+    }
+
+    /// Remove all elements within a range.
+    ///
+    /// This only needs two binary searches and a single move of the elements after the range.
+    /// Returns the number of removed elements.
+    pub fn remove_range<R: RangeBounds<A::Item>>(&mut self, range: R) -> usize {
+        let range = self.index_range(range);
+        let n = range.len();
+        self.0.drain(range);
+        n
+    }
+
+    /// In place intersection with another set. This never copies elements, so it does not require Clone.
+    ///
+    /// For sets of `u32` or `u64`, the opt-in `simd::intersection` of the `simd` feature is faster.
+    pub fn intersection_with(&mut self, that: &impl AbstractVecSet<A::Item>) {
+        self.check_invariants();
+        InPlaceSmallVecMergeStateRef::merge(
+            &mut self.0,
+            &that.as_slice(),
+            SetIntersectionOp,
+            NoConverter,
+        );
+    }
+
+    /// In place union with the elements of an iterator, in any order and possibly with duplicates.
+    ///
+    /// The elements are sorted and deduplicated in a single batch, which is then merged in a single pass. Sorting
+    /// needs the elements in a temporary buffer, so this is not free of intermediate storage. For a [BTreeSet],
+    /// which is already sorted, use `|=` instead, which merges its elements directly.
+    ///
+    /// This is the same as [Extend], but more discoverable next to the other `*_with` methods.
+    pub fn union_with_iter(&mut self, iter: impl IntoIterator<Item = A::Item>) {
+        *self |= Self::from_iter(iter);
+    }
+
+    /// In place difference with another set. This never copies elements, so it does not require Clone.
+    pub fn difference_with(&mut self, that: &impl AbstractVecSet<A::Item>) {
+        self.check_invariants();
+        InPlaceSmallVecMergeStateRef::merge(&mut self.0, &that.as_slice(), SetDiffOpt, NoConverter);
+    }
+
+    /// The range of indices of the elements within a range of values.
+    ///
+    /// An inverted range gives an empty range of indices.
+    fn index_range<R: RangeBounds<A::Item>>(&self, range: R) -> Range<usize> {
+        let elements = self.0.as_slice();
+        let start = match range.start_bound() {
+            Bound::Included(x) => elements.partition_point(|e| e < x),
+            Bound::Excluded(x) => elements.partition_point(|e| e <= x),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(x) => elements.partition_point(|e| e <= x),
+            Bound::Excluded(x) => elements.partition_point(|e| e < x),
+            Bound::Unbounded => elements.len(),
+        };
+        start..end.max(start)
     }
 
     /// creates a set from a vec.
@@ -408,37 +923,54 @@ impl<T: Ord + Clone, A: Array<Item = T>, B: Array<Item = T>> Sub<&VecSet<B>> for
 
 impl<T: Ord, A: Array<Item = T>, B: Array<Item = T>> BitAndAssign<VecSet<B>> for VecSet<A> {
     fn bitand_assign(&mut self, that: VecSet<B>) {
+        self.check_invariants();
+        that.check_invariants();
         InPlaceMergeState::merge(&mut self.0, that.0, SetIntersectionOp, IdConverter);
     }
 }
 
-impl<T: Ord + Clone, A: Array<Item = T>, B: Array<Item = T>> BitAndAssign<&VecSet<B>>
-    for VecSet<A>
-{
+impl<T: Ord, A: Array<Item = T>, B: Array<Item = T>> BitAndAssign<&VecSet<B>> for VecSet<A> {
     fn bitand_assign(&mut self, that: &VecSet<B>) {
-        InPlaceSmallVecMergeStateRef::merge(
-            &mut self.0,
-            &that.0,
-            SetIntersectionOp,
-            CloneConverter,
-        );
+        self.intersection_with(that)
     }
 }
 
 impl<T: Ord, A: Array<Item = T>, B: Array<Item = T>> BitOrAssign<VecSet<B>> for VecSet<A> {
     fn bitor_assign(&mut self, that: VecSet<B>) {
+        self.check_invariants();
+        that.check_invariants();
         InPlaceMergeState::merge(&mut self.0, that.0, SetUnionOp, IdConverter);
     }
 }
 
+/// Merges the elements of a BTreeSet in their sorted order, without collecting them into a temporary set first
+impl<T: Ord, A: Array<Item = T>> BitOrAssign<BTreeSet<T>> for VecSet<A> {
+    fn bitor_assign(&mut self, that: BTreeSet<T>) {
+        self.check_invariants();
+        InPlaceIterMergeState::union(&mut self.0, that.into_iter());
+    }
+}
+
+/// The elements of a HashSet are not sorted, so they are sorted in a temporary buffer, like in
+/// [union_with_iter](VecSet::union_with_iter)
+impl<T: Ord, A: Array<Item = T>, S> BitOrAssign<HashSet<T, S>> for VecSet<A> {
+    fn bitor_assign(&mut self, that: HashSet<T, S>) {
+        self.union_with_iter(that);
+    }
+}
+
 impl<T: Ord + Clone, A: Array<Item = T>, B: Array<Item = T>> BitOrAssign<&VecSet<B>> for VecSet<A> {
     fn bitor_assign(&mut self, that: &VecSet<B>) {
+        self.check_invariants();
+        that.check_invariants();
         InPlaceSmallVecMergeStateRef::merge(&mut self.0, &that.0, SetUnionOp, CloneConverter);
     }
 }
 
 impl<T: Ord, A: Array<Item = T>, B: Array<Item = T>> BitXorAssign<VecSet<B>> for VecSet<A> {
     fn bitxor_assign(&mut self, that: VecSet<B>) {
+        self.check_invariants();
+        that.check_invariants();
         InPlaceMergeState::merge(&mut self.0, that.0, SetXorOp, IdConverter);
     }
 }
@@ -447,19 +979,23 @@ impl<T: Ord + Clone, A: Array<Item = T>, B: Array<Item = T>> BitXorAssign<&VecSe
     for VecSet<A>
 {
     fn bitxor_assign(&mut self, that: &VecSet<B>) {
+        self.check_invariants();
+        that.check_invariants();
         InPlaceSmallVecMergeStateRef::merge(&mut self.0, &that.0, SetXorOp, CloneConverter);
     }
 }
 
 impl<T: Ord, A: Array<Item = T>, B: Array<Item = T>> SubAssign<VecSet<B>> for VecSet<A> {
     fn sub_assign(&mut self, that: VecSet<B>) {
+        self.check_invariants();
+        that.check_invariants();
         InPlaceMergeState::merge(&mut self.0, that.0, SetDiffOpt, IdConverter);
     }
 }
 
-impl<T: Ord + Clone, A: Array<Item = T>, B: Array<Item = T>> SubAssign<&VecSet<B>> for VecSet<A> {
+impl<T: Ord, A: Array<Item = T>, B: Array<Item = T>> SubAssign<&VecSet<B>> for VecSet<A> {
     fn sub_assign(&mut self, that: &VecSet<B>) {
-        InPlaceSmallVecMergeStateRef::merge(&mut self.0, &that.0, SetDiffOpt, CloneConverter);
+        self.difference_with(that)
     }
 }
 
@@ -469,6 +1005,38 @@ impl<A: Array> AsRef<[A::Item]> for VecSet<A> {
     }
 }
 
+impl<A: Array> Deref for VecSet<A> {
+    type Target = [A::Item];
+
+    fn deref(&self) -> &[A::Item] {
+        self.as_slice()
+    }
+}
+
+impl<A: Array> Borrow<[A::Item]> for VecSet<A> {
+    fn borrow(&self) -> &[A::Item] {
+        self.as_slice()
+    }
+}
+
+impl<T: PartialEq, A: Array<Item = T>> PartialEq<[T]> for VecSet<A> {
+    fn eq(&self, other: &[T]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+impl<T: PartialEq, A: Array<Item = T>> PartialEq<&[T]> for VecSet<A> {
+    fn eq(&self, other: &&[T]) -> bool {
+        self.as_slice() == *other
+    }
+}
+
+impl<T: PartialEq, A: Array<Item = T>> PartialEq<Vec<T>> for VecSet<A> {
+    fn eq(&self, other: &Vec<T>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
 impl<T: Ord, A: Array<Item = T>> From<Vec<T>> for VecSet<A> {
     fn from(vec: Vec<T>) -> Self {
         Self::from_vec(vec)
@@ -504,6 +1072,29 @@ impl<T: Ord, A: Array<Item = T>> Extend<T> for VecSet<A> {
     }
 }
 
+#[cfg(feature = "rand")]
+impl<A: Array> VecSet<A> {
+    /// A uniformly random element, or None if the set is empty
+    pub fn choose<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Option<&A::Item> {
+        rand::seq::SliceRandom::choose(self.as_slice(), rng)
+    }
+
+    /// `k` distinct random elements in ascending order, or all elements if the set has at most `k` elements.
+    ///
+    /// Only the chosen indices are allocated, not copies of the elements.
+    pub fn sample<R: rand::Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        k: usize,
+    ) -> impl Iterator<Item = &A::Item> + '_ {
+        let elements = self.as_slice();
+        let mut indices =
+            rand::seq::index::sample(rng, elements.len(), k.min(elements.len())).into_vec();
+        indices.sort_unstable();
+        indices.into_iter().map(move |i| &elements[i])
+    }
+}
+
 impl<T: Ord, I: MergeStateMut<A = T, B = T>> MergeOperation<I> for SetUnionOp {
     fn cmp(&self, a: &T, b: &T) -> Ordering {
         a.cmp(b)
@@ -549,6 +1140,27 @@ impl<T: Ord, I: MergeStateMut<A = T, B = T>> MergeOperation<I> for SetDiffOpt {
     }
 }
 
+/// Which kinds of elements exist: elements only in a, elements only in b, and elements in both.
+pub(crate) fn relation_flags<T: Ord>(a: &[T], b: &[T]) -> (bool, bool, bool) {
+    let m = RelationMergeState::merge(a, b, SetRelationOp);
+    (m.a_only, m.b_only, m.both)
+}
+
+impl<'a, T: Ord> MergeOperation<RelationMergeState<'a, T, T>> for SetRelationOp {
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut RelationMergeState<'a, T, T>, n: usize) -> bool {
+        m.advance_a(n)
+    }
+    fn from_b(&self, m: &mut RelationMergeState<'a, T, T>, n: usize) -> bool {
+        m.advance_b(n)
+    }
+    fn collision(&self, m: &mut RelationMergeState<'a, T, T>) -> bool {
+        m.advance_both()
+    }
+}
+
 impl<T: Ord, I: MergeStateMut<A = T, B = T>> MergeOperation<I> for SetXorOp {
     fn cmp(&self, a: &T, b: &T) -> Ordering {
         a.cmp(b)
@@ -564,17 +1176,39 @@ impl<T: Ord, I: MergeStateMut<A = T, B = T>> MergeOperation<I> for SetXorOp {
     }
 }
 
+#[cfg(feature = "total")]
+impl<T: Ord, I: MergeStateMut<A = T, B = T>> MergeOperation<I> for SetCombineOp {
+    fn cmp(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+    fn from_a(&self, m: &mut I, n: usize) -> bool {
+        m.advance_a(n, self.a_only)
+    }
+    fn from_b(&self, m: &mut I, n: usize) -> bool {
+        m.advance_b(n, self.b_only)
+    }
+    fn collision(&self, m: &mut I) -> bool {
+        m.advance_a(1, self.both) && m.advance_b(1, false)
+    }
+}
+
 #[cfg(feature = "serde")]
 impl<A: Array> Serialize for VecSet<A>
 where
     A::Item: Serialize,
 {
+    /// Human readable formats get a sequence of elements. Binary formats get a tuple of a flag that the
+    /// elements are sorted and the elements, so deserialization can skip sorting.
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut state = serializer.serialize_seq(Some(self.len()))?;
-        for item in self.iter() {
-            state.serialize_element(&item)?;
+        if serializer.is_human_readable() {
+            let mut state = serializer.serialize_seq(Some(self.len()))?;
+            for item in self.iter() {
+                state.serialize_element(&item)?;
+            }
+            state.end()
+        } else {
+            (true, self.as_slice()).serialize(serializer)
         }
-        state.end()
     }
 }
 
@@ -584,9 +1218,55 @@ where
     A::Item: Deserialize<'de> + Ord,
 {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        deserializer.deserialize_seq(VecSetVisitor {
-            phantom: PhantomData,
-        })
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_seq(VecSetVisitor {
+                phantom: PhantomData,
+            })
+        } else {
+            let (sorted, RawElements(mut values)) =
+                <(bool, RawElements<A>)>::deserialize(deserializer)?;
+            // do not trust the flag, but checking is much cheaper than sorting
+            if !sorted || first_unsorted(&values, |x| x).is_some() {
+                values.sort();
+                values.dedup();
+            }
+            Ok(VecSet(values))
+        }
+    }
+}
+
+/// Elements of a sequence in the order in which they were serialized, used for the binary serde encoding
+#[cfg(feature = "serde")]
+pub(crate) struct RawElements<A: Array>(pub SmallVec<A>);
+
+#[cfg(feature = "serde")]
+impl<'de, A: Array> Deserialize<'de> for RawElements<A>
+where
+    A::Item: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RawElementsVisitor<A>(PhantomData<A>);
+
+        impl<'de, A: Array> Visitor<'de> for RawElementsVisitor<A>
+        where
+            A::Item: Deserialize<'de>,
+        {
+            type Value = RawElements<A>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a sequence")
+            }
+
+            fn visit_seq<B: SeqAccess<'de>>(self, mut seq: B) -> Result<Self::Value, B::Error> {
+                let mut values = SmallVec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(value) = seq.next_element()? {
+                    values.push(value);
+                }
+                Ok(RawElements(values))
+            }
+        }
+
+        deserializer.deserialize_seq(RawElementsVisitor(PhantomData))
     }
 }
 
@@ -626,6 +1306,54 @@ where
 #[repr(transparent)]
 pub struct ArchivedVecSet<T>(rkyv::vec::ArchivedVec<T>);
 
+/// Hashes like a [VecSet] with the same elements, so archived and live sets can be used interchangeably as keys
+#[cfg(feature = "rkyv")]
+impl<T: Hash> Hash for ArchivedVecSet<T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.0.as_slice().hash(state)
+    }
+}
+
+/// Compares like a [VecSet] with the same elements, so archived sets can be nested in archived sets and maps
+#[cfg(feature = "rkyv")]
+impl<T: PartialEq> PartialEq for ArchivedVecSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_slice() == other.0.as_slice()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<T: Eq> Eq for ArchivedVecSet<T> {}
+
+#[cfg(feature = "rkyv")]
+impl<T: PartialOrd> PartialOrd for ArchivedVecSet<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.0.as_slice().partial_cmp(other.0.as_slice())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<T: Ord> Ord for ArchivedVecSet<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.as_slice().cmp(other.0.as_slice())
+    }
+}
+
+/// Allows looking up archived sets by a slice of their elements, e.g. when they are keys of an archived map
+#[cfg(feature = "rkyv")]
+impl<T> Borrow<[T]> for ArchivedVecSet<T> {
+    fn borrow(&self) -> &[T] {
+        self.0.as_slice()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<T: fmt::Debug> fmt::Debug for ArchivedVecSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.iter()).finish()
+    }
+}
+
 #[cfg(feature = "rkyv")]
 impl<A> rkyv::Archive for VecSet<A>
 where
@@ -673,10 +1401,13 @@ where
 #[cfg(feature = "rkyv_validated")]
 #[derive(Debug)]
 pub enum ArchivedVecSetError {
-    /// error with the individual elements of the VecSet
-    ValueCheckError,
+    /// error with the individual elements of the VecSet, with the message of the underlying error
+    ValueCheckError(String),
     /// elements were not properly ordered
-    OrderCheckError,
+    OrderCheckError {
+        /// index of the first element that is not greater than the element before it
+        index: usize,
+    },
 }
 
 #[cfg(feature = "rkyv_validated")]
@@ -685,7 +1416,12 @@ impl std::error::Error for ArchivedVecSetError {}
 #[cfg(feature = "rkyv_validated")]
 impl std::fmt::Display for ArchivedVecSetError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Self::ValueCheckError(cause) => write!(f, "invalid elements: {}", cause),
+            Self::OrderCheckError { index } => {
+                write!(f, "elements are not strictly sorted at index {}", index)
+            }
+        }
     }
 }
 
@@ -694,7 +1430,7 @@ impl<C: ?Sized, T> bytecheck::CheckBytes<C> for ArchivedVecSet<T>
 where
     C: ArchiveContext,
     C::Error: std::error::Error,
-    T: Ord + Archive + CheckBytes<C>,
+    T: Ord + CheckBytes<C>,
     bool: bytecheck::CheckBytes<C>,
 {
     type Error = ArchivedVecSetError;
@@ -704,9 +1440,9 @@ where
     ) -> Result<&'a Self, Self::Error> {
         let values = &(*value).0;
         CheckBytes::check_bytes(values, context)
-            .map_err(|_| ArchivedVecSetError::ValueCheckError)?;
-        if !values.iter().zip(values.iter().skip(1)).all(|(a, b)| a < b) {
-            return Err(ArchivedVecSetError::OrderCheckError);
+            .map_err(|e| ArchivedVecSetError::ValueCheckError(e.to_string()))?;
+        if let Some(index) = first_unsorted(values, |x| x) {
+            return Err(ArchivedVecSetError::OrderCheckError { index });
         };
         Ok(&*value)
     }
@@ -716,7 +1452,53 @@ impl<A: Array> VecSet<A>
 where
     A::Item: Ord + Clone,
 {
+    /// The intersection of any number of sets.
+    ///
+    /// The sets are intersected in order of increasing size, and this stops as soon as the intersection is empty,
+    /// so this is typically much cheaper than intersecting the sets in the given order.
+    ///
+    /// The intersection of zero sets is the empty set.
+    ///
+    /// # Example
+    /// ```
+    /// use vec_collections::VecSet;
+    /// let a: VecSet<[u32; 4]> = (0..1000).collect();
+    /// let b: VecSet<[u32; 4]> = (500..2000).collect();
+    /// let c: VecSet<[u32; 4]> = vec![1, 600, 700].into_iter().collect();
+    /// let r: VecSet<[u32; 4]> = VecSet::intersect_all(vec![&a, &b, &c]);
+    /// assert_eq!(r, vec![600, 700]);
+    /// ```
+    pub fn intersect_all<'a, S, I>(sets: I) -> Self
+    where
+        S: AbstractVecSet<A::Item> + 'a,
+        I: IntoIterator<Item = &'a S>,
+        A::Item: 'a,
+    {
+        let mut sets: Vec<&S> = sets.into_iter().collect();
+        sets.sort_by_key(|set| set.as_slice().len());
+        let mut sets = sets.into_iter();
+        let mut res: Self = match sets.next() {
+            Some(first) => Self::new_unsafe(first.as_slice().iter().cloned().collect()),
+            None => return Self::empty(),
+        };
+        for set in sets {
+            if res.is_empty() {
+                break;
+            }
+            res.intersection_with(set);
+        }
+        res
+    }
+
+    /// Copy all elements within a range into a new set.
+    ///
+    /// This only needs two binary searches and a copy of the elements within the range.
+    pub fn extract_range<R: RangeBounds<A::Item>>(&self, range: R) -> Self {
+        Self::new_unsafe(self.0[self.index_range(range)].iter().cloned().collect())
+    }
+
     pub fn union(&self, that: &impl AbstractVecSet<A::Item>) -> Self {
+        self.check_invariants();
         Self(SmallVecMergeState::merge(
             self.as_slice(),
             that.as_slice(),
@@ -726,6 +1508,7 @@ where
     }
 
     pub fn intersection(&self, that: &impl AbstractVecSet<A::Item>) -> Self {
+        self.check_invariants();
         Self(SmallVecMergeState::merge(
             self.as_slice(),
             that.as_slice(),
@@ -735,6 +1518,7 @@ where
     }
 
     pub fn symmetric_difference(&self, that: &impl AbstractVecSet<A::Item>) -> Self {
+        self.check_invariants();
         Self(SmallVecMergeState::merge(
             self.as_slice(),
             that.as_slice(),
@@ -744,6 +1528,7 @@ where
     }
 
     pub fn difference(&self, that: &impl AbstractVecSet<A::Item>) -> Self {
+        self.check_invariants();
         Self(SmallVecMergeState::merge(
             self.as_slice(),
             that.as_slice(),
@@ -753,29 +1538,68 @@ where
     }
 
     pub fn union_with(&mut self, that: &impl AbstractVecSet<A::Item>) {
-        InPlaceSmallVecMergeStateRef::merge(&mut self.0, &that.as_slice(), SetUnionOp, NoConverter);
-    }
-
-    pub fn intersection_with(&mut self, that: &impl AbstractVecSet<A::Item>) {
+        self.check_invariants();
         InPlaceSmallVecMergeStateRef::merge(
             &mut self.0,
             &that.as_slice(),
-            SetIntersectionOp,
-            NoConverter,
+            SetUnionOp,
+            CloneConverter,
         );
     }
 
-    pub fn xor_with(&mut self, that: &impl AbstractVecSet<A::Item>) {
+    /// In place union with another set, making sure there is space for at least `additional` more elements first.
+    ///
+    /// This is [reserve](VecSet::reserve) followed by [union_with](VecSet::union_with). When merging many batches,
+    /// pass the expected total growth with the first batch, so the following merges do not reallocate.
+    pub fn union_with_capacity_hint(
+        &mut self,
+        that: &impl AbstractVecSet<A::Item>,
+        additional: usize,
+    ) {
+        self.reserve(additional);
+        self.union_with(that);
+    }
+
+    /// In place symmetric difference with another set.
+    pub fn symmetric_difference_with(&mut self, that: &impl AbstractVecSet<A::Item>) {
+        self.check_invariants();
         InPlaceSmallVecMergeStateRef::merge(
             &mut self.0,
             &that.as_slice(),
-            SetIntersectionOp,
-            NoConverter,
+            SetXorOp,
+            CloneConverter,
         );
     }
 
-    pub fn difference_with(&mut self, that: &impl AbstractVecSet<A::Item>) {
-        InPlaceSmallVecMergeStateRef::merge(&mut self.0, &that.as_slice(), SetDiffOpt, NoConverter);
+    /// In place symmetric difference with another set. Same as
+    /// [symmetric_difference_with](VecSet::symmetric_difference_with).
+    pub fn xor_with(&mut self, that: &impl AbstractVecSet<A::Item>) {
+        self.symmetric_difference_with(that)
+    }
+
+    /// In place union with a slice of items in any order, which may contain duplicates.
+    ///
+    /// The items are copied into a temporary set first, which does not allocate for up to 16 items.
+    pub fn union_with_unsorted(&mut self, items: &[A::Item]) {
+        self.union_with(&Self::sorted_probe(items));
+    }
+
+    /// In place intersection with a slice of items in any order, which may contain duplicates.
+    ///
+    /// The items are copied into a temporary set first, which does not allocate for up to 16 items.
+    pub fn intersection_with_unsorted(&mut self, items: &[A::Item]) {
+        self.intersection_with(&Self::sorted_probe(items));
+    }
+
+    /// In place difference with a slice of items in any order, which may contain duplicates.
+    ///
+    /// The items are copied into a temporary set first, which does not allocate for up to 16 items.
+    pub fn difference_with_unsorted(&mut self, items: &[A::Item]) {
+        self.difference_with(&Self::sorted_probe(items));
+    }
+
+    fn sorted_probe(items: &[A::Item]) -> VecSet<[A::Item; 16]> {
+        items.iter().cloned().collect()
     }
 }
 
@@ -797,6 +1621,113 @@ mod test {
         std::mem::drop(sv);
     }
 
+    #[test]
+    fn slice_traits() {
+        use std::borrow::Borrow;
+        let a: VecSet<[u32; 2]> = vec![3, 1, 2].into();
+        assert_eq!(a, vec![1, 2, 3]);
+        assert_eq!(a, &[1u32, 2, 3][..]);
+        assert_eq!(a.first(), Some(&1));
+        let slice: &[u32] = a.borrow();
+        assert_eq!(slice.binary_search(&2), Ok(1));
+        assert!(a.contains(&3));
+    }
+
+    /// A key type that can not be cloned, to make sure that ops that never copy elements do not require Clone
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct NoClone(String);
+
+    fn no_clone(values: &[&str]) -> VecSet<[NoClone; 2]> {
+        values.iter().map(|x| NoClone(x.to_string())).collect()
+    }
+
+    #[test]
+    fn non_clone_elements() {
+        let mut a = no_clone(&["a", "b", "c", "d"]);
+        let b = no_clone(&["b", "c", "d", "e"]);
+        assert!(!a.is_disjoint(&b));
+        assert!(!a.is_subset(&b));
+        assert_eq!(a.relation(&b), SetRelation::Overlapping);
+        assert!(a.contains_all(&no_clone(&["a", "d"])));
+        assert!(a.contains(&NoClone("c".into())));
+        a.intersection_with(&b);
+        a &= &no_clone(&["b", "c"]);
+        a -= &no_clone(&["c"]);
+        a.difference_with(&no_clone(&["x"]));
+        a &= no_clone(&["b", "c"]);
+        a |= no_clone(&["z"]);
+        a ^= no_clone(&["z", "y"]);
+        a -= no_clone(&["y"]);
+        assert!(a.insert(NoClone("q".into())));
+        assert!(a.remove(&NoClone("q".into())));
+        a.retain(|x| x.0 != "x");
+        assert_eq!(a, no_clone(&["b"]));
+    }
+
+    #[test]
+    #[cfg(all(debug_assertions, feature = "debug_invariants"))]
+    #[should_panic(expected = "invariant violated")]
+    fn check_invariants() {
+        let a = Test::new_unsafe(SmallVec::from_vec(vec![2, 1]));
+        a.contains(&1);
+    }
+
+    #[test]
+    fn clone_from_reuses_allocation() {
+        let mut a: VecSet<[u32; 2]> = (0..100).collect();
+        let b: VecSet<[u32; 2]> = (10..60).collect();
+        let ptr = a.as_slice().as_ptr();
+        a.clone_from(&b);
+        assert_eq!(a, b);
+        assert_eq!(a.as_slice().as_ptr(), ptr);
+    }
+
+    #[test]
+    fn union_with_capacity_hint() {
+        let mut a: VecSet<[u32; 2]> = (0..100).map(|x| x * 10).collect();
+        let batches: Vec<VecSet<[u32; 2]>> = (1..10)
+            .map(|i| (0..100).map(|x| x * 10 + i).collect())
+            .collect();
+        a.union_with_capacity_hint(&batches[0], 900);
+        let ptr = a.as_slice().as_ptr();
+        for batch in &batches[1..] {
+            a.union_with(batch);
+        }
+        assert_eq!(a, (0..1000).collect::<VecSet<[u32; 2]>>());
+        // the merges used the reserved capacity
+        assert_eq!(a.as_slice().as_ptr(), ptr);
+    }
+
+    #[test]
+    fn replace_keeps_or_displaces() {
+        /// ordered by the first field only
+        #[derive(Debug)]
+        struct Tagged(u32, &'static str);
+        impl PartialEq for Tagged {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+        impl Eq for Tagged {}
+        impl PartialOrd for Tagged {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Tagged {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+        let mut a: VecSet<[Tagged; 2]> = VecSet::empty();
+        assert_eq!(a.get_or_insert(Tagged(1, "first")).1, "first");
+        assert_eq!(a.get_or_insert(Tagged(1, "second")).1, "first");
+        assert!(a.replace(Tagged(2, "first")).is_none());
+        assert_eq!(a.replace(Tagged(1, "third")).map(|x| x.1), Some("first"));
+        let tags: Vec<_> = a.iter().map(|x| x.1).collect();
+        assert_eq!(tags, vec!["third", "first"]);
+    }
+
     impl<T: Arbitrary + Ord + Copy + Default + fmt::Debug> Arbitrary for VecSet<[T; 2]> {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
             Self::from_vec(Arbitrary::arbitrary(g))
@@ -827,20 +1758,36 @@ mod test {
         #[cfg(feature = "serde")]
         fn serde_roundtrip(reference: Test) -> bool {
             let bytes = serde_json::to_vec(&reference).unwrap();
-            let deser = serde_json::from_slice(&bytes).unwrap();
+            let deser: Test = serde_json::from_slice(&bytes).unwrap();
             reference == deser
         }
 
+        #[cfg(feature = "serde")]
+        fn serde_binary_roundtrip(reference: Test) -> bool {
+            let bytes = serde_cbor::to_vec(&reference).unwrap();
+            let deser: Test = serde_cbor::from_slice(&bytes).unwrap();
+            // an unsorted sequence is sorted on deserialization
+            let unsorted: Vec<i64> = reference.iter().rev().cloned().collect();
+            let bytes = serde_cbor::to_vec(&(false, unsorted)).unwrap();
+            let deser_unsorted: Test = serde_cbor::from_slice(&bytes).unwrap();
+            reference == deser && reference == deser_unsorted
+        }
+
         #[cfg(feature = "rkyv")]
         fn rkyv_roundtrip_unvalidated(a: Test) -> bool {
             use rkyv::*;
             use ser::Serializer;
+            fn hash_of(x: &impl Hash) -> u64 {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                x.hash(&mut hasher);
+                hash::Hasher::finish(&hasher)
+            }
             let mut serializer = ser::serializers::AllocSerializer::<256>::default();
             serializer.serialize_value(&a).unwrap();
             let bytes = serializer.into_serializer().into_inner();
             let archived = unsafe { rkyv::archived_root::<Test>(&bytes) };
             let deserialized: Test = archived.deserialize(&mut Infallible).unwrap();
-            a == deserialized
+            a == deserialized && hash_of(&a) == hash_of(archived)
         }
 
         #[cfg(feature = "rkyv_validated")]
@@ -856,6 +1803,49 @@ mod test {
             a == deserialized
         }
 
+        #[cfg(feature = "rkyv_validated")]
+        #[quickcheck]
+        fn rkyv_validation_error(a: Vec<i64>) -> bool {
+            use rkyv::{ser::Serializer, validation::CheckArchiveError};
+            // a vec has the same archived representation as a set, but does not have to be sorted
+            let mut serializer = rkyv::ser::serializers::AllocSerializer::<256>::default();
+            serializer.serialize_value(&a).unwrap();
+            let bytes = serializer.into_serializer().into_inner();
+            match (rkyv::check_archived_root::<Test>(&bytes), first_unsorted(&a, |x| x)) {
+                (Ok(archived), None) => archived.as_slice() == &a[..],
+                (Err(CheckArchiveError::CheckBytesError(ArchivedVecSetError::OrderCheckError { index })), Some(expected)) => {
+                    index == expected
+                }
+                _ => false,
+            }
+        }
+
+        #[cfg(feature = "rkyv_validated")]
+        #[quickcheck]
+        fn rkyv_validated_strings(a: BTreeSet<String>) -> bool {
+            use rkyv::*;
+            use ser::Serializer;
+            // archived strings and boxed strs must be ordered exactly like the unarchived values
+            fn roundtrip<T>(a: &VecSet<[T; 2]>) -> bool
+            where
+                T: Archive + Serialize<ser::serializers::AllocSerializer<256>> + core::ops::Deref<Target = str>,
+                T::Archived: Ord
+                    + core::ops::Deref<Target = str>
+                    + for<'a> bytecheck::CheckBytes<validation::validators::DefaultValidator<'a>>,
+            {
+                let mut serializer = ser::serializers::AllocSerializer::<256>::default();
+                serializer.serialize_value(a).unwrap();
+                let bytes = serializer.into_serializer().into_inner();
+                match rkyv::check_archived_root::<VecSet<[T; 2]>>(&bytes) {
+                    Ok(archived) => a.iter().map(|x| &**x).eq(archived.as_slice().iter().map(|x| &**x)),
+                    Err(_) => false,
+                }
+            }
+            let strings: VecSet<[String; 2]> = a.iter().cloned().collect();
+            let boxed: VecSet<[Box<str>; 2]> = a.iter().map(|x| x.clone().into_boxed_str()).collect();
+            roundtrip(&strings) && roundtrip(&boxed)
+        }
+
         fn is_disjoint_sample(a: Test, b: Test) -> bool {
             binary_property_test(&a, &b, a.is_disjoint(&b), |a, b| !(a & b))
         }
@@ -880,6 +1870,47 @@ mod test {
             binary_element_test(&a, &b, &a - &b, |a, b| a & !b)
         }
 
+        fn retain_extract_if(a: Reference) -> bool {
+            let mut retained: Test = a.iter().cloned().collect();
+            let mut extracted = retained.clone();
+            let removed = retained.retain(|x| x % 3 == 0);
+            let extracted_part = extracted.extract_if(|x| x % 3 != 0);
+            retained == extracted
+                && removed == extracted_part.len()
+                && extracted_part.iter().all(|x| x % 3 != 0)
+                && &retained | &extracted_part == a.iter().cloned().collect::<Test>()
+        }
+
+        fn repair(a: Vec<i64>) -> bool {
+            let mut set = Test::new_unsafe(a.iter().cloned().collect());
+            let sorted = first_unsorted(&a, |x| x).is_none();
+            set.repair() != sorted && set == a.into_iter().collect::<Test>()
+        }
+
+        fn partition(a: Reference) -> bool {
+            let set: Test = a.iter().cloned().collect();
+            let (even, odd) = set.partition(|x| x % 2 == 0);
+            let (expected_even, expected_odd): (Reference, Reference) = a.into_iter().partition(|x| x % 2 == 0);
+            even.iter().eq(expected_even.iter()) && odd.iter().eq(expected_odd.iter())
+        }
+
+        fn extract_remove_range(a: Reference, from: i64, to: i64) -> bool {
+            let set: Test = a.iter().cloned().collect();
+            let check = |range: (Bound<i64>, Bound<i64>)| {
+                let expected: Test = a.iter().filter(|x| range.contains(*x)).cloned().collect();
+                let mut removed = set.clone();
+                let n = removed.remove_range(range);
+                set.extract_range(range) == expected
+                    && n == expected.len()
+                    && &removed | &expected == set
+                    && removed.iter().all(|x| !range.contains(x))
+            };
+            check((Bound::Included(from), Bound::Excluded(to)))
+                && check((Bound::Excluded(from), Bound::Included(to)))
+                && check((Bound::Unbounded, Bound::Included(to)))
+                && check((Bound::Excluded(from), Bound::Unbounded))
+        }
+
         fn union(a: Reference, b: Reference) -> bool {
             let mut a1: Test = a.iter().cloned().collect();
             let b1: Test = b.iter().cloned().collect();
@@ -933,6 +1964,192 @@ mod test {
             expected == actual
         }
 
+        fn intersect_all(sets: Vec<Reference>) -> bool {
+            let sets1: Vec<Test> = sets.iter().map(|x| x.iter().cloned().collect()).collect();
+            let expected = sets
+                .iter()
+                .skip(1)
+                .fold(sets.first().cloned().unwrap_or_default(), |a, b| &a & b);
+            let actual: Test = VecSet::intersect_all(&sets1);
+            actual.iter().eq(expected.iter())
+        }
+
+        fn relation(a: Reference, b: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let expected = if a == b {
+                SetRelation::Equal
+            } else if a.is_subset(&b) {
+                SetRelation::Subset
+            } else if a.is_superset(&b) {
+                SetRelation::Superset
+            } else if a.is_disjoint(&b) {
+                SetRelation::Disjoint
+            } else {
+                SetRelation::Overlapping
+            };
+            a1.relation(&b1) == expected
+        }
+
+        fn iter_double_ended(a: Reference, n: usize) -> bool {
+            let set: Test = a.iter().cloned().collect();
+            let n = n % (a.len() + 2);
+            set.iter().rev().eq(a.iter().rev())
+                && set.iter().nth(n) == a.iter().nth(n)
+                && set.iter().nth_back(n) == a.iter().nth_back(n)
+                && set.iter().rev().step_by(2).eq(a.iter().rev().step_by(2))
+        }
+
+        fn ord_is_lexicographic(a: Reference, b: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let a: Vec<i64> = a.into_iter().collect();
+            let b: Vec<i64> = b.into_iter().collect();
+            a1.cmp(&b1) == a.cmp(&b)
+                && a1.cmp_by_len_then_lex(&b1) == a.len().cmp(&b.len()).then_with(|| a.cmp(&b))
+        }
+
+        fn try_from_sorted(a: Vec<i64>) -> bool {
+            let elements: SmallVec<[i64; 2]> = a.iter().cloned().collect();
+            let sorted = a.windows(2).all(|w| w[0] < w[1]);
+            match Test::try_from_sorted(elements) {
+                Ok(set) => sorted && set.as_slice() == a.as_slice(),
+                Err(e) => !sorted && a[e.index() - 1] >= a[e.index()] && e.into_inner().as_slice() == a.as_slice(),
+            }
+        }
+
+        fn pop_first_last(a: Reference) -> bool {
+            let mut actual: Test = a.iter().cloned().collect();
+            let mut expected = a;
+            let mut res = true;
+            while !expected.is_empty() {
+                res &= actual.pop_first() == expected.pop_first();
+                res &= actual.pop_last() == expected.pop_last();
+            }
+            res && actual.pop_first().is_none() && actual.pop_last().is_none()
+        }
+
+        fn replace_get_or_insert(a: Reference, b: Vec<i64>) -> bool {
+            let mut actual: Test = a.iter().cloned().collect();
+            let mut expected = a;
+            let mut res = true;
+            for x in b {
+                res &= actual.replace(x) == expected.replace(x);
+                res &= *actual.get_or_insert(x + 1) == x + 1;
+                expected.insert(x + 1);
+            }
+            res && actual.iter().eq(expected.iter())
+        }
+
+        fn union_with(a: Reference, b: Reference) -> bool {
+            let mut a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            a1.union_with(&b1);
+            let expected: Test = a.union(&b).cloned().collect();
+            a1 == expected
+        }
+
+        fn with_unsorted(a: Reference, b: Vec<i64>) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let b1: Test = b.iter().cloned().collect();
+            let mut union = a1.clone();
+            union.union_with_unsorted(&b);
+            let mut intersection = a1.clone();
+            intersection.intersection_with_unsorted(&b);
+            let mut difference = a1.clone();
+            difference.difference_with_unsorted(&b);
+            union == &a1 | &b1 && intersection == &a1 & &b1 && difference == &a1 - &b1
+        }
+
+        fn slice_operand(a: Test, b: Test) -> bool {
+            let slice: &[i64] = b.as_slice();
+            let mut in_place = a.clone();
+            in_place.union_with(&slice);
+            a.union(&slice) == &a | &b
+                && in_place == &a | &b
+                && a.is_subset(&slice) == a.is_subset(&b)
+                && a.relation(&slice) == a.relation(&b)
+        }
+
+        fn union_with_std(a: Reference, b: Reference) -> bool {
+            let a1: Test = a.iter().cloned().collect();
+            let expected: Test = a.union(&b).cloned().collect();
+            let mut from_iter = a1.clone();
+            from_iter.union_with_iter(b.iter().rev().cloned());
+            let mut from_btree = a1.clone();
+            from_btree |= b.clone();
+            let mut from_hash = a1;
+            from_hash |= b.iter().cloned().collect::<HashSet<_>>();
+            from_iter == expected && from_btree == expected && from_hash == expected
+        }
+
+        fn partition_point(a: Reference, value: i64) -> bool {
+            let set: Test = a.iter().cloned().collect();
+            let archived: &[i64] = set.as_slice();
+            let lower = AbstractVecSet::partition_point(&set, |x| *x < value);
+            lower == a.range(..value).count()
+                && AbstractVecSet::partition_point(&archived, |x| *x < value) == lower
+                && AbstractVecSet::binary_search_by(&set, |x| x.cmp(&value)) == a.contains(&value).then_some(lower).ok_or(lower)
+        }
+
+        fn search_by_key(a: Reference, value: i64) -> bool {
+            // small values, so that lookups hit and sets are both below and above the linear search length
+            let elements: Vec<i64> = a.iter().map(|x| x % 64).collect::<BTreeSet<_>>().into_iter().collect();
+            let value = value % 64;
+            let set: Test = elements.iter().cloned().collect();
+            super::search_by_key(&elements, &value, |x| x) == elements.binary_search(&value)
+                && set.contains(&value) == elements.contains(&value)
+        }
+
+        fn cast_storage(a: Reference) -> bool {
+            let set: Test = a.iter().cloned().collect();
+            let ptr = set.as_slice().as_ptr();
+            let spilled = set.len() > 2;
+            let wide: VecSet<[i64; 4]> = set.clone().cast_storage();
+            // more elements than the inline capacity of the target, so a spilled buffer must be reused
+            let narrow: VecSet<[i64; 1]> = set.cast_storage();
+            wide.iter().eq(a.iter())
+                && narrow.iter().eq(a.iter())
+                && (!spilled || narrow.as_slice().as_ptr() == ptr)
+        }
+
+        fn iter_after(a: Reference, value: i64) -> bool {
+            use std::ops::Bound;
+            let set: Test = a.iter().cloned().collect();
+            set.iter_after(&value).eq(a.range((Bound::Excluded(value), Bound::Unbounded)))
+        }
+
+        fn in_place_ops(a: Test, b: Test) -> bool {
+            let in_place = |f: fn(&mut Test, &Test)| {
+                let mut r = a.clone();
+                f(&mut r, &b);
+                r
+            };
+            in_place(|a, b| a.union_with(b)) == a.union(&b)
+                && in_place(|a, b| a.intersection_with(b)) == a.intersection(&b)
+                && in_place(|a, b| a.difference_with(b)) == a.difference(&b)
+                && in_place(|a, b| a.symmetric_difference_with(b)) == a.symmetric_difference(&b)
+                && in_place(|a, b| a.xor_with(b)) == a.symmetric_difference(&b)
+        }
+
+        #[cfg(feature = "rand")]
+        fn sample(a: Test, k: usize, seed: u64) -> bool {
+            use rand::SeedableRng;
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let sample: Vec<i64> = a.sample(&mut rng, k).cloned().collect();
+            let chosen = a.choose(&mut rng);
+            sample.len() == k.min(a.len())
+                && sample.windows(2).all(|w| w[0] < w[1])
+                && sample.iter().all(|x| a.contains(x))
+                && chosen.map_or(a.is_empty(), |x| a.contains(x))
+        }
+
+        fn contains_all_any(a: Test, b: Test) -> bool {
+            let probes = b.as_slice();
+            a.contains_all(probes) == probes.iter().all(|x| a.contains(x))
+                && a.contains_any(probes) == probes.iter().any(|x| a.contains(x))
+        }
+
         fn is_subset(a: Reference, b: Reference) -> bool {
             let a1: Test = a.iter().cloned().collect();
             let b1: Test = b.iter().cloned().collect();